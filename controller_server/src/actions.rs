@@ -1,8 +1,14 @@
 use core::str;
 
+use byteorder::{BigEndian, ByteOrder};
 use num_enum::TryFromPrimitive;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    air_mouse::AirMouseSettings,
+    screen_preview::{PreviewFormat, PreviewSettings},
+};
+
 /// This macro generates 2 separate enums:
 ///
 /// `ActionType` - Used to convert u8 into an ActionType
@@ -56,6 +62,28 @@ define_actions!(
     TerminalCommand(TerminalCommand) = 7,
     MouseDown(Button) = 8,
     MouseUp(Button) = 9,
+    SetVolume(u8) = 10,
+    RequestDesktopState = 11,
+    Media(MediaCommand) = 12,
+    SetBrightness(u8) = 13,
+    Power(PowerCommand) = 14,
+    Gamepad(GamepadEvent) = 15,
+    Gesture(GestureEvent) = 16,
+    Pen(PenEvent) = 17,
+    RunMacro(String) = 18,
+    ListMacros = 19,
+    RunShortcut(String) = 20,
+    ListShortcuts = 21,
+    Presentation(PresentationCommand) = 22,
+    AirMouse(AirMouseEvent) = 23,
+    Window(WindowCommand) = 24,
+    ListApps(String) = 25,
+    LaunchApp(String) = 26,
+    FileTransfer(FileTransferCommand) = 27,
+    OpenUrl(String) = 28,
+    MirrorNotifications(bool) = 29,
+    ScreenPreview(PreviewCommand) = 30,
+    Monitor(MonitorCommand) = 31,
 );
 
 /// Action struct is defined by the define_macros! macro
@@ -92,9 +120,61 @@ impl Action {
                 Self::MouseDown(DeserializableAction::from_bytes(encoded))
             }
             Some(ActionType::MouseUp) => Self::MouseUp(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::SetVolume) => {
+                let volume = encoded[0].min(100);
+                *encoded = &encoded[1..];
+                Self::SetVolume(volume)
+            }
+            Some(ActionType::RequestDesktopState) => Self::RequestDesktopState,
+            Some(ActionType::Media) => Self::Media(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Power) => Self::Power(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Gamepad) => Self::Gamepad(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Gesture) => Self::Gesture(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Pen) => Self::Pen(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::RunMacro) => Self::RunMacro(decode_short_string(encoded)),
+            Some(ActionType::ListMacros) => Self::ListMacros,
+            Some(ActionType::RunShortcut) => Self::RunShortcut(decode_short_string(encoded)),
+            Some(ActionType::ListShortcuts) => Self::ListShortcuts,
+            Some(ActionType::AirMouse) => Self::AirMouse(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Window) => Self::Window(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::ListApps) => Self::ListApps(decode_short_string(encoded)),
+            Some(ActionType::LaunchApp) => Self::LaunchApp(decode_short_string(encoded)),
+            Some(ActionType::OpenUrl) => Self::OpenUrl(decode_long_string(encoded)),
+            Some(ActionType::MirrorNotifications) => {
+                let subscribe = encoded[0] != 0;
+                *encoded = &encoded[1..];
+                Self::MirrorNotifications(subscribe)
+            }
+            Some(ActionType::FileTransfer) => {
+                Self::FileTransfer(DeserializableAction::from_bytes(encoded))
+            }
+            Some(ActionType::ScreenPreview) => {
+                Self::ScreenPreview(DeserializableAction::from_bytes(encoded))
+            }
+            Some(ActionType::Monitor) => Self::Monitor(DeserializableAction::from_bytes(encoded)),
+            Some(ActionType::Presentation) => {
+                Self::Presentation(DeserializableAction::from_bytes(encoded))
+            }
+            Some(ActionType::SetBrightness) => {
+                let brightness = encoded[0].min(100);
+                *encoded = &encoded[1..];
+                Self::SetBrightness(brightness)
+            }
             None => unreachable!("Action type not recognized!"),
         }
     }
+
+    /// Whether the bytes end in the middle of an action, which must wait for the
+    /// rest to arrive before being decoded. Actions are expected to come in a single
+    /// read, except for file transfers, whose chunks are bigger than a read.
+    pub fn is_incomplete(encoded: &[u8]) -> bool {
+        match encoded.split_first() {
+            Some((&action_type, rest)) if action_type == ActionType::FileTransfer as u8 => {
+                FileTransferCommand::encoded_len(rest).is_none_or(|len| len > rest.len())
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Any data associated to any action must implement this trait.
@@ -125,6 +205,680 @@ impl DeserializableAction for TerminalCommand {
     }
 }
 
+/// Reads a string prefixed by its length in a single byte
+fn decode_short_string(bytes: &mut &[u8]) -> String {
+    let size = bytes[0] as usize;
+    let string = String::from_utf8_lossy(&bytes[1..size + 1]).into_owned();
+    *bytes = &bytes[(size + 1)..];
+    string
+}
+
+/// Reads a string prefixed by its u16 big-endian length, for strings that may
+/// not fit in 255 bytes
+fn decode_long_string(bytes: &mut &[u8]) -> String {
+    let size = BigEndian::read_u16(bytes) as usize;
+    let string = String::from_utf8_lossy(&bytes[2..size + 2]).into_owned();
+    *bytes = &bytes[(size + 2)..];
+    string
+}
+
+/// Playback commands sent to a media player
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
+pub enum MediaCommand {
+    PlayPause,
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    /// Relative seek in milliseconds
+    Seek(i32),
+    /// Absolute position in the current track in milliseconds
+    SetPosition(u32),
+    SetShuffle(bool),
+    /// Ask the server for the list of running players
+    ListPlayers,
+    /// Target a specific player, by id, for the following commands
+    SelectPlayer(String),
+    /// A command this server doesn't know, ignored
+    Unknown,
+}
+
+impl DeserializableAction for MediaCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        match command {
+            0 => MediaCommand::PlayPause,
+            1 => MediaCommand::Play,
+            2 => MediaCommand::Pause,
+            3 => MediaCommand::Stop,
+            4 => MediaCommand::Next,
+            5 => MediaCommand::Previous,
+            6 => {
+                let offset = BigEndian::read_i32(bytes);
+                *bytes = &bytes[4..];
+                MediaCommand::Seek(offset)
+            }
+            7 => {
+                let position = BigEndian::read_u32(bytes);
+                *bytes = &bytes[4..];
+                MediaCommand::SetPosition(position)
+            }
+            8 => {
+                let shuffle = bytes[0] != 0;
+                *bytes = &bytes[1..];
+                MediaCommand::SetShuffle(shuffle)
+            }
+            9 => MediaCommand::ListPlayers,
+            10 => MediaCommand::SelectPlayer(decode_short_string(bytes)),
+            _ => {
+                log::warn!("Unknown media command: {}, ignoring", command);
+                MediaCommand::Unknown
+            }
+        }
+    }
+}
+
+/// Power management commands
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum PowerCommand {
+    LockScreen,
+    Suspend,
+    Hibernate,
+    Reboot,
+    LogOut,
+    Shutdown,
+    /// Shut down after the given number of minutes
+    ScheduleShutdown(u16),
+    CancelScheduledShutdown,
+}
+
+impl DeserializableAction for PowerCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        match command {
+            0 => PowerCommand::LockScreen,
+            1 => PowerCommand::Suspend,
+            2 => PowerCommand::Hibernate,
+            3 => PowerCommand::Reboot,
+            4 => PowerCommand::LogOut,
+            5 => PowerCommand::Shutdown,
+            6 => {
+                let minutes = BigEndian::read_u16(bytes);
+                *bytes = &bytes[2..];
+                PowerCommand::ScheduleShutdown(minutes)
+            }
+            7 => PowerCommand::CancelScheduledShutdown,
+            _ => {
+                // never guess a destructive command
+                log::warn!("Unknown power command: {}, ignoring", command);
+                PowerCommand::CancelScheduledShutdown
+            }
+        }
+    }
+}
+
+/// Buttons of the virtual gamepad, laid out like an Xbox controller
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum GamepadButton {
+    A = 0,
+    B = 1,
+    X = 2,
+    Y = 3,
+    LeftBumper = 4,
+    RightBumper = 5,
+    Select = 6,
+    Start = 7,
+    Mode = 8,
+    LeftThumb = 9,
+    RightThumb = 10,
+    DpadUp = 11,
+    DpadDown = 12,
+    DpadLeft = 13,
+    DpadRight = 14,
+}
+
+impl GamepadButton {
+    fn from_u8(button: u8) -> Option<Self> {
+        Some(match button {
+            0 => GamepadButton::A,
+            1 => GamepadButton::B,
+            2 => GamepadButton::X,
+            3 => GamepadButton::Y,
+            4 => GamepadButton::LeftBumper,
+            5 => GamepadButton::RightBumper,
+            6 => GamepadButton::Select,
+            7 => GamepadButton::Start,
+            8 => GamepadButton::Mode,
+            9 => GamepadButton::LeftThumb,
+            10 => GamepadButton::RightThumb,
+            11 => GamepadButton::DpadUp,
+            12 => GamepadButton::DpadDown,
+            13 => GamepadButton::DpadLeft,
+            14 => GamepadButton::DpadRight,
+            _ => return None,
+        })
+    }
+}
+
+/// Which stick or trigger a gamepad event refers to
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum GamepadSide {
+    Left,
+    Right,
+}
+
+/// Input for the calling client's virtual gamepad
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum GamepadEvent {
+    Button {
+        button: GamepadButton,
+        pressed: bool,
+    },
+    /// Stick position, each axis from -32768 to 32767. Positive y points down.
+    Stick { stick: GamepadSide, x: i16, y: i16 },
+    /// Analog trigger, from 0 (released) to 255 (fully pressed)
+    Trigger { trigger: GamepadSide, value: u8 },
+    /// Releases every button and centers the sticks and triggers
+    Reset,
+}
+
+impl DeserializableAction for GamepadEvent {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let event = bytes[0];
+        *bytes = &bytes[1..];
+        let side = |byte: u8| {
+            if byte == 0 {
+                GamepadSide::Left
+            } else {
+                GamepadSide::Right
+            }
+        };
+        match event {
+            0 => {
+                let (button, pressed) = (bytes[0], bytes[1] != 0);
+                *bytes = &bytes[2..];
+                match GamepadButton::from_u8(button) {
+                    Some(button) => GamepadEvent::Button { button, pressed },
+                    None => {
+                        log::warn!("Unknown gamepad button: {}, releasing all", button);
+                        GamepadEvent::Reset
+                    }
+                }
+            }
+            1 => {
+                let stick = side(bytes[0]);
+                let x = BigEndian::read_i16(&bytes[1..]);
+                let y = BigEndian::read_i16(&bytes[3..]);
+                *bytes = &bytes[5..];
+                GamepadEvent::Stick { stick, x, y }
+            }
+            2 => {
+                let (trigger, value) = (side(bytes[0]), bytes[1]);
+                *bytes = &bytes[2..];
+                GamepadEvent::Trigger { trigger, value }
+            }
+            3 => GamepadEvent::Reset,
+            _ => {
+                // releasing is safer than leaving a button stuck
+                log::warn!("Unknown gamepad event: {}, releasing all", event);
+                GamepadEvent::Reset
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Touch gestures recognized by the phone
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum GestureEvent {
+    Swipe {
+        fingers: u8,
+        direction: SwipeDirection,
+    },
+    /// Pinch by a number of steps: positive when the fingers spread apart (zoom in),
+    /// negative when they move together (zoom out)
+    Pinch {
+        steps: i8,
+    },
+    Rotate {
+        clockwise: bool,
+    },
+    Tap {
+        fingers: u8,
+    },
+}
+
+impl DeserializableAction for GestureEvent {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let gesture = bytes[0];
+        *bytes = &bytes[1..];
+        match gesture {
+            0 => {
+                let (fingers, direction) = (bytes[0], bytes[1]);
+                *bytes = &bytes[2..];
+                let direction = match direction {
+                    0 => SwipeDirection::Up,
+                    1 => SwipeDirection::Down,
+                    2 => SwipeDirection::Left,
+                    _ => SwipeDirection::Right,
+                };
+                GestureEvent::Swipe { fingers, direction }
+            }
+            1 => {
+                let steps = bytes[0] as i8;
+                *bytes = &bytes[1..];
+                GestureEvent::Pinch { steps }
+            }
+            2 => {
+                let clockwise = bytes[0] != 0;
+                *bytes = &bytes[1..];
+                GestureEvent::Rotate { clockwise }
+            }
+            3 => {
+                let fingers = bytes[0];
+                *bytes = &bytes[1..];
+                GestureEvent::Tap { fingers }
+            }
+            _ => {
+                log::warn!("Unknown gesture: {}, ignoring", gesture);
+                GestureEvent::Pinch { steps: 0 }
+            }
+        }
+    }
+}
+
+/// Stylus state on the phone screen, used as a graphics tablet
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub struct PenEvent {
+    /// Position across the screen, from 0 to 65535 on both axes
+    pub x: u16,
+    pub y: u16,
+    /// From 0 to 65535, only meaningful while touching
+    pub pressure: u16,
+    /// Tilt in degrees, from -90 to 90
+    pub tilt_x: i8,
+    pub tilt_y: i8,
+    /// The pen is touching or hovering close to the screen
+    pub in_range: bool,
+    pub touching: bool,
+    /// The side button of the pen is held
+    pub barrel_button: bool,
+}
+
+const PEN_IN_RANGE: u8 = 0b001;
+const PEN_TOUCHING: u8 = 0b010;
+const PEN_BARREL_BUTTON: u8 = 0b100;
+
+impl DeserializableAction for PenEvent {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let flags = bytes[8];
+        let pen = PenEvent {
+            x: BigEndian::read_u16(bytes),
+            y: BigEndian::read_u16(&bytes[2..]),
+            pressure: BigEndian::read_u16(&bytes[4..]),
+            tilt_x: bytes[6] as i8,
+            tilt_y: bytes[7] as i8,
+            in_range: flags & PEN_IN_RANGE != 0,
+            touching: flags & PEN_TOUCHING != 0,
+            barrel_button: flags & PEN_BARREL_BUTTON != 0,
+        };
+        *bytes = &bytes[9..];
+        pen
+    }
+}
+
+/// Slide control and laser pointer for presentations
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum PresentationCommand {
+    /// Starts the slideshow and its timer from the first slide
+    Start,
+    Stop,
+    Next,
+    Previous,
+    First,
+    /// Blanks the screen, or brings the slide back
+    ToggleBlank,
+    /// Moves the laser pointer by the phone's rotation since the last sample,
+    /// in hundredths of a degree. Shows the pointer if it is hidden.
+    Laser {
+        dx: i16,
+        dy: i16,
+    },
+    LaserOff,
+    /// Switches the keys sent to the presentation app, e.g. `libreoffice` or `pdf`
+    SelectProfile(String),
+    /// Asks for the current slide, timer and profile
+    Status,
+}
+
+impl DeserializableAction for PresentationCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        match command {
+            0 => PresentationCommand::Start,
+            1 => PresentationCommand::Stop,
+            2 => PresentationCommand::Next,
+            3 => PresentationCommand::Previous,
+            4 => PresentationCommand::First,
+            5 => PresentationCommand::ToggleBlank,
+            6 => {
+                let dx = BigEndian::read_i16(bytes);
+                let dy = BigEndian::read_i16(&bytes[2..]);
+                *bytes = &bytes[4..];
+                PresentationCommand::Laser { dx, dy }
+            }
+            7 => PresentationCommand::LaserOff,
+            8 => PresentationCommand::SelectProfile(decode_short_string(bytes)),
+            9 => PresentationCommand::Status,
+            _ => {
+                log::warn!("Unknown presentation command: {}, ignoring", command);
+                PresentationCommand::Status
+            }
+        }
+    }
+}
+
+/// Gyroscope input of the air mouse mode
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum AirMouseEvent {
+    /// Turn rates of the phone in tenths of a degree per second, and the time since
+    /// the previous sample. Positive yaw turns right, positive pitch tilts up.
+    Sample {
+        yaw: i16,
+        pitch: i16,
+        interval_ms: u8,
+    },
+    /// Stops the cursor and recalibrates the gyroscope while the phone is held still
+    Recenter,
+    Settings(AirMouseSettings),
+}
+
+impl DeserializableAction for AirMouseEvent {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let event = bytes[0];
+        *bytes = &bytes[1..];
+        match event {
+            0 => {
+                let sample = AirMouseEvent::Sample {
+                    yaw: BigEndian::read_i16(bytes),
+                    pitch: BigEndian::read_i16(&bytes[2..]),
+                    interval_ms: bytes[4],
+                };
+                *bytes = &bytes[5..];
+                sample
+            }
+            1 => AirMouseEvent::Recenter,
+            2 => {
+                let settings = AirMouseSettings {
+                    dead_zone: bytes[0],
+                    smoothing: bytes[1],
+                    sensitivity: bytes[2],
+                };
+                *bytes = &bytes[3..];
+                AirMouseEvent::Settings(settings)
+            }
+            _ => {
+                log::warn!("Unknown air mouse event: {}, ignoring", event);
+                AirMouseEvent::Sample {
+                    yaw: 0,
+                    pitch: 0,
+                    interval_ms: 0,
+                }
+            }
+        }
+    }
+}
+
+/// Window management. Window `0` stands for the focused window.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum WindowCommand {
+    /// Asks for the window list
+    List,
+    Focus(u32),
+    Minimize(u32),
+    ToggleMaximize(u32),
+    Close(u32),
+    MoveToWorkspace {
+        window: u32,
+        workspace: u8,
+    },
+    MoveToMonitor {
+        window: u32,
+        monitor: u8,
+    },
+}
+
+impl WindowCommand {
+    /// The window the command acts on
+    pub fn window(&self) -> Option<u32> {
+        match self {
+            WindowCommand::List => None,
+            WindowCommand::Focus(window)
+            | WindowCommand::Minimize(window)
+            | WindowCommand::ToggleMaximize(window)
+            | WindowCommand::Close(window)
+            | WindowCommand::MoveToWorkspace { window, .. }
+            | WindowCommand::MoveToMonitor { window, .. } => Some(*window),
+        }
+    }
+}
+
+impl DeserializableAction for WindowCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        if command == 0 {
+            return WindowCommand::List;
+        }
+
+        let window = BigEndian::read_u32(bytes);
+        *bytes = &bytes[4..];
+        match command {
+            1 => WindowCommand::Focus(window),
+            2 => WindowCommand::Minimize(window),
+            3 => WindowCommand::ToggleMaximize(window),
+            4 => WindowCommand::Close(window),
+            5 | 6 => {
+                let target = bytes[0];
+                *bytes = &bytes[1..];
+                if command == 5 {
+                    WindowCommand::MoveToWorkspace {
+                        window,
+                        workspace: target,
+                    }
+                } else {
+                    WindowCommand::MoveToMonitor {
+                        window,
+                        monitor: target,
+                    }
+                }
+            }
+            _ => {
+                log::warn!("Unknown window command: {}, ignoring", command);
+                WindowCommand::List
+            }
+        }
+    }
+}
+
+/// Preview of the screen around the cursor, see `screen_preview`
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum PreviewCommand {
+    /// Starts streaming to the client, or changes the settings of its stream
+    Start(PreviewSettings),
+    Stop,
+}
+
+impl DeserializableAction for PreviewCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        match command {
+            0 => {
+                let settings = PreviewSettings {
+                    monitor: bytes[0],
+                    format: match bytes[1] {
+                        1 => PreviewFormat::Png,
+                        _ => PreviewFormat::Jpeg,
+                    },
+                    fps: bytes[2],
+                    width: BigEndian::read_u16(&bytes[3..]),
+                    height: BigEndian::read_u16(&bytes[5..]),
+                    scale: bytes[7],
+                };
+                *bytes = &bytes[8..];
+                PreviewCommand::Start(settings)
+            }
+            1 => PreviewCommand::Stop,
+            _ => {
+                log::warn!("Unknown screen preview command: {}, ignoring", command);
+                PreviewCommand::Stop
+            }
+        }
+    }
+}
+
+/// Monitor layout and pointer placement, see `monitors`
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum MonitorCommand {
+    /// Asks for the monitor list
+    List,
+    /// Moves the cursor to the center of the monitor
+    JumpTo(u8),
+    /// Keeps the cursor moved from the phones on the monitor
+    Confine(u8),
+    Release,
+}
+
+impl DeserializableAction for MonitorCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        *bytes = &bytes[1..];
+        match command {
+            0 => MonitorCommand::List,
+            1 | 2 => {
+                let monitor = bytes[0];
+                *bytes = &bytes[1..];
+                if command == 1 {
+                    MonitorCommand::JumpTo(monitor)
+                } else {
+                    MonitorCommand::Confine(monitor)
+                }
+            }
+            3 => MonitorCommand::Release,
+            _ => {
+                log::warn!("Unknown monitor command: {}, ignoring", command);
+                MonitorCommand::List
+            }
+        }
+    }
+}
+
+/// File transfer between the phone and the desktop, see `file_transfer`.
+/// Transfer ids are picked by the side sending the file.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone)]
+pub enum FileTransferCommand {
+    /// The phone starts sending a file
+    Offer {
+        transfer_id: u32,
+        size: u64,
+        sha256: [u8; 32],
+        name: String,
+    },
+    Chunk {
+        transfer_id: u32,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    /// The phone sent the whole file
+    Finish(u32),
+    /// The phone gives up sending the file
+    Cancel(u32),
+    /// The phone asks for the next bytes of a file offered by the desktop
+    Request { transfer_id: u32, offset: u64 },
+    /// The phone has the whole desktop file, and its checksum matches
+    Received(u32),
+    /// The phone refuses or gives up receiving the desktop file
+    Decline(u32),
+    /// A command this server doesn't know, ignored
+    Unknown,
+}
+
+impl FileTransferCommand {
+    /// Size of the encoded command, or `None` if the bytes are too short to tell
+    fn encoded_len(bytes: &[u8]) -> Option<usize> {
+        let len = match *bytes.first()? {
+            // sub-code, id, size, checksum, name length, name
+            0 => 1 + 4 + 8 + 32 + 1 + *bytes.get(45)? as usize,
+            // sub-code, id, offset, data length, data
+            1 => 1 + 4 + 8 + 2 + BigEndian::read_u16(bytes.get(13..15)?) as usize,
+            4 => 1 + 4 + 8,
+            _ => 1 + 4,
+        };
+        Some(len)
+    }
+}
+
+impl DeserializableAction for FileTransferCommand {
+    fn from_bytes(bytes: &mut &[u8]) -> Self {
+        let command = bytes[0];
+        let transfer_id = BigEndian::read_u32(&bytes[1..5]);
+        *bytes = &bytes[5..];
+        match command {
+            0 => {
+                let size = BigEndian::read_u64(bytes);
+                let mut sha256 = [0; 32];
+                sha256.copy_from_slice(&bytes[8..40]);
+                *bytes = &bytes[40..];
+                FileTransferCommand::Offer {
+                    transfer_id,
+                    size,
+                    sha256,
+                    name: decode_short_string(bytes),
+                }
+            }
+            1 => {
+                let offset = BigEndian::read_u64(bytes);
+                let len = BigEndian::read_u16(&bytes[8..10]) as usize;
+                let data = bytes[10..10 + len].to_vec();
+                *bytes = &bytes[10 + len..];
+                FileTransferCommand::Chunk {
+                    transfer_id,
+                    offset,
+                    data,
+                }
+            }
+            2 => FileTransferCommand::Finish(transfer_id),
+            3 => FileTransferCommand::Cancel(transfer_id),
+            4 => {
+                let offset = BigEndian::read_u64(bytes);
+                *bytes = &bytes[8..];
+                FileTransferCommand::Request {
+                    transfer_id,
+                    offset,
+                }
+            }
+            5 => FileTransferCommand::Received(transfer_id),
+            6 => FileTransferCommand::Decline(transfer_id),
+            _ => {
+                log::warn!("Unknown file transfer command: {}, ignoring", command);
+                FileTransferCommand::Unknown
+            }
+        }
+    }
+}
+
 /// Represents keys from keyboard
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug)]
 pub enum Key {
@@ -141,6 +895,7 @@ pub enum Key {
     PreviousTab = 10,
     BrightnessDown = 11,
     AltTab = 12,
+    BrightnessUp = 13,
     // Add new keys here with incrementing values
 }
 
@@ -163,6 +918,7 @@ impl DeserializableAction for Key {
             10 => Key::PreviousTab,
             11 => Key::BrightnessDown,
             12 => Key::AltTab,
+            13 => Key::BrightnessUp,
             _ => {
                 log::warn!("Unknown key code: {}, ignoring", btn);
                 Key::Backspace // Fallback to no-op key
@@ -213,7 +969,13 @@ impl DeserializableAction for Button {
 #[cfg(test)]
 mod tests {
 
-    use crate::actions::{Action, Button, DeltaCoordinates, Key, TerminalCommand};
+    use crate::actions::{
+        Action, AirMouseEvent, Button, DeltaCoordinates, FileTransferCommand, GamepadButton,
+        GamepadEvent, GamepadSide, GestureEvent, Key, MediaCommand, MonitorCommand, PenEvent,
+        PowerCommand, PresentationCommand, PreviewCommand, SwipeDirection, TerminalCommand,
+        WindowCommand,
+    };
+    use crate::screen_preview::{PreviewFormat, PreviewSettings};
 
     #[test]
     fn decode_key() {
@@ -255,10 +1017,34 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn decode_brightness_keys() {
+        let mut key_brightness_down: &[u8] = &[0u8, 11u8];
+        let mut key_brightness_up: &[u8] = &[0u8, 13u8];
+
+        assert!(matches!(
+            Action::decode(&mut key_brightness_down),
+            Action::KeyPress(Key::BrightnessDown)
+        ));
+        assert!(matches!(
+            Action::decode(&mut key_brightness_up),
+            Action::KeyPress(Key::BrightnessUp)
+        ));
+    }
+
+    #[test]
+    fn set_brightness() {
+        let mut set_brightness: &[u8] = &[13u8, 70u8];
+        assert!(matches!(
+            Action::decode(&mut set_brightness),
+            Action::SetBrightness(70)
+        ));
+    }
+
     #[test]
     fn decode_text() {
-        let mut text_a_lower: &[u8] = &[1u8, 'a' as u8];
-        let mut text_v_upper: &[u8] = &[1u8, 'V' as u8];
+        let mut text_a_lower: &[u8] = &[1u8, b'a'];
+        let mut text_v_upper: &[u8] = &[1u8, b'V'];
 
         assert!(matches!(
             Action::decode(&mut text_a_lower),
@@ -307,16 +1093,404 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn set_volume() {
+        let mut set_volume: &[u8] = &[10u8, 45u8];
+        assert!(matches!(
+            Action::decode(&mut set_volume),
+            Action::SetVolume(45)
+        ));
+
+        // volume is clamped to 100%
+        let mut too_loud: &[u8] = &[10u8, 250u8];
+        assert!(matches!(
+            Action::decode(&mut too_loud),
+            Action::SetVolume(100)
+        ));
+    }
+
+    #[test]
+    fn request_desktop_state() {
+        let mut request: &[u8] = &[11u8];
+        assert!(matches!(
+            Action::decode(&mut request),
+            Action::RequestDesktopState
+        ));
+    }
+
+    #[test]
+    fn media_commands() {
+        let mut next: &[u8] = &[12u8, 4u8];
+        assert!(matches!(
+            Action::decode(&mut next),
+            Action::Media(MediaCommand::Next)
+        ));
+
+        let mut seek_back: Vec<u8> = vec![12u8, 6u8];
+        seek_back.extend_from_slice(&(-5000i32).to_be_bytes());
+        assert!(matches!(
+            Action::decode(&mut seek_back.as_slice()),
+            Action::Media(MediaCommand::Seek(-5000))
+        ));
+
+        let mut position: Vec<u8> = vec![12u8, 7u8];
+        position.extend_from_slice(&90_000u32.to_be_bytes());
+        assert!(matches!(
+            Action::decode(&mut position.as_slice()),
+            Action::Media(MediaCommand::SetPosition(90_000))
+        ));
+
+        let mut shuffle: &[u8] = &[12u8, 8u8, 1u8];
+        assert!(matches!(
+            Action::decode(&mut shuffle),
+            Action::Media(MediaCommand::SetShuffle(true))
+        ));
+
+        let mut select: Vec<u8> = vec![12u8, 10u8, 3u8];
+        select.extend_from_slice(b"vlc");
+        let mut bytes = select.as_slice();
+        match Action::decode(&mut bytes) {
+            Action::Media(MediaCommand::SelectPlayer(id)) => assert_eq!(id, "vlc"),
+            other => panic!("Expected SelectPlayer but got {:?}", other),
+        }
+        assert_eq!(bytes.len(), 0);
+
+        let mut unknown: &[u8] = &[12u8, 99u8];
+        assert!(matches!(
+            Action::decode(&mut unknown),
+            Action::Media(MediaCommand::Unknown)
+        ));
+    }
+
+    #[test]
+    fn power_commands() {
+        let mut lock: &[u8] = &[14u8, 0u8];
+        assert!(matches!(
+            Action::decode(&mut lock),
+            Action::Power(PowerCommand::LockScreen)
+        ));
+
+        let mut schedule: &[u8] = &[14u8, 6u8, 0u8, 30u8];
+        assert!(matches!(
+            Action::decode(&mut schedule),
+            Action::Power(PowerCommand::ScheduleShutdown(30))
+        ));
+        assert_eq!(schedule.len(), 0);
+
+        let mut cancel: &[u8] = &[14u8, 7u8];
+        assert!(matches!(
+            Action::decode(&mut cancel),
+            Action::Power(PowerCommand::CancelScheduledShutdown)
+        ));
+    }
+
+    #[test]
+    fn gamepad_events() {
+        let mut button: &[u8] = &[15u8, 0u8, 11u8, 1u8];
+        assert!(matches!(
+            Action::decode(&mut button),
+            Action::Gamepad(GamepadEvent::Button {
+                button: GamepadButton::DpadUp,
+                pressed: true
+            })
+        ));
+
+        //                      | stick | right | x = -32768  | y = 256    |
+        let mut stick: &[u8] = &[15u8, 1u8, 1u8, 0x80, 0x00, 0x01, 0x00];
+        assert!(matches!(
+            Action::decode(&mut stick),
+            Action::Gamepad(GamepadEvent::Stick {
+                stick: GamepadSide::Right,
+                x: -32768,
+                y: 256
+            })
+        ));
+        assert_eq!(stick.len(), 0);
+
+        let mut trigger: &[u8] = &[15u8, 2u8, 0u8, 200u8];
+        assert!(matches!(
+            Action::decode(&mut trigger),
+            Action::Gamepad(GamepadEvent::Trigger {
+                trigger: GamepadSide::Left,
+                value: 200
+            })
+        ));
+
+        // unknown buttons release everything rather than guessing
+        let mut unknown: &[u8] = &[15u8, 0u8, 99u8, 1u8];
+        assert!(matches!(
+            Action::decode(&mut unknown),
+            Action::Gamepad(GamepadEvent::Reset)
+        ));
+        assert_eq!(unknown.len(), 0);
+    }
+
+    #[test]
+    fn gesture_events() {
+        let mut swipe: &[u8] = &[16u8, 0u8, 3u8, 2u8];
+        assert!(matches!(
+            Action::decode(&mut swipe),
+            Action::Gesture(GestureEvent::Swipe {
+                fingers: 3,
+                direction: SwipeDirection::Left
+            })
+        ));
+
+        let mut pinch: &[u8] = &[16u8, 1u8, (-2i8) as u8];
+        assert!(matches!(
+            Action::decode(&mut pinch),
+            Action::Gesture(GestureEvent::Pinch { steps: -2 })
+        ));
+
+        let mut tap: &[u8] = &[16u8, 3u8, 2u8];
+        assert!(matches!(
+            Action::decode(&mut tap),
+            Action::Gesture(GestureEvent::Tap { fingers: 2 })
+        ));
+        assert_eq!(tap.len(), 0);
+    }
+
+    #[test]
+    fn pen_event() {
+        //                  | pen | x = 256  | y = 65535  | pressure | tilt     | touching |
+        let mut pen: &[u8] = &[17u8, 1, 0, 0xff, 0xff, 0x80, 0, (-30i8) as u8, 10, 0b011];
+        let Action::Pen(pen_event) = Action::decode(&mut pen) else {
+            panic!("Expected Pen");
+        };
+        assert_eq!(
+            pen_event,
+            PenEvent {
+                x: 256,
+                y: 65535,
+                pressure: 0x8000,
+                tilt_x: -30,
+                tilt_y: 10,
+                in_range: true,
+                touching: true,
+                barrel_button: false,
+            }
+        );
+        assert_eq!(pen.len(), 0);
+    }
+
+    #[test]
+    fn macros() {
+        let mut run: &[u8] = &[18u8, 4u8, b'c', b'o', b'p', b'y', 19u8];
+        assert!(matches!(Action::decode(&mut run), Action::RunMacro(id) if id == "copy"));
+        assert!(matches!(Action::decode(&mut run), Action::ListMacros));
+        assert_eq!(run.len(), 0);
+    }
+
+    #[test]
+    fn presentation() {
+        //                | next    | laser -1.5° right, 0.2° down  | profile "pdf"           |
+        let mut bytes: &[u8] = &[22, 2, 22, 6, 0xff, 0x6a, 0, 20, 22, 8, 3, b'p', b'd', b'f'];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Presentation(PresentationCommand::Next)
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Presentation(PresentationCommand::Laser { dx: -150, dy: 20 })
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Presentation(PresentationCommand::SelectProfile(profile)) if profile == "pdf"
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn air_mouse() {
+        //                | sample 30°/s right, 1.2°/s down, 16ms | settings 3, 50%, 20 |
+        let mut bytes: &[u8] = &[23, 0, 0x01, 0x2c, 0xff, 0xf4, 16, 23, 2, 3, 50, 20, 23, 1];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::AirMouse(AirMouseEvent::Sample {
+                yaw: 300,
+                pitch: -12,
+                interval_ms: 16
+            })
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::AirMouse(AirMouseEvent::Settings(settings)) if settings.smoothing == 50
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::AirMouse(AirMouseEvent::Recenter)
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn window_commands() {
+        //                | list  | close 0x0a000003           | window 7 to monitor 1   |
+        let mut bytes: &[u8] = &[24, 0, 24, 4, 0x0a, 0, 0, 3, 24, 6, 0, 0, 0, 7, 1];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Window(WindowCommand::List)
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Window(WindowCommand::Close(0x0a000003))
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Window(WindowCommand::MoveToMonitor {
+                window: 7,
+                monitor: 1
+            })
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn shortcuts() {
+        let mut run: &[u8] = &[20u8, 3u8, b'c', b'u', b't', 21u8];
+        assert!(matches!(Action::decode(&mut run), Action::RunShortcut(id) if id == "cut"));
+        assert!(matches!(Action::decode(&mut run), Action::ListShortcuts));
+        assert_eq!(run.len(), 0);
+    }
+
+    #[test]
+    fn file_transfer_commands() {
+        let mut offer = vec![27u8, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 1, 0];
+        offer.extend_from_slice(&[0xab; 32]);
+        offer.extend_from_slice(&[5, b'a', b'.', b'p', b'd', b'f']);
+        //                   | chunk of 3 bytes at offset 2                           |
+        offer.extend_from_slice(&[27, 1, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3, 1, 2, 3]);
+        //                   | request from offset 256                | finish        |
+        offer.extend_from_slice(&[27, 4, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 1, 0, 27, 2, 0, 0, 0, 9]);
+
+        let mut bytes: &[u8] = &offer;
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::FileTransfer(FileTransferCommand::Offer { transfer_id: 9, size: 256, sha256, name })
+                if sha256 == [0xab; 32] && name == "a.pdf"
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::FileTransfer(FileTransferCommand::Chunk { transfer_id: 9, offset: 2, data })
+                if data == [1, 2, 3]
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::FileTransfer(FileTransferCommand::Request {
+                transfer_id: 7,
+                offset: 256
+            })
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::FileTransfer(FileTransferCommand::Finish(9))
+        ));
+        assert_eq!(bytes.len(), 0);
+
+        let mut bytes: &[u8] = &[27, 99, 0, 0, 0, 9, 5];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::FileTransfer(FileTransferCommand::Unknown)
+        ));
+        assert!(matches!(Action::decode(&mut bytes), Action::Disconnect));
+    }
+
+    #[test]
+    fn split_file_chunks_wait_for_the_rest() {
+        let chunk: &[u8] = &[27, 1, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3, 1, 2, 3];
+        assert!(!Action::is_incomplete(chunk));
+        assert!(Action::is_incomplete(&chunk[..18]));
+        assert!(Action::is_incomplete(&chunk[..10]));
+        assert!(Action::is_incomplete(&chunk[..1]));
+        // other actions always come whole
+        assert!(!Action::is_incomplete(&[0u8]));
+    }
+
+    #[test]
+    fn open_url() {
+        let mut bytes: &[u8] = &[28u8, 0, 5, b'h', b't', b't', b'p', b':', 21u8];
+        assert!(matches!(Action::decode(&mut bytes), Action::OpenUrl(url) if url == "http:"));
+        assert!(matches!(Action::decode(&mut bytes), Action::ListShortcuts));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn monitor_commands() {
+        //                  | list | jump to 1 | confine to 0 | release |
+        let mut bytes: &[u8] = &[31u8, 0, 31u8, 1, 1, 31u8, 2, 0, 31u8, 3];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Monitor(MonitorCommand::List)
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Monitor(MonitorCommand::JumpTo(1))
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Monitor(MonitorCommand::Confine(0))
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::Monitor(MonitorCommand::Release)
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn screen_preview() {
+        //                  | start: monitor 1, png, 5 fps, 320x200, 50% | stop |
+        let mut bytes: &[u8] = &[30u8, 0, 1, 1, 5, 0x01, 0x40, 0, 200, 50, 30u8, 1];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::ScreenPreview(PreviewCommand::Start(PreviewSettings {
+                monitor: 1,
+                format: PreviewFormat::Png,
+                fps: 5,
+                width: 320,
+                height: 200,
+                scale: 50,
+            }))
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::ScreenPreview(PreviewCommand::Stop)
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn mirror_notifications() {
+        let mut bytes: &[u8] = &[29u8, 1, 29u8, 0];
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::MirrorNotifications(true)
+        ));
+        assert!(matches!(
+            Action::decode(&mut bytes),
+            Action::MirrorNotifications(false)
+        ));
+        assert_eq!(bytes.len(), 0);
+    }
+
+    #[test]
+    fn apps() {
+        let mut bytes: &[u8] = &[25u8, 0u8, 26u8, 5u8, b'a', b'.', b'd', b'e', b's'];
+        assert!(matches!(Action::decode(&mut bytes), Action::ListApps(query) if query.is_empty()));
+        assert!(matches!(Action::decode(&mut bytes), Action::LaunchApp(id) if id == "a.des"));
+        assert_eq!(bytes.len(), 0);
+    }
+
     #[test]
     fn terminal_command_open_firefox() {
         let mut firefox_command: Vec<u8> = vec![7u8];
         let utf8_bytes: &[u8] = "firefox".as_bytes();
         firefox_command.push(utf8_bytes.len() as u8);
         firefox_command.append(&mut utf8_bytes.to_owned());
-        let mut bytes = &mut firefox_command.as_slice();
+        let bytes = &mut firefox_command.as_slice();
 
         // First, match the Action enum to ensure it's a TerminalCommand
-        if let Action::TerminalCommand(TerminalCommand { command }) = Action::decode(&mut bytes) {
+        if let Action::TerminalCommand(TerminalCommand { command }) = Action::decode(bytes) {
             // Then check if the command matches "firefox"
             assert_eq!(command, "firefox");
             assert_eq!(bytes.len(), 0);
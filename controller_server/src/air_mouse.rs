@@ -0,0 +1,184 @@
+//! Turns the phone's gyroscope into cursor motion, pointing it like a remote.
+//!
+//! Each sample carries the turn rate of the phone. Rates inside the dead zone are
+//! dropped so a hand held still doesn't drift, the rest is smoothed and integrated
+//! into pixels. Sub-pixel motion is carried over to the next sample.
+//!
+//! Recentering resets the filter and measures the gyroscope bias over the next
+//! samples, which must be taken with the phone held still.
+
+use serde::{Deserialize, Serialize};
+
+/// Samples averaged to measure the gyroscope bias after recentering
+const CALIBRATION_SAMPLES: u32 = 20;
+
+/// Longest gap between samples taken into account, so a stalled connection
+/// doesn't throw the cursor across the screen
+const MAX_SAMPLE_INTERVAL_MS: u8 = 100;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub struct AirMouseSettings {
+    /// Turn rates below this are ignored, in degrees per second
+    pub dead_zone: u8,
+    /// How much of the previous motion is kept on each sample, in percent.
+    /// 0 follows the hand exactly, higher values trade lag for steadiness.
+    pub smoothing: u8,
+    /// Pixels the cursor travels per degree the phone turns
+    pub sensitivity: u8,
+}
+
+impl Default for AirMouseSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 2,
+            smoothing: 60,
+            sensitivity: 25,
+        }
+    }
+}
+
+/// Gyroscope filter of one phone
+#[derive(Debug, Default)]
+pub struct AirMouse {
+    settings: AirMouseSettings,
+    /// Smoothed turn rate, in degrees per second
+    velocity: (f32, f32),
+    /// Motion not yet sent to the cursor, in pixels
+    remainder: (f32, f32),
+    /// Measured zero-rate offset of the gyroscope, in degrees per second
+    bias: (f32, f32),
+    /// Sum and count of the samples taken since recentering, while calibrating
+    calibration: Option<((f32, f32), u32)>,
+}
+
+impl AirMouse {
+    pub fn set_settings(&mut self, settings: AirMouseSettings) {
+        self.settings = AirMouseSettings {
+            // keep some of the new motion, or the cursor would never move
+            smoothing: settings.smoothing.min(95),
+            ..settings
+        };
+    }
+
+    /// Stops the cursor and measures the gyroscope bias over the next samples
+    pub fn recenter(&mut self) {
+        self.velocity = (0.0, 0.0);
+        self.remainder = (0.0, 0.0);
+        self.calibration = Some(((0.0, 0.0), 0));
+    }
+
+    /// Cursor motion in pixels for turn rates in tenths of a degree per second,
+    /// sampled `interval_ms` after the previous sample. Positive yaw turns right
+    /// and positive pitch tilts up.
+    pub fn sample(&mut self, yaw: i16, pitch: i16, interval_ms: u8) -> (i32, i32) {
+        // screen y grows downwards
+        let rate = (yaw as f32 / 10.0, -(pitch as f32) / 10.0);
+
+        if let Some(((sum_x, sum_y), count)) = self.calibration.as_mut() {
+            *sum_x += rate.0;
+            *sum_y += rate.1;
+            *count += 1;
+            if *count == CALIBRATION_SAMPLES {
+                self.bias = (*sum_x / *count as f32, *sum_y / *count as f32);
+                self.calibration = None;
+            }
+            return (0, 0);
+        }
+
+        let dead_zone = self.settings.dead_zone as f32;
+        let smoothing = self.settings.smoothing as f32 / 100.0;
+        let filter = |rate: f32, bias: f32, velocity: f32| {
+            let rate = rate - bias;
+            // soft dead zone: motion starts from zero at its edge instead of jumping
+            let rate = rate.signum() * (rate.abs() - dead_zone).max(0.0);
+            velocity * smoothing + rate * (1.0 - smoothing)
+        };
+        self.velocity = (
+            filter(rate.0, self.bias.0, self.velocity.0),
+            filter(rate.1, self.bias.1, self.velocity.1),
+        );
+
+        let seconds = interval_ms.min(MAX_SAMPLE_INTERVAL_MS) as f32 / 1000.0;
+        let sensitivity = self.settings.sensitivity as f32;
+        let x = self.remainder.0 + self.velocity.0 * seconds * sensitivity;
+        let y = self.remainder.1 + self.velocity.1 * seconds * sensitivity;
+        let pixels = (x.trunc(), y.trunc());
+        self.remainder = (x - pixels.0, y - pixels.1);
+        (pixels.0 as i32, pixels.1 as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unsmoothed() -> AirMouse {
+        let mut air_mouse = AirMouse::default();
+        air_mouse.set_settings(AirMouseSettings {
+            dead_zone: 2,
+            smoothing: 0,
+            sensitivity: 10,
+        });
+        air_mouse
+    }
+
+    #[test]
+    fn turn_rate_becomes_cursor_motion() {
+        let mut air_mouse = unsmoothed();
+        // 52°/s right and 12°/s up for 100ms, minus the 2°/s dead zone
+        assert_eq!(air_mouse.sample(520, 120, 100), (50, -10));
+    }
+
+    #[test]
+    fn fastest_tilt_down() {
+        let mut air_mouse = unsmoothed();
+        let (x, y) = air_mouse.sample(0, i16::MIN, 20);
+        assert_eq!(x, 0);
+        assert!(y > 0);
+    }
+
+    #[test]
+    fn dead_zone_stops_drift() {
+        let mut air_mouse = unsmoothed();
+        for _ in 0..100 {
+            assert_eq!(air_mouse.sample(15, -19, 20), (0, 0));
+        }
+    }
+
+    #[test]
+    fn sub_pixel_motion_adds_up() {
+        let mut air_mouse = unsmoothed();
+        // 2.5°/s past the dead zone for 10ms is a quarter of a pixel
+        let moved: i32 = (0..9).map(|_| air_mouse.sample(45, 0, 10).0).sum();
+        assert_eq!(moved, 2);
+    }
+
+    #[test]
+    fn smoothing_eases_in() {
+        let mut air_mouse = AirMouse::default();
+        let first = air_mouse.sample(1000, 0, 20).0;
+        let second = air_mouse.sample(1000, 0, 20).0;
+        assert!(first > 0 && second > first);
+    }
+
+    #[test]
+    fn recenter_removes_the_bias() {
+        let mut air_mouse = unsmoothed();
+        air_mouse.recenter();
+        for _ in 0..CALIBRATION_SAMPLES {
+            assert!(air_mouse.calibration.is_some());
+            assert_eq!(air_mouse.sample(100, 0, 20), (0, 0));
+        }
+        assert!(air_mouse.calibration.is_none());
+
+        // a 10°/s offset is now read as standing still
+        assert_eq!(air_mouse.sample(100, 0, 100), (0, 0));
+        assert_eq!(air_mouse.sample(620, 0, 100), (50, 0));
+    }
+
+    #[test]
+    fn long_gaps_are_capped() {
+        let mut air_mouse = unsmoothed();
+        assert_eq!(air_mouse.sample(520, 0, 250), (50, 0));
+    }
+}
@@ -0,0 +1,149 @@
+//! System volume read/write.
+//!
+//! On Linux this talks to the running sound server through its CLI:
+//! `wpctl` (PipeWire/WirePlumber) first, falling back to `pactl` (PulseAudio).
+//! The commands are spawned with an argument vector, never through a shell.
+
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+use serde::Serialize;
+
+/// Volume of the default output device
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeState {
+    /// Volume in percent (0-100, may exceed 100 when over-amplified)
+    pub level: u8,
+    pub muted: bool,
+}
+
+/// Reads the volume of the default output device.
+/// Returns `None` if no supported sound server could be queried.
+#[cfg(target_os = "linux")]
+pub fn read_volume() -> Option<VolumeState> {
+    if let Some(output) = command_output("wpctl", &["get-volume", "@DEFAULT_AUDIO_SINK@"]) {
+        return parse_wpctl_volume(&output);
+    }
+
+    let volume = command_output("pactl", &["get-sink-volume", "@DEFAULT_SINK@"])?;
+    let mute = command_output("pactl", &["get-sink-mute", "@DEFAULT_SINK@"])?;
+    Some(VolumeState {
+        level: parse_pactl_volume(&volume)?,
+        muted: parse_pactl_mute(&mute)?,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_volume() -> Option<VolumeState> {
+    None
+}
+
+/// Sets the absolute volume of the default output device, in percent.
+#[cfg(target_os = "linux")]
+pub fn set_volume(percent: u8) -> Result<(), String> {
+    let percent = percent.min(100);
+    let wpctl_level = format!("{:.2}", percent as f32 / 100.0);
+    if run("wpctl", &["set-volume", "@DEFAULT_AUDIO_SINK@", &wpctl_level]).is_ok() {
+        return Ok(());
+    }
+    run(
+        "pactl",
+        &["set-sink-volume", "@DEFAULT_SINK@", &format!("{}%", percent)],
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_volume(_percent: u8) -> Result<(), String> {
+    Err("Setting the volume is not supported on this OS".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(target_os = "linux")]
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", program, status))
+    }
+}
+
+/// Parses `wpctl get-volume` output, e.g. `Volume: 0.45 [MUTED]`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_wpctl_volume(output: &str) -> Option<VolumeState> {
+    let rest = output.trim().strip_prefix("Volume:")?;
+    let mut parts = rest.split_whitespace();
+    let level: f32 = parts.next()?.parse().ok()?;
+    let muted = parts.any(|part| part == "[MUTED]");
+    Some(VolumeState {
+        level: (level * 100.0).round().clamp(0.0, 255.0) as u8,
+        muted,
+    })
+}
+
+/// Parses `pactl get-sink-volume` output, e.g.
+/// `Volume: front-left: 29491 /  45% / -20.81 dB,   front-right: 29491 /  45% / -20.81 dB`
+/// Returns the first channel's volume.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_pactl_volume(output: &str) -> Option<u8> {
+    output
+        .split_whitespace()
+        .find_map(|part| part.strip_suffix('%'))
+        .and_then(|percent| percent.parse::<u16>().ok())
+        .map(|percent| percent.min(255) as u8)
+}
+
+/// Parses `pactl get-sink-mute` output, e.g. `Mute: no`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_pactl_mute(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Mute:")?.trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wpctl_volume() {
+        assert_eq!(
+            parse_wpctl_volume("Volume: 0.45\n"),
+            Some(VolumeState {
+                level: 45,
+                muted: false
+            })
+        );
+        assert_eq!(
+            parse_wpctl_volume("Volume: 0.30 [MUTED]\n"),
+            Some(VolumeState {
+                level: 30,
+                muted: true
+            })
+        );
+        assert_eq!(parse_wpctl_volume("garbage"), None);
+    }
+
+    #[test]
+    fn pactl_volume_and_mute() {
+        let volume =
+            "Volume: front-left: 29491 /  45% / -20.81 dB,   front-right: 29491 /  45% / -20.81 dB\n        balance 0.00\n";
+        assert_eq!(parse_pactl_volume(volume), Some(45));
+        assert_eq!(parse_pactl_mute("Mute: yes\n"), Some(true));
+        assert_eq!(parse_pactl_mute("Mute: no\n"), Some(false));
+        assert_eq!(parse_pactl_mute("nothing"), None);
+    }
+}
@@ -0,0 +1,246 @@
+//! `lazectl`: controls a running `laze-server` through its control socket.
+
+#[cfg(unix)]
+use std::{path::PathBuf, process::ExitCode};
+
+#[cfg(unix)]
+use clap::{Parser, Subcommand};
+
+#[cfg(unix)]
+use server::{
+    control_socket, ClientInfo, ControlClient, ControlReply, ControlRequest, ServerConfig,
+    ServerRequest, ServerResponse, ServerStatus,
+};
+
+/// Bad arguments
+#[cfg(unix)]
+const EXIT_USAGE: u8 = 64;
+/// No server is listening on the socket
+#[cfg(unix)]
+const EXIT_UNAVAILABLE: u8 = 69;
+
+#[cfg(unix)]
+#[derive(Parser, Debug)]
+#[command(name = "lazectl", version, about = "Controls a running Laze server")]
+struct Args {
+    /// Control socket of the server [default: $XDG_RUNTIME_DIR/laze/control.sock]
+    #[arg(short, long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[cfg(unix)]
+#[derive(Subcommand, Debug, PartialEq)]
+enum Command {
+    /// Shows the server status and the connected phones
+    Status,
+    /// Disconnects a phone
+    Kick {
+        /// Id of the phone, as shown in the events
+        id: usize,
+    },
+    /// Stops listening to the phones and disconnects them
+    Stop,
+    /// Listens to the phones again
+    Start,
+    /// Prints the server events as JSON, one per line, until interrupted
+    Events,
+    /// Prints the server config as JSON
+    Config {
+        /// Applies the config in the file instead, e.g. an edited server.json
+        #[arg(long, value_name = "FILE")]
+        apply: Option<PathBuf>,
+    },
+}
+
+#[cfg(unix)]
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::from(EXIT_USAGE)
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+
+    let path = args.socket.unwrap_or_else(control_socket);
+    let mut client = match ControlClient::connect(&path) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("lazectl: {}", e);
+            return ExitCode::from(EXIT_UNAVAILABLE);
+        }
+    };
+
+    match run(&mut client, args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("lazectl: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(unix)]
+fn run(client: &mut ControlClient, command: Command) -> Result<(), String> {
+    let request = match command {
+        Command::Status => {
+            let status = match client.request(&ControlRequest::Server(ServerRequest::GetStatus))? {
+                ControlReply::Response(ServerResponse::ServerStatus(status)) => status,
+                other => return Err(format!("Unexpected reply: {:?}", other)),
+            };
+            let phones =
+                match client.request(&ControlRequest::Server(ServerRequest::ListClients))? {
+                    ControlReply::Response(ServerResponse::ClientList(list)) => list.clients,
+                    other => return Err(format!("Unexpected reply: {:?}", other)),
+                };
+            print!("{}", describe(&status, &phones));
+            return Ok(());
+        }
+        Command::Events => {
+            client.send(&ControlRequest::Subscribe)?;
+            loop {
+                match client.receive()? {
+                    ControlReply::Event(event) => {
+                        println!(
+                            "{}",
+                            serde_json::to_string(&event).map_err(|e| e.to_string())?
+                        )
+                    }
+                    other => return Err(format!("Unexpected reply: {:?}", other)),
+                }
+            }
+        }
+        Command::Config { apply: None } => {
+            match client.request(&ControlRequest::Server(ServerRequest::GetConfig))? {
+                ControlReply::Response(ServerResponse::ServerConfig(config)) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?
+                ),
+                ControlReply::Response(ServerResponse::Error(e)) => return Err(e),
+                other => return Err(format!("Unexpected reply: {:?}", other)),
+            }
+            return Ok(());
+        }
+        Command::Config { apply: Some(file) } => ServerRequest::UpdateConfig(
+            ServerConfig::from_file(&file).map_err(|e| format!("{}: {}", file.display(), e))?,
+        ),
+        Command::Kick { id } => ServerRequest::TerminateClient(id),
+        Command::Stop => ServerRequest::StopServer,
+        Command::Start => ServerRequest::InitServer,
+    };
+
+    match client.request(&ControlRequest::Server(request))? {
+        ControlReply::Response(ServerResponse::ClientTerminated(client)) => {
+            println!("Disconnected phone {}", client.client_id)
+        }
+        ControlReply::Response(ServerResponse::ServerStopped(_)) => {
+            println!("Stopped listening to the phones")
+        }
+        ControlReply::Response(ServerResponse::ServerStarted(_)) => {
+            println!("Listening to the phones")
+        }
+        ControlReply::Response(ServerResponse::ConfigUpdated(updated)) => {
+            println!("Applied the config");
+            if updated.listener_restarted {
+                println!("The phones now connect to the new address");
+            }
+            if updated.discovery_restarted {
+                println!("Restarted the discovery");
+            }
+        }
+        ControlReply::Response(ServerResponse::Error(e)) => return Err(e),
+        other => return Err(format!("Unexpected reply: {:?}", other)),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn describe(status: &ServerStatus, phones: &[ClientInfo]) -> String {
+    let uptime = status.uptime_secs / 60;
+    let mut text = match (status.listening, status.address) {
+        (true, Some(address)) => format!("Listening on {}", address),
+        (true, None) => format!("Can't listen on port {}", status.port),
+        (false, _) => "Stopped".to_string(),
+    };
+    text += &format!(
+        ", up {}h {}m, discovery {}\n{} of {} phones connected\n",
+        uptime / 60,
+        uptime % 60,
+        if status.discovery { "on" } else { "off" },
+        status.clients,
+        status.max_clients
+    );
+    for phone in phones {
+        text += &format!(
+            "  {:<3} {:<20} {}\n",
+            phone.id,
+            phone.device_name.as_deref().unwrap_or("Unknown device"),
+            phone.addr
+        );
+    }
+    text
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("lazectl: the control socket is only available on Unix");
+    std::process::exit(1);
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let args = Args::try_parse_from(["lazectl", "kick", "2"]).unwrap();
+        assert_eq!(args.command, Command::Kick { id: 2 });
+        assert_eq!(args.socket, None);
+
+        let args = Args::try_parse_from(["lazectl", "-s", "/tmp/laze.sock", "status"]).unwrap();
+        assert_eq!(args.command, Command::Status);
+        assert_eq!(args.socket, Some(PathBuf::from("/tmp/laze.sock")));
+
+        let args = Args::try_parse_from(["lazectl", "config", "--apply", "server.json"]).unwrap();
+        assert_eq!(
+            args.command,
+            Command::Config {
+                apply: Some(PathBuf::from("server.json"))
+            }
+        );
+
+        assert!(Args::try_parse_from(["lazectl"]).is_err());
+        assert!(Args::try_parse_from(["lazectl", "kick", "phone"]).is_err());
+    }
+
+    #[test]
+    fn status_text() {
+        let status = ServerStatus {
+            listening: true,
+            address: Some(([192, 168, 1, 20], 7878).into()),
+            port: 7878,
+            max_clients: 10,
+            clients: 1,
+            uptime_secs: 7500,
+            discovery: true,
+        };
+        let phones = [ClientInfo {
+            id: 1,
+            addr: "192.168.1.30:50312".to_string(),
+            device_name: Some("Pixel 8".to_string()),
+        }];
+        assert_eq!(
+            describe(&status, &phones),
+            "Listening on 192.168.1.20:7878, up 2h 5m, discovery on\n\
+             1 of 10 phones connected\n  \
+             1   Pixel 8              192.168.1.30:50312\n"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Screen brightness through the Linux backlight sysfs interface.
+//!
+//! Writing `/sys/class/backlight/*/brightness` usually requires root (or a udev rule).
+//! When the write is denied, the brightness is set through logind's
+//! `Session.SetBrightness`, which is allowed for the user owning the active session.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Default location of backlight devices
+pub const SYSFS_BACKLIGHT_ROOT: &str = "/sys/class/backlight";
+
+/// Step, in percent, used by the brightness up/down keys
+pub const BRIGHTNESS_STEP: i8 = 10;
+
+/// Never go fully dark - a black screen can't be seen to be turned back up
+const MIN_BRIGHTNESS_PERCENT: u8 = 1;
+
+/// A backlight device exposed under a sysfs root, e.g.
+/// `/sys/class/backlight/intel_backlight`
+pub struct Backlight {
+    device: PathBuf,
+}
+
+impl Backlight {
+    /// Picks the first backlight device found under `/sys/class/backlight`.
+    pub fn detect() -> Option<Self> {
+        Self::detect_in(Path::new(SYSFS_BACKLIGHT_ROOT))
+    }
+
+    /// Picks the first backlight device found under `root`.
+    /// Devices are sorted by name so the choice is stable.
+    pub fn detect_in(root: &Path) -> Option<Self> {
+        let mut devices: Vec<PathBuf> = fs::read_dir(root)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join("max_brightness").exists())
+            .collect();
+        devices.sort();
+        devices.into_iter().next().map(|device| Self { device })
+    }
+
+    /// Device name, e.g. `intel_backlight`
+    pub fn name(&self) -> String {
+        self.device
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn read_value(&self, file: &str) -> Option<u32> {
+        fs::read_to_string(self.device.join(file))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Current brightness in percent of the device maximum.
+    pub fn percent(&self) -> Option<u8> {
+        let max = self.read_value("max_brightness")?;
+        if max == 0 {
+            return None;
+        }
+        let current = self.read_value("brightness")?;
+        Some(((current as u64 * 100 + max as u64 / 2) / max as u64).min(100) as u8)
+    }
+
+    /// Sets the brightness in percent of the device maximum.
+    /// Returns the percentage actually applied.
+    pub fn set_percent(&self, percent: u8) -> Result<u8, String> {
+        let max = self
+            .read_value("max_brightness")
+            .filter(|max| *max > 0)
+            .ok_or_else(|| format!("Could not read max_brightness of {}", self.name()))?;
+        let percent = percent.clamp(MIN_BRIGHTNESS_PERCENT, 100);
+        let raw = ((percent as u64 * max as u64 + 50) / 100).max(1) as u32;
+
+        match fs::write(self.device.join("brightness"), raw.to_string()) {
+            Ok(()) => Ok(percent),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                set_through_logind(&self.name(), raw)?;
+                Ok(percent)
+            }
+            Err(e) => Err(format!(
+                "Failed to write brightness of {}: {}",
+                self.name(),
+                e
+            )),
+        }
+    }
+
+    /// Changes the brightness by `delta` percent. Returns the percentage applied.
+    pub fn adjust(&self, delta: i8) -> Result<u8, String> {
+        let current = self
+            .percent()
+            .ok_or_else(|| format!("Could not read brightness of {}", self.name()))?;
+        let target = (current as i16 + delta as i16).clamp(0, 100) as u8;
+        self.set_percent(target)
+    }
+}
+
+/// Current screen brightness in percent, if a backlight device is available.
+pub fn read_brightness() -> Option<u8> {
+    Backlight::detect()?.percent()
+}
+
+#[cfg(target_os = "linux")]
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto",
+    gen_async = false
+)]
+trait LogindSession {
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+}
+
+#[cfg(target_os = "linux")]
+fn set_through_logind(device: &str, raw: u32) -> Result<(), String> {
+    let connection = zbus::blocking::Connection::system()
+        .map_err(|e| format!("Failed to connect to the system bus: {}", e))?;
+    LogindSessionProxy::new(&connection)
+        .and_then(|session| session.set_brightness("backlight", device, raw))
+        .map_err(|e| format!("logind refused to set brightness of {}: {}", device, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_through_logind(device: &str, _raw: u32) -> Result<(), String> {
+    Err(format!(
+        "Permission denied writing brightness of {}",
+        device
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_device(root: &Path, name: &str, brightness: u32, max: u32) {
+        let device = root.join(name);
+        fs::create_dir_all(&device).unwrap();
+        fs::write(device.join("brightness"), format!("{}\n", brightness)).unwrap();
+        fs::write(device.join("max_brightness"), format!("{}\n", max)).unwrap();
+    }
+
+    fn raw_brightness(root: &Path, name: &str) -> u32 {
+        fs::read_to_string(root.join(name).join("brightness"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_percent_from_fake_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        fake_device(root.path(), "intel_backlight", 600, 1200);
+
+        let backlight = Backlight::detect_in(root.path()).unwrap();
+        assert_eq!(backlight.name(), "intel_backlight");
+        assert_eq!(backlight.percent(), Some(50));
+    }
+
+    #[test]
+    fn no_device_found() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(Backlight::detect_in(root.path()).is_none());
+    }
+
+    #[test]
+    fn picks_first_device_by_name() {
+        let root = tempfile::tempdir().unwrap();
+        fake_device(root.path(), "nvidia_0", 10, 100);
+        fake_device(root.path(), "acpi_video0", 20, 100);
+
+        let backlight = Backlight::detect_in(root.path()).unwrap();
+        assert_eq!(backlight.name(), "acpi_video0");
+    }
+
+    #[test]
+    fn sets_absolute_brightness() {
+        let root = tempfile::tempdir().unwrap();
+        fake_device(root.path(), "intel_backlight", 0, 1200);
+        let backlight = Backlight::detect_in(root.path()).unwrap();
+
+        assert_eq!(backlight.set_percent(75), Ok(75));
+        assert_eq!(raw_brightness(root.path(), "intel_backlight"), 900);
+
+        // values above 100% are clamped
+        assert_eq!(backlight.set_percent(200), Ok(100));
+        assert_eq!(raw_brightness(root.path(), "intel_backlight"), 1200);
+
+        // the screen never goes fully dark
+        assert_eq!(backlight.set_percent(0), Ok(MIN_BRIGHTNESS_PERCENT));
+        assert_eq!(raw_brightness(root.path(), "intel_backlight"), 12);
+    }
+
+    #[test]
+    fn adjusts_relative_brightness() {
+        let root = tempfile::tempdir().unwrap();
+        fake_device(root.path(), "intel_backlight", 50, 100);
+        let backlight = Backlight::detect_in(root.path()).unwrap();
+
+        assert_eq!(backlight.adjust(BRIGHTNESS_STEP), Ok(60));
+        assert_eq!(backlight.adjust(-BRIGHTNESS_STEP), Ok(50));
+        assert_eq!(backlight.adjust(100), Ok(100));
+        assert_eq!(raw_brightness(root.path(), "intel_backlight"), 100);
+    }
+}
@@ -0,0 +1,68 @@
+//! Messages pushed from the server to the mobile clients.
+//!
+//! These are serialized to JSON and sent through the server's `Notifier`.
+
+use serde::Serialize;
+
+use crate::{
+    actions::PowerCommand, desktop_state::DesktopState, file_transfer::TransferUpdate,
+    launcher::AppInfo, macros::MacroSummary, media::PlayerInfo, monitors::MonitorInfo,
+    notifications::DesktopNotification, presentation::PresentationStatus, shortcuts::Shortcut,
+    window_manager::WindowInfo,
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum ClientMessage {
+    DesktopState(DesktopState),
+    MediaPlayers {
+        players: Vec<PlayerInfo>,
+        /// Id of the player this client targeted, if any
+        selected: Option<String>,
+    },
+    /// Outcome of a power command. `error` is `None` on success.
+    PowerResult {
+        command: PowerCommand,
+        error: Option<String>,
+    },
+    /// Macros the client can run, sent in reply to `ListMacros`
+    Macros {
+        macros: Vec<MacroSummary>,
+    },
+    /// Slide number, timer and laser state. Sent after each slide command and in
+    /// reply to the `Status` presentation command.
+    Presentation(PresentationStatus),
+    /// Open windows, pushed when they change and in reply to the `List` window command
+    Windows {
+        windows: Vec<WindowInfo>,
+    },
+    /// Monitor layout, sent in reply to the `List` monitor command
+    Monitors {
+        monitors: Vec<MonitorInfo>,
+    },
+    /// Installed applications matching the query of `ListApps`, with the icons the
+    /// client doesn't have yet
+    Apps {
+        apps: Vec<AppInfo>,
+    },
+    /// Progress of a file transfer, sent to the client taking part in it
+    FileTransfer(TransferUpdate),
+    /// A notification shown on the desktop, sent to the clients that subscribed
+    Notification(DesktopNotification),
+    /// The shortcut catalog, sent in reply to `ListShortcuts` and whenever it is edited
+    Shortcuts {
+        shortcuts: Vec<Shortcut>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialized_with_type_tag() {
+        let message = ClientMessage::DesktopState(DesktopState::default());
+        let json = serde_json::to_string(&message).unwrap();
+        assert!(json.starts_with("{\"type\":\"desktop_state\",\"data\":{"));
+    }
+}
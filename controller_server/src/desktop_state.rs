@@ -0,0 +1,94 @@
+//! Desktop state fed back to the mobile clients: volume, now-playing media and brightness.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    audio::{self, VolumeState},
+    brightness,
+    client_messages::ClientMessage,
+    logger::Loggable,
+    media::{self, NowPlaying},
+    server::notifier::Notifier,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Snapshot of the desktop state. Fields are `None` when they could not be read.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct DesktopState {
+    pub volume: Option<VolumeState>,
+    pub media: Option<NowPlaying>,
+    /// Brightness in percent
+    pub brightness: Option<u8>,
+}
+
+impl DesktopState {
+    pub fn read() -> Self {
+        Self {
+            volume: audio::read_volume(),
+            media: media::now_playing(),
+            brightness: brightness::read_brightness(),
+        }
+    }
+
+    /// Reads the current state and pushes it to every connected client.
+    pub fn publish(notifier: &Notifier) -> Self {
+        let state = Self::read();
+        notifier.broadcast(&ClientMessage::DesktopState(state.clone()));
+        state
+    }
+}
+
+/// Background thread that polls the desktop state and pushes it to the clients
+/// whenever it changes, or whenever a new client connects.
+/// The thread stops when the monitor is dropped.
+pub struct DesktopStateMonitor {
+    shutdown_signal: Arc<AtomicBool>,
+}
+
+impl DesktopStateMonitor {
+    pub fn start(notifier: Notifier) -> Self {
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::clone(&shutdown_signal);
+
+        thread::spawn(move || {
+            let mut last_state: Option<DesktopState> = None;
+            let mut last_client_count = 0;
+
+            while !shutdown.load(Ordering::SeqCst) {
+                let client_count = notifier.client_count();
+
+                // nobody is listening - don't bother querying the desktop
+                if client_count > 0 {
+                    let state = DesktopState::read();
+                    if client_count > last_client_count || last_state.as_ref() != Some(&state) {
+                        Self::static_log_debug(&format!("Publishing desktop state: {:?}", state));
+                        notifier.broadcast(&ClientMessage::DesktopState(state.clone()));
+                        last_state = Some(state);
+                    }
+                }
+                last_client_count = client_count;
+
+                thread::sleep(POLL_INTERVAL);
+            }
+            Self::static_log_info("Desktop state monitor has exited.");
+        });
+
+        Self { shutdown_signal }
+    }
+}
+
+impl Drop for DesktopStateMonitor {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+    }
+}
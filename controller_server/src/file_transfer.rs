@@ -0,0 +1,718 @@
+//! Files sent between the phones and the desktop.
+//!
+//! Phone to desktop: the phone offers a file with its name, size and SHA-256, and
+//! is told the offset to start from. Bytes kept from an interrupted attempt at the
+//! same file are not sent again. Each chunk is acknowledged with the bytes written
+//! so far. Once the last one is in, the checksum is verified and the file is moved
+//! into the download directory, next to any file of the same name rather than over it.
+//!
+//! Desktop to phone: the desktop offers the file, then the phone asks for it chunk
+//! by chunk from the offset it has, which is how it resumes too.
+//!
+//! The download directory is set in `file_transfer.json` in the config directory.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    actions::FileTransferCommand,
+    client_messages::ClientMessage,
+    logger::Loggable,
+    paths,
+    server::{
+        core::{ServerEvent, TransferredFile},
+        notifier::Notifier,
+    },
+};
+
+pub const FILE_TRANSFER_FILE: &str = "file_transfer.json";
+
+/// Bytes sent to the phone for each request
+const DOWNLOAD_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Progress of a transfer, sent to the phone taking part in it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TransferUpdate {
+    /// The desktop takes the file. The phone sends it from `offset`.
+    Accepted {
+        transfer_id: u32,
+        offset: u64,
+    },
+    /// Bytes written so far. Sent for each chunk, and when a chunk doesn't start
+    /// where the file ends, so the phone can go back.
+    Progress {
+        transfer_id: u32,
+        offset: u64,
+    },
+    /// The file was saved as `name`, which differs from the offered one when a file
+    /// of that name was already there
+    Completed {
+        transfer_id: u32,
+        name: String,
+    },
+    Failed {
+        transfer_id: u32,
+        error: String,
+    },
+    /// The desktop sends a file. The phone asks for its bytes with `Request`.
+    Offer {
+        transfer_id: u32,
+        name: String,
+        size: u64,
+        /// Hex encoded
+        sha256: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Settings {
+    /// `None` for the user's downloads directory
+    download_dir: Option<PathBuf>,
+}
+
+/// A file coming from a phone
+struct Upload {
+    name: String,
+    size: u64,
+    sha256: [u8; 32],
+    /// Partial file in the download directory, named after the checksum
+    part: PathBuf,
+    file: File,
+    written: u64,
+}
+
+/// A desktop file going to a phone
+struct Download {
+    path: PathBuf,
+    name: String,
+    size: u64,
+}
+
+#[derive(Default)]
+struct Transfers {
+    settings: Settings,
+    /// Where the settings are saved, `None` to keep them in memory only
+    settings_path: Option<PathBuf>,
+    /// By client and transfer id
+    uploads: HashMap<(usize, u32), Upload>,
+    downloads: HashMap<(usize, u32), Download>,
+    next_download_id: u32,
+    notifier: Notifier,
+}
+
+/// The transfers in progress and the download settings, shared by the controller and
+/// the desktop app, which sends files and picks the download directory.
+#[derive(Clone, Default)]
+pub struct FileTransfers {
+    inner: Arc<Mutex<Transfers>>,
+}
+
+impl FileTransfers {
+    /// Transfers configured by `file_transfer.json` in the config directory
+    pub fn load() -> Self {
+        paths::load_config(FILE_TRANSFER_FILE, Self::open).unwrap_or_else(Self::in_memory)
+    }
+
+    /// Transfers configured by the given file, which doesn't need to exist yet
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let settings = paths::load_json(path)?.unwrap_or_default();
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Transfers {
+                settings,
+                settings_path: Some(path.to_path_buf()),
+                ..Transfers::default()
+            })),
+        })
+    }
+
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Where received files are saved
+    pub fn download_dir(&self) -> Result<PathBuf, String> {
+        self.inner.lock().unwrap().download_dir()
+    }
+
+    /// Saves received files in `dir` from now on. `None` goes back to the default.
+    pub fn set_download_dir(&self, dir: Option<PathBuf>) -> Result<(), String> {
+        if let Some(dir) = &dir {
+            if !dir.is_absolute() {
+                return Err(format!("{} is not an absolute path", dir.display()));
+            }
+        }
+        let mut transfers = self.inner.lock().unwrap();
+        transfers.settings.download_dir = dir;
+        transfers.save()
+    }
+
+    /// Where the replies and file chunks are sent, given by the server
+    pub fn set_notifier(&self, notifier: Notifier) {
+        self.inner.lock().unwrap().notifier = notifier;
+    }
+
+    /// Offers a desktop file to a phone, returning the transfer id
+    pub fn send_file(&self, client_id: usize, path: &Path) -> Result<u32, String> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("{} is not a file", path.display()))?;
+        let size = fs::metadata(path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?
+            .len();
+        let sha256 = checksum(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+
+        let mut transfers = self.inner.lock().unwrap();
+        transfers.next_download_id = transfers.next_download_id.wrapping_add(1);
+        let transfer_id = transfers.next_download_id;
+        transfers.downloads.insert(
+            (client_id, transfer_id),
+            Download {
+                path: path.to_path_buf(),
+                name: name.clone(),
+                size,
+            },
+        );
+        transfers.notifier.send_to(
+            client_id,
+            &ClientMessage::FileTransfer(TransferUpdate::Offer {
+                transfer_id,
+                name,
+                size,
+                sha256: hex(&sha256),
+            }),
+        );
+        Ok(transfer_id)
+    }
+
+    /// Runs a command from a phone, replying to it if needed
+    pub fn handle(&self, client_id: usize, command: FileTransferCommand) {
+        let mut transfers = self.inner.lock().unwrap();
+        if let Some(update) = transfers.process(client_id, command) {
+            transfers
+                .notifier
+                .send_to(client_id, &ClientMessage::FileTransfer(update));
+        }
+    }
+
+    /// Drops the client's transfers. Partial uploads stay on disk to be resumed.
+    pub fn client_disconnected(&self, client_id: usize) {
+        let mut transfers = self.inner.lock().unwrap();
+        transfers
+            .uploads
+            .retain(|(client, _), _| *client != client_id);
+        transfers
+            .downloads
+            .retain(|(client, _), _| *client != client_id);
+    }
+}
+
+impl Transfers {
+    fn download_dir(&self) -> Result<PathBuf, String> {
+        self.settings
+            .download_dir
+            .clone()
+            .or_else(paths::default_download_dir)
+            .ok_or_else(|| "No download directory".to_string())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        match &self.settings_path {
+            Some(path) => paths::save_json(path, &self.settings),
+            None => Ok(()),
+        }
+    }
+
+    fn process(
+        &mut self,
+        client_id: usize,
+        command: FileTransferCommand,
+    ) -> Option<TransferUpdate> {
+        match command {
+            FileTransferCommand::Offer {
+                transfer_id,
+                size,
+                sha256,
+                name,
+            } => Some(
+                self.accept(client_id, transfer_id, size, sha256, &name)
+                    .unwrap_or_else(|error| TransferUpdate::Failed { transfer_id, error }),
+            ),
+            FileTransferCommand::Chunk {
+                transfer_id,
+                offset,
+                data,
+            } => Some(self.write_chunk(client_id, transfer_id, offset, &data)),
+            FileTransferCommand::Finish(transfer_id) => Some(self.finish(client_id, transfer_id)),
+            FileTransferCommand::Cancel(transfer_id) => {
+                if let Some(upload) = self.uploads.remove(&(client_id, transfer_id)) {
+                    let _ = fs::remove_file(&upload.part);
+                }
+                None
+            }
+            FileTransferCommand::Request {
+                transfer_id,
+                offset,
+            } => self.send_chunk(client_id, transfer_id, offset).err(),
+            FileTransferCommand::Received(transfer_id) => {
+                if let Some(download) = self.downloads.remove(&(client_id, transfer_id)) {
+                    self.log_info(&format!("Sent {} to client {}", download.name, client_id));
+                    self.notifier
+                        .publish(ServerEvent::FileSent(TransferredFile {
+                            client_id,
+                            name: download.name,
+                            path: download.path,
+                            size: download.size,
+                        }));
+                }
+                None
+            }
+            FileTransferCommand::Decline(transfer_id) => {
+                self.downloads.remove(&(client_id, transfer_id));
+                None
+            }
+            FileTransferCommand::Unknown => None,
+        }
+    }
+
+    fn accept(
+        &mut self,
+        client_id: usize,
+        transfer_id: u32,
+        size: u64,
+        sha256: [u8; 32],
+        name: &str,
+    ) -> Result<TransferUpdate, String> {
+        let name = safe_file_name(name).ok_or_else(|| format!("Invalid file name '{}'", name))?;
+        let dir = self.download_dir()?;
+        fs::create_dir_all(&dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+
+        // the same file sent again picks up where the last attempt stopped
+        let part = dir.join(format!(".laze-{}.part", hex(&sha256)));
+        // two uploads writing the same part would corrupt it
+        if self
+            .uploads
+            .iter()
+            .any(|(key, upload)| *key != (client_id, transfer_id) && upload.part == part)
+        {
+            return Err(format!("{} is already being received", name));
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&part)
+            .map_err(|e| format!("Can't write {}: {}", part.display(), e))?;
+        let mut written = file.metadata().map_err(|e| e.to_string())?.len();
+        if written > size {
+            file.set_len(0).map_err(|e| e.to_string())?;
+            written = 0;
+        }
+        if written > 0 {
+            self.log_info(&format!("Resuming {} at byte {}", name, written));
+        }
+
+        self.uploads.insert(
+            (client_id, transfer_id),
+            Upload {
+                name,
+                size,
+                sha256,
+                part,
+                file,
+                written,
+            },
+        );
+        Ok(TransferUpdate::Accepted {
+            transfer_id,
+            offset: written,
+        })
+    }
+
+    fn write_chunk(
+        &mut self,
+        client_id: usize,
+        transfer_id: u32,
+        offset: u64,
+        data: &[u8],
+    ) -> TransferUpdate {
+        let Some(upload) = self.uploads.get_mut(&(client_id, transfer_id)) else {
+            return unknown_transfer(transfer_id);
+        };
+        if offset != upload.written {
+            return TransferUpdate::Progress {
+                transfer_id,
+                offset: upload.written,
+            };
+        }
+        if upload.written + data.len() as u64 > upload.size {
+            let upload = self.uploads.remove(&(client_id, transfer_id)).unwrap();
+            let _ = fs::remove_file(&upload.part);
+            return TransferUpdate::Failed {
+                transfer_id,
+                error: format!("More than the {} bytes offered", upload.size),
+            };
+        }
+
+        let result = upload
+            .file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| upload.file.write_all(data));
+        if let Err(e) = result {
+            self.uploads.remove(&(client_id, transfer_id));
+            return TransferUpdate::Failed {
+                transfer_id,
+                error: format!("Failed to write: {}", e),
+            };
+        }
+        upload.written += data.len() as u64;
+        TransferUpdate::Progress {
+            transfer_id,
+            offset: upload.written,
+        }
+    }
+
+    fn finish(&mut self, client_id: usize, transfer_id: u32) -> TransferUpdate {
+        let Some(upload) = self.uploads.remove(&(client_id, transfer_id)) else {
+            return unknown_transfer(transfer_id);
+        };
+        let failed = |error: String| TransferUpdate::Failed { transfer_id, error };
+
+        if upload.written != upload.size {
+            // the part stays, so offering the file again resumes it
+            return failed(format!("Got {} of {} bytes", upload.written, upload.size));
+        }
+        drop(upload.file);
+        match checksum(&upload.part) {
+            Ok(sha256) if sha256 == upload.sha256 => {}
+            Ok(_) => {
+                let _ = fs::remove_file(&upload.part);
+                return failed("Checksum mismatch".to_string());
+            }
+            Err(e) => return failed(format!("Failed to verify: {}", e)),
+        }
+
+        let dir = upload.part.parent().unwrap_or(Path::new("."));
+        let path = unique_path(dir, &upload.name);
+        if let Err(e) = fs::rename(&upload.part, &path) {
+            return failed(format!("Can't save {}: {}", path.display(), e));
+        }
+        let name = path
+            .file_name()
+            .map_or(upload.name, |name| name.to_string_lossy().into_owned());
+        self.log_info(&format!(
+            "Received {} from client {}",
+            path.display(),
+            client_id
+        ));
+        self.notifier
+            .publish(ServerEvent::FileReceived(TransferredFile {
+                client_id,
+                name: name.clone(),
+                path,
+                size: upload.size,
+            }));
+        TransferUpdate::Completed { transfer_id, name }
+    }
+
+    /// Sends the phone the bytes of an offered file from `offset`
+    fn send_chunk(
+        &mut self,
+        client_id: usize,
+        transfer_id: u32,
+        offset: u64,
+    ) -> Result<(), TransferUpdate> {
+        let download = self
+            .downloads
+            .get(&(client_id, transfer_id))
+            .ok_or_else(|| unknown_transfer(transfer_id))?;
+        let failed = |error: String| TransferUpdate::Failed { transfer_id, error };
+        if offset > download.size {
+            return Err(failed(format!("Offset {} is past the end", offset)));
+        }
+
+        let mut data = Vec::new();
+        File::open(&download.path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(offset))?;
+                file.take(DOWNLOAD_CHUNK_SIZE).read_to_end(&mut data)
+            })
+            .map_err(|e| failed(format!("Can't read {}: {}", download.name, e)))?;
+        self.notifier
+            .send_file_chunk(client_id, transfer_id, offset, &data);
+        Ok(())
+    }
+}
+
+fn unknown_transfer(transfer_id: u32) -> TransferUpdate {
+    TransferUpdate::Failed {
+        transfer_id,
+        error: "Unknown transfer".to_string(),
+    }
+}
+
+fn checksum(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The last component of the name sent by the phone, so it can't point outside
+/// the download directory. `None` if nothing usable is left.
+fn safe_file_name(name: &str) -> Option<String> {
+    let name: String = name
+        .rsplit(['/', '\\'])
+        .next()?
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// `dir/name`, or `dir/name (1).ext`, `dir/name (2).ext`... if it is taken
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfers(dir: &Path) -> FileTransfers {
+        let transfers = FileTransfers::in_memory();
+        transfers.set_download_dir(Some(dir.to_path_buf())).unwrap();
+        transfers
+    }
+
+    fn process(
+        transfers: &FileTransfers,
+        client_id: usize,
+        command: FileTransferCommand,
+    ) -> Option<TransferUpdate> {
+        transfers.inner.lock().unwrap().process(client_id, command)
+    }
+
+    fn offer(transfer_id: u32, name: &str, content: &[u8]) -> FileTransferCommand {
+        FileTransferCommand::Offer {
+            transfer_id,
+            size: content.len() as u64,
+            sha256: Sha256::digest(content).into(),
+            name: name.to_string(),
+        }
+    }
+
+    fn chunk(transfer_id: u32, offset: u64, data: &[u8]) -> FileTransferCommand {
+        FileTransferCommand::Chunk {
+            transfer_id,
+            offset,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn receive_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let transfers = transfers(dir.path());
+
+        assert_eq!(
+            process(&transfers, 1, offer(4, "../photo.jpg", b"hello world")),
+            Some(TransferUpdate::Accepted {
+                transfer_id: 4,
+                offset: 0
+            })
+        );
+        process(&transfers, 1, chunk(4, 0, b"hello "));
+        // a repeated chunk is answered with where to go on from
+        assert_eq!(
+            process(&transfers, 1, chunk(4, 0, b"hello ")),
+            Some(TransferUpdate::Progress {
+                transfer_id: 4,
+                offset: 6
+            })
+        );
+        process(&transfers, 1, chunk(4, 6, b"world"));
+        assert_eq!(
+            process(&transfers, 1, FileTransferCommand::Finish(4)),
+            Some(TransferUpdate::Completed {
+                transfer_id: 4,
+                name: "photo.jpg".to_string()
+            })
+        );
+        assert_eq!(
+            fs::read(dir.path().join("photo.jpg")).unwrap(),
+            b"hello world"
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn resume_after_disconnecting() {
+        let dir = tempfile::tempdir().unwrap();
+        let transfers = transfers(dir.path());
+
+        process(&transfers, 1, offer(1, "notes.pdf", b"0123456789"));
+        process(&transfers, 1, chunk(1, 0, b"0123"));
+        transfers.client_disconnected(1);
+
+        assert_eq!(
+            process(&transfers, 2, offer(9, "notes.pdf", b"0123456789")),
+            Some(TransferUpdate::Accepted {
+                transfer_id: 9,
+                offset: 4
+            })
+        );
+        process(&transfers, 2, chunk(9, 4, b"456789"));
+        process(&transfers, 2, FileTransferCommand::Finish(9));
+        assert_eq!(
+            fs::read(dir.path().join("notes.pdf")).unwrap(),
+            b"0123456789"
+        );
+    }
+
+    #[test]
+    fn the_same_file_is_received_once_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let transfers = transfers(dir.path());
+
+        process(&transfers, 1, offer(1, "song.mp3", b"0123456789"));
+        process(&transfers, 1, chunk(1, 0, b"0123"));
+        assert!(matches!(
+            process(&transfers, 2, offer(7, "song.mp3", b"0123456789")),
+            Some(TransferUpdate::Failed { transfer_id: 7, .. })
+        ));
+        assert!(matches!(
+            process(&transfers, 1, offer(2, "copy.mp3", b"0123456789")),
+            Some(TransferUpdate::Failed { transfer_id: 2, .. })
+        ));
+
+        // the refused offers left the upload alone
+        process(&transfers, 1, chunk(1, 4, b"456789"));
+        assert_eq!(
+            process(&transfers, 1, FileTransferCommand::Finish(1)),
+            Some(TransferUpdate::Completed {
+                transfer_id: 1,
+                name: "song.mp3".to_string()
+            })
+        );
+        assert_eq!(
+            process(&transfers, 2, offer(7, "song.mp3", b"0123456789")),
+            Some(TransferUpdate::Accepted {
+                transfer_id: 7,
+                offset: 0
+            })
+        );
+    }
+
+    #[test]
+    fn corrupted_files_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let transfers = transfers(dir.path());
+
+        process(&transfers, 1, offer(1, "a.txt", b"abc"));
+        process(&transfers, 1, chunk(1, 0, b"abd"));
+        assert!(matches!(
+            process(&transfers, 1, FileTransferCommand::Finish(1)),
+            Some(TransferUpdate::Failed { .. })
+        ));
+
+        process(&transfers, 1, offer(2, "b.txt", b"abc"));
+        assert!(matches!(
+            process(&transfers, 1, chunk(2, 0, b"abcd")),
+            Some(TransferUpdate::Failed { .. })
+        ));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn name_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("scan.pdf"), "").unwrap();
+        fs::write(dir.path().join("scan (1).pdf"), "").unwrap();
+        fs::write(dir.path().join(".profile"), "").unwrap();
+
+        assert_eq!(
+            unique_path(dir.path(), "scan.pdf"),
+            dir.path().join("scan (2).pdf")
+        );
+        assert_eq!(
+            unique_path(dir.path(), ".profile"),
+            dir.path().join(".profile (1)")
+        );
+        assert_eq!(unique_path(dir.path(), "new"), dir.path().join("new"));
+        assert_eq!(safe_file_name("C:\\Users\\me\\a.txt").unwrap(), "a.txt");
+        assert_eq!(safe_file_name(".."), None);
+        assert_eq!(safe_file_name("dir/"), None);
+    }
+
+    #[test]
+    fn send_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        fs::write(&path, b"report").unwrap();
+        let transfers = transfers(dir.path());
+
+        let transfer_id = transfers.send_file(3, &path).unwrap();
+        let request = |client_id, offset| {
+            process(
+                &transfers,
+                client_id,
+                FileTransferCommand::Request {
+                    transfer_id,
+                    offset,
+                },
+            )
+        };
+        assert_eq!(request(3, 2), None);
+        assert!(matches!(request(3, 7), Some(TransferUpdate::Failed { .. })));
+        // only the client it was offered to can fetch it
+        assert!(matches!(request(4, 0), Some(TransferUpdate::Failed { .. })));
+
+        process(&transfers, 3, FileTransferCommand::Received(transfer_id));
+        assert!(matches!(request(3, 0), Some(TransferUpdate::Failed { .. })));
+    }
+
+    #[test]
+    fn download_dir_is_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_TRANSFER_FILE);
+
+        let transfers = FileTransfers::open(&path).unwrap();
+        assert!(transfers
+            .set_download_dir(Some(PathBuf::from("relative")))
+            .is_err());
+        transfers
+            .set_download_dir(Some(dir.path().join("phone")))
+            .unwrap();
+
+        let reloaded = FileTransfers::open(&path).unwrap();
+        assert_eq!(reloaded.download_dir().unwrap(), dir.path().join("phone"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Maps touch gestures from the phone to desktop input.
+//!
+//! Each gesture has a name (e.g. `swipe_left_3`, `pinch_in`) bound to a key combo,
+//! a mouse click or a modified scroll. The defaults follow the OS conventions for
+//! workspace switching, overview and zoom. They can be overridden in `gestures.json`
+//! in the config directory:
+//!
+//! ```json
+//! {
+//!     "swipe_left_3": { "keys": ["meta", "page_up"] },
+//!     "rotate_clockwise": { "keys": ["control", { "unicode": "r" }] },
+//!     "tap_3": "none"
+//! }
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{GestureEvent, SwipeDirection},
+    input::{InputButton, InputKey},
+    paths,
+};
+
+pub const GESTURES_FILE: &str = "gestures.json";
+
+/// What a gesture does on the desktop
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureAction {
+    /// Press the keys together, e.g. Ctrl+Alt+Right
+    Keys(Vec<InputKey>),
+    Click(InputButton),
+    /// Scroll while holding the keys, e.g. Ctrl+scroll to zoom.
+    /// Each gesture step scrolls one notch: up (zoom in) when spreading the fingers
+    /// or rotating clockwise, down otherwise.
+    ScrollWith(Vec<InputKey>),
+    /// Ignore the gesture
+    None,
+}
+
+/// Gesture names bound to their actions
+pub struct GestureBindings {
+    bindings: HashMap<String, GestureAction>,
+}
+
+impl GestureBindings {
+    /// The OS defaults, overridden by the user's `gestures.json` if there is one
+    pub fn load() -> Self {
+        paths::load_config(GESTURES_FILE, |path| {
+            let mut bindings = Self::defaults();
+            bindings.override_from(path).map(|_| bindings)
+        })
+        .unwrap_or_else(Self::defaults)
+    }
+
+    pub fn defaults() -> Self {
+        Self {
+            bindings: default_bindings().into_iter().collect(),
+        }
+    }
+
+    /// Replaces the bindings of the gestures listed in the file, if there is one
+    pub fn override_from(&mut self, path: &Path) -> Result<(), String> {
+        let overrides: Option<HashMap<String, GestureAction>> = paths::load_json(path)?;
+        self.bindings.extend(overrides.unwrap_or_default());
+        Ok(())
+    }
+
+    /// The action bound to a gesture, and how many times to repeat it
+    pub fn resolve(&self, event: &GestureEvent) -> Option<(&GestureAction, u32)> {
+        let (name, repeat) = gesture_name(event)?;
+        self.bindings
+            .get(&name)
+            .filter(|action| **action != GestureAction::None)
+            .map(|action| (action, repeat))
+    }
+}
+
+/// Binding name of a gesture, and how many steps it covers
+fn gesture_name(event: &GestureEvent) -> Option<(String, u32)> {
+    match event {
+        GestureEvent::Swipe { fingers, direction } => {
+            let direction = match direction {
+                SwipeDirection::Up => "up",
+                SwipeDirection::Down => "down",
+                SwipeDirection::Left => "left",
+                SwipeDirection::Right => "right",
+            };
+            Some((format!("swipe_{}_{}", direction, fingers), 1))
+        }
+        GestureEvent::Pinch { steps: 0 } => None,
+        GestureEvent::Pinch { steps } if *steps > 0 => {
+            Some(("pinch_out".to_string(), steps.unsigned_abs() as u32))
+        }
+        GestureEvent::Pinch { steps } => {
+            Some(("pinch_in".to_string(), steps.unsigned_abs() as u32))
+        }
+        GestureEvent::Rotate { clockwise: true } => Some(("rotate_clockwise".to_string(), 1)),
+        GestureEvent::Rotate { clockwise: false } => {
+            Some(("rotate_counter_clockwise".to_string(), 1))
+        }
+        GestureEvent::Tap { fingers } => Some((format!("tap_{}", fingers), 1)),
+    }
+}
+
+fn default_bindings() -> Vec<(String, GestureAction)> {
+    use GestureAction::*;
+    use InputKey::*;
+
+    // workspace switching and overview
+    #[cfg(target_os = "linux")]
+    let (previous_workspace, next_workspace, overview, desktop) = (
+        vec![Control, Alt, LeftArrow],
+        vec![Control, Alt, RightArrow],
+        vec![Meta],
+        vec![Meta],
+    );
+    #[cfg(target_os = "windows")]
+    let (previous_workspace, next_workspace, overview, desktop) = (
+        vec![Control, Meta, LeftArrow],
+        vec![Control, Meta, RightArrow],
+        vec![Meta, Tab],
+        vec![Meta, Unicode('d')],
+    );
+    #[cfg(target_os = "macos")]
+    let (previous_workspace, next_workspace, overview, desktop) = (
+        vec![Control, LeftArrow],
+        vec![Control, RightArrow],
+        vec![Control, UpArrow],
+        vec![Control, DownArrow],
+    );
+    #[cfg(target_os = "macos")]
+    let zoom_modifier = Meta;
+    #[cfg(not(target_os = "macos"))]
+    let zoom_modifier = Control;
+
+    let mut bindings = Vec::new();
+    for fingers in [3, 4] {
+        // content follows the fingers: swiping left reveals the workspace on the right
+        bindings.extend([
+            (
+                format!("swipe_left_{}", fingers),
+                Keys(next_workspace.clone()),
+            ),
+            (
+                format!("swipe_right_{}", fingers),
+                Keys(previous_workspace.clone()),
+            ),
+            (format!("swipe_up_{}", fingers), Keys(overview.clone())),
+            (format!("swipe_down_{}", fingers), Keys(desktop.clone())),
+        ]);
+    }
+    bindings.extend(
+        [
+            ("pinch_in", ScrollWith(vec![zoom_modifier])),
+            ("pinch_out", ScrollWith(vec![zoom_modifier])),
+            ("rotate_clockwise", None),
+            ("rotate_counter_clockwise", None),
+            ("tap_2", Click(InputButton::Right)),
+            ("tap_3", Click(InputButton::Middle)),
+        ]
+        .map(|(name, action)| (name.to_string(), action)),
+    );
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn default_gestures() {
+        let bindings = GestureBindings::defaults();
+
+        let swipe = GestureEvent::Swipe {
+            fingers: 3,
+            direction: SwipeDirection::Left,
+        };
+        assert!(matches!(
+            bindings.resolve(&swipe),
+            Some((GestureAction::Keys(_), 1))
+        ));
+        assert_eq!(
+            bindings.resolve(&GestureEvent::Tap { fingers: 2 }),
+            Some((&GestureAction::Click(InputButton::Right), 1))
+        );
+        // unbound by default
+        assert_eq!(
+            bindings.resolve(&GestureEvent::Rotate { clockwise: true }),
+            None
+        );
+        assert_eq!(bindings.resolve(&GestureEvent::Tap { fingers: 5 }), None);
+    }
+
+    #[test]
+    fn pinch_steps() {
+        let bindings = GestureBindings::defaults();
+        let (action, steps) = bindings
+            .resolve(&GestureEvent::Pinch { steps: -3 })
+            .unwrap();
+        assert!(matches!(action, GestureAction::ScrollWith(_)));
+        assert_eq!(steps, 3);
+        assert_eq!(
+            gesture_name(&GestureEvent::Pinch { steps: 2 }).unwrap().0,
+            "pinch_out"
+        );
+        assert_eq!(bindings.resolve(&GestureEvent::Pinch { steps: 0 }), None);
+    }
+
+    #[test]
+    fn override_from_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{
+                "rotate_clockwise": {{ "keys": ["control", {{ "unicode": "r" }}] }},
+                "tap_2": "none"
+            }}"#
+        )
+        .unwrap();
+
+        let mut bindings = GestureBindings::defaults();
+        bindings.override_from(file.path()).unwrap();
+
+        assert_eq!(
+            bindings.resolve(&GestureEvent::Rotate { clockwise: true }),
+            Some((
+                &GestureAction::Keys(vec![InputKey::Control, InputKey::Unicode('r')]),
+                1
+            ))
+        );
+        assert_eq!(bindings.resolve(&GestureEvent::Tap { fingers: 2 }), None);
+    }
+
+    #[test]
+    fn invalid_file_is_reported() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, r#"{{ "tap_2": {{ "keys": ["hyper"] }} }}"#).unwrap();
+
+        let mut bindings = GestureBindings::defaults();
+        assert!(bindings.override_from(file.path()).is_err());
+        // defaults are kept
+        assert!(bindings
+            .resolve(&GestureEvent::Tap { fingers: 2 })
+            .is_some());
+    }
+}
@@ -0,0 +1,128 @@
+//! Input backend built on the enigo crate (X11 on Linux, Windows, macOS).
+
+use enigo::{Coordinate, Enigo, Keyboard, Mouse, Settings};
+
+use super::{Axis, Direction, InputBackend, InputButton, InputKey};
+
+#[cfg(target_os = "linux")]
+const XF86_MON_BRIGHTNESS_UP: u32 = 0x1008_FF02;
+#[cfg(target_os = "linux")]
+const XF86_MON_BRIGHTNESS_DOWN: u32 = 0x1008_FF03;
+
+pub struct EnigoBackend {
+    enigo: Enigo,
+}
+
+// enigo holds raw display handles, but it is only ever used behind the
+// application mutex, one thread at a time.
+unsafe impl Send for EnigoBackend {}
+unsafe impl Sync for EnigoBackend {}
+
+impl EnigoBackend {
+    pub fn new() -> Result<Self, String> {
+        Enigo::new(&Settings::default())
+            .map(|enigo| Self { enigo })
+            .map_err(|e| format!("Failed to connect enigo to the display: {}", e))
+    }
+}
+
+fn to_enigo_direction(direction: Direction) -> enigo::Direction {
+    match direction {
+        Direction::Press => enigo::Direction::Press,
+        Direction::Release => enigo::Direction::Release,
+        Direction::Click => enigo::Direction::Click,
+    }
+}
+
+fn to_enigo_button(button: InputButton) -> enigo::Button {
+    match button {
+        InputButton::Left => enigo::Button::Left,
+        InputButton::Right => enigo::Button::Right,
+        InputButton::Middle => enigo::Button::Middle,
+    }
+}
+
+fn to_enigo_key(key: InputKey) -> Result<enigo::Key, String> {
+    Ok(match key {
+        InputKey::Backspace => enigo::Key::Backspace,
+        InputKey::Return => enigo::Key::Return,
+        InputKey::Tab => enigo::Key::Tab,
+        InputKey::Escape => enigo::Key::Escape,
+        InputKey::Space => enigo::Key::Space,
+        InputKey::Delete => enigo::Key::Delete,
+        InputKey::Home => enigo::Key::Home,
+        InputKey::End => enigo::Key::End,
+        InputKey::PageUp => enigo::Key::PageUp,
+        InputKey::PageDown => enigo::Key::PageDown,
+        InputKey::UpArrow => enigo::Key::UpArrow,
+        InputKey::DownArrow => enigo::Key::DownArrow,
+        InputKey::LeftArrow => enigo::Key::LeftArrow,
+        InputKey::RightArrow => enigo::Key::RightArrow,
+        InputKey::Control => enigo::Key::Control,
+        InputKey::Shift => enigo::Key::Shift,
+        InputKey::Alt => enigo::Key::Alt,
+        InputKey::Meta => enigo::Key::Meta,
+        InputKey::F4 => enigo::Key::F4,
+        InputKey::F5 => enigo::Key::F5,
+        InputKey::F11 => enigo::Key::F11,
+        InputKey::VolumeMute => enigo::Key::VolumeMute,
+        InputKey::VolumeDown => enigo::Key::VolumeDown,
+        InputKey::VolumeUp => enigo::Key::VolumeUp,
+        InputKey::MediaPlayPause => enigo::Key::MediaPlayPause,
+        #[cfg(not(target_os = "macos"))]
+        InputKey::MediaStop => enigo::Key::MediaStop,
+        InputKey::MediaNext => enigo::Key::MediaNextTrack,
+        InputKey::MediaPrevious => enigo::Key::MediaPrevTrack,
+        #[cfg(target_os = "linux")]
+        InputKey::BrightnessUp => enigo::Key::Other(XF86_MON_BRIGHTNESS_UP),
+        #[cfg(target_os = "linux")]
+        InputKey::BrightnessDown => enigo::Key::Other(XF86_MON_BRIGHTNESS_DOWN),
+        #[cfg(target_os = "macos")]
+        InputKey::BrightnessUp => enigo::Key::BrightnessUp,
+        #[cfg(target_os = "macos")]
+        InputKey::BrightnessDown => enigo::Key::BrightnessDown,
+        InputKey::Unicode(c) => enigo::Key::Unicode(c),
+        #[allow(unreachable_patterns)]
+        other => return Err(format!("Key {:?} is not available on this OS", other)),
+    })
+}
+
+impl InputBackend for EnigoBackend {
+    fn name(&self) -> &'static str {
+        "enigo"
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) -> Result<(), String> {
+        self.enigo
+            .move_mouse(dx, dy, Coordinate::Rel)
+            .map_err(|e| format!("Failed to move mouse: {}", e))
+    }
+
+    fn button(&mut self, button: InputButton, direction: Direction) -> Result<(), String> {
+        self.enigo
+            .button(to_enigo_button(button), to_enigo_direction(direction))
+            .map_err(|e| format!("Failed to send {:?} button: {}", button, e))
+    }
+
+    fn key(&mut self, key: InputKey, direction: Direction) -> Result<(), String> {
+        self.enigo
+            .key(to_enigo_key(key)?, to_enigo_direction(direction))
+            .map_err(|e| format!("Failed to send key {:?}: {}", key, e))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        self.enigo
+            .text(text)
+            .map_err(|e| format!("Failed to type text: {}", e))
+    }
+
+    fn scroll(&mut self, amount: i32, axis: Axis) -> Result<(), String> {
+        let axis = match axis {
+            Axis::Vertical => enigo::Axis::Vertical,
+            Axis::Horizontal => enigo::Axis::Horizontal,
+        };
+        self.enigo
+            .scroll(amount, axis)
+            .map_err(|e| format!("Failed to scroll: {}", e))
+    }
+}
@@ -0,0 +1,211 @@
+//! Linux input event codes (see `linux/input-event-codes.h`) and the translation
+//! of `InputKey`s and characters into them.
+
+use super::{InputButton, InputKey};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_ABS: u16 = 0x03;
+
+pub const SYN_REPORT: u16 = 0;
+
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_HWHEEL: u16 = 0x06;
+pub const REL_WHEEL: u16 = 0x08;
+pub const REL_WHEEL_HI_RES: u16 = 0x0b;
+pub const REL_HWHEEL_HI_RES: u16 = 0x0c;
+
+/// Hi-res wheel units per wheel notch
+pub const WHEEL_HI_RES_PER_NOTCH: i32 = 120;
+
+pub const BTN_LEFT: u16 = 0x110;
+pub const BTN_RIGHT: u16 = 0x111;
+pub const BTN_MIDDLE: u16 = 0x112;
+
+// pen tablet
+pub const BTN_TOOL_PEN: u16 = 0x140;
+pub const BTN_TOUCH: u16 = 0x14a;
+pub const BTN_STYLUS: u16 = 0x14b;
+
+// gamepad buttons, named after the Xbox layout as the xpad driver reports them
+pub const BTN_A: u16 = 0x130;
+pub const BTN_B: u16 = 0x131;
+pub const BTN_X: u16 = 0x133;
+pub const BTN_Y: u16 = 0x134;
+pub const BTN_TL: u16 = 0x136;
+pub const BTN_TR: u16 = 0x137;
+pub const BTN_SELECT: u16 = 0x13a;
+pub const BTN_START: u16 = 0x13b;
+pub const BTN_MODE: u16 = 0x13c;
+pub const BTN_THUMBL: u16 = 0x13d;
+pub const BTN_THUMBR: u16 = 0x13e;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_Z: u16 = 0x02;
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+pub const ABS_RZ: u16 = 0x05;
+pub const ABS_HAT0X: u16 = 0x10;
+pub const ABS_HAT0Y: u16 = 0x11;
+pub const ABS_PRESSURE: u16 = 0x18;
+pub const ABS_TILT_X: u16 = 0x1a;
+pub const ABS_TILT_Y: u16 = 0x1b;
+
+/// Device property: the device maps directly onto the screen, like a tablet
+pub const INPUT_PROP_DIRECT: u16 = 0x01;
+
+pub const KEY_ESC: u16 = 1;
+pub const KEY_BACKSPACE: u16 = 14;
+pub const KEY_TAB: u16 = 15;
+pub const KEY_ENTER: u16 = 28;
+pub const KEY_LEFTCTRL: u16 = 29;
+pub const KEY_LEFTSHIFT: u16 = 42;
+pub const KEY_LEFTALT: u16 = 56;
+pub const KEY_SPACE: u16 = 57;
+pub const KEY_F4: u16 = 62;
+pub const KEY_F5: u16 = 63;
+pub const KEY_F11: u16 = 87;
+pub const KEY_HOME: u16 = 102;
+pub const KEY_UP: u16 = 103;
+pub const KEY_PAGEUP: u16 = 104;
+pub const KEY_LEFT: u16 = 105;
+pub const KEY_RIGHT: u16 = 106;
+pub const KEY_END: u16 = 107;
+pub const KEY_DOWN: u16 = 108;
+pub const KEY_PAGEDOWN: u16 = 109;
+pub const KEY_DELETE: u16 = 111;
+pub const KEY_MUTE: u16 = 113;
+pub const KEY_VOLUMEDOWN: u16 = 114;
+pub const KEY_VOLUMEUP: u16 = 115;
+pub const KEY_LEFTMETA: u16 = 125;
+pub const KEY_NEXTSONG: u16 = 163;
+pub const KEY_PLAYPAUSE: u16 = 164;
+pub const KEY_PREVIOUSSONG: u16 = 165;
+pub const KEY_STOPCD: u16 = 166;
+pub const KEY_BRIGHTNESSDOWN: u16 = 224;
+pub const KEY_BRIGHTNESSUP: u16 = 225;
+
+/// Highest keyboard key code a virtual keyboard registers
+pub const KEY_MAX_KEYBOARD: u16 = 255;
+
+pub fn button_code(button: InputButton) -> u16 {
+    match button {
+        InputButton::Left => BTN_LEFT,
+        InputButton::Right => BTN_RIGHT,
+        InputButton::Middle => BTN_MIDDLE,
+    }
+}
+
+/// Key code for a key, plus whether Shift must be held (only for characters).
+pub fn key_code(key: InputKey) -> Option<(u16, bool)> {
+    let code = match key {
+        InputKey::Backspace => KEY_BACKSPACE,
+        InputKey::Return => KEY_ENTER,
+        InputKey::Tab => KEY_TAB,
+        InputKey::Escape => KEY_ESC,
+        InputKey::Space => KEY_SPACE,
+        InputKey::Delete => KEY_DELETE,
+        InputKey::Home => KEY_HOME,
+        InputKey::End => KEY_END,
+        InputKey::PageUp => KEY_PAGEUP,
+        InputKey::PageDown => KEY_PAGEDOWN,
+        InputKey::UpArrow => KEY_UP,
+        InputKey::DownArrow => KEY_DOWN,
+        InputKey::LeftArrow => KEY_LEFT,
+        InputKey::RightArrow => KEY_RIGHT,
+        InputKey::Control => KEY_LEFTCTRL,
+        InputKey::Shift => KEY_LEFTSHIFT,
+        InputKey::Alt => KEY_LEFTALT,
+        InputKey::Meta => KEY_LEFTMETA,
+        InputKey::F4 => KEY_F4,
+        InputKey::F5 => KEY_F5,
+        InputKey::F11 => KEY_F11,
+        InputKey::VolumeMute => KEY_MUTE,
+        InputKey::VolumeDown => KEY_VOLUMEDOWN,
+        InputKey::VolumeUp => KEY_VOLUMEUP,
+        InputKey::MediaPlayPause => KEY_PLAYPAUSE,
+        InputKey::MediaStop => KEY_STOPCD,
+        InputKey::MediaNext => KEY_NEXTSONG,
+        InputKey::MediaPrevious => KEY_PREVIOUSSONG,
+        InputKey::BrightnessUp => KEY_BRIGHTNESSUP,
+        InputKey::BrightnessDown => KEY_BRIGHTNESSDOWN,
+        InputKey::Unicode(c) => return char_code(c),
+    };
+    Some((code, false))
+}
+
+/// Key code for a character on a US keyboard layout, plus whether Shift must be held.
+/// Characters that can't be typed on that layout return `None`.
+pub fn char_code(c: char) -> Option<(u16, bool)> {
+    const LETTERS: [u16; 26] = [
+        30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38, 50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17,
+        45, 21, 44,
+    ];
+    const DIGITS: [u16; 10] = [11, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+    Some(match c {
+        'a'..='z' => (LETTERS[(c as u8 - b'a') as usize], false),
+        'A'..='Z' => (LETTERS[(c as u8 - b'A') as usize], true),
+        '0'..='9' => (DIGITS[(c as u8 - b'0') as usize], false),
+        ')' => (11, true),
+        '!' => (2, true),
+        '@' => (3, true),
+        '#' => (4, true),
+        '$' => (5, true),
+        '%' => (6, true),
+        '^' => (7, true),
+        '&' => (8, true),
+        '*' => (9, true),
+        '(' => (10, true),
+        '-' => (12, false),
+        '_' => (12, true),
+        '=' => (13, false),
+        '+' => (13, true),
+        '[' => (26, false),
+        '{' => (26, true),
+        ']' => (27, false),
+        '}' => (27, true),
+        ';' => (39, false),
+        ':' => (39, true),
+        '\'' => (40, false),
+        '"' => (40, true),
+        '`' => (41, false),
+        '~' => (41, true),
+        '\\' => (43, false),
+        '|' => (43, true),
+        ',' => (51, false),
+        '<' => (51, true),
+        '.' => (52, false),
+        '>' => (52, true),
+        '/' => (53, false),
+        '?' => (53, true),
+        ' ' => (KEY_SPACE, false),
+        '\n' => (KEY_ENTER, false),
+        '\t' => (KEY_TAB, false),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letters_digits_and_symbols() {
+        assert_eq!(char_code('a'), Some((30, false)));
+        assert_eq!(char_code('Z'), Some((44, true)));
+        assert_eq!(char_code('0'), Some((11, false)));
+        assert_eq!(char_code('?'), Some((53, true)));
+        assert_eq!(char_code('é'), None);
+    }
+
+    #[test]
+    fn named_keys() {
+        assert_eq!(key_code(InputKey::Control), Some((KEY_LEFTCTRL, false)));
+        assert_eq!(key_code(InputKey::Unicode('W')), Some((17, true)));
+        assert_eq!(button_code(InputButton::Right), BTN_RIGHT);
+    }
+}
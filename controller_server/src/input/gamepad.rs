@@ -0,0 +1,245 @@
+//! Virtual gamepads, one per connected client.
+//!
+//! On Linux each gamepad is a uinput device identified as an Xbox 360 controller,
+//! so games and emulators pick it up with their standard mapping.
+
+use crate::actions::GamepadEvent;
+
+/// A plugged-in virtual gamepad. Dropping it unplugs the device.
+pub trait VirtualGamepad: Send + Sync {
+    fn send(&mut self, event: &GamepadEvent) -> Result<(), String>;
+}
+
+/// Plugs in a new virtual gamepad for the given client
+pub fn create_gamepad(client_id: usize) -> Result<Box<dyn VirtualGamepad>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(platform::UinputGamepad::new(client_id)?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = client_id;
+        Err("Virtual gamepads are only supported on Linux".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::VirtualGamepad;
+    use crate::actions::{GamepadButton, GamepadEvent, GamepadSide};
+    use crate::input::{
+        evdev::*,
+        uinput::{AbsoluteAxis, UinputDevice, BUS_USB},
+    };
+
+    // Xbox 360 controller ids, recognized by SDL and most games
+    const XBOX_360_VENDOR_ID: u16 = 0x045e;
+    const XBOX_360_PRODUCT_ID: u16 = 0x028e;
+
+    const BUTTONS: [u16; 11] = [
+        BTN_A, BTN_B, BTN_X, BTN_Y, BTN_TL, BTN_TR, BTN_SELECT, BTN_START, BTN_MODE, BTN_THUMBL,
+        BTN_THUMBR,
+    ];
+
+    const STICK_AXES: [u16; 4] = [ABS_X, ABS_Y, ABS_RX, ABS_RY];
+    const TRIGGER_AXES: [u16; 2] = [ABS_Z, ABS_RZ];
+    const HAT_AXES: [u16; 2] = [ABS_HAT0X, ABS_HAT0Y];
+
+    /// Pressed state of the d-pad, reported as the hat axes
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub(super) struct Dpad {
+        up: bool,
+        down: bool,
+        left: bool,
+        right: bool,
+    }
+
+    impl Dpad {
+        fn hat(&self) -> (i32, i32) {
+            (
+                self.right as i32 - self.left as i32,
+                self.down as i32 - self.up as i32,
+            )
+        }
+    }
+
+    /// Translates a gamepad event into `(type, code, value)` evdev events,
+    /// updating the d-pad state along the way.
+    pub(super) fn evdev_events(dpad: &mut Dpad, event: &GamepadEvent) -> Vec<(u16, u16, i32)> {
+        match event {
+            GamepadEvent::Button { button, pressed } => {
+                let code = match button {
+                    GamepadButton::A => BTN_A,
+                    GamepadButton::B => BTN_B,
+                    GamepadButton::X => BTN_X,
+                    GamepadButton::Y => BTN_Y,
+                    GamepadButton::LeftBumper => BTN_TL,
+                    GamepadButton::RightBumper => BTN_TR,
+                    GamepadButton::Select => BTN_SELECT,
+                    GamepadButton::Start => BTN_START,
+                    GamepadButton::Mode => BTN_MODE,
+                    GamepadButton::LeftThumb => BTN_THUMBL,
+                    GamepadButton::RightThumb => BTN_THUMBR,
+                    GamepadButton::DpadUp => {
+                        dpad.up = *pressed;
+                        return hat_events(dpad);
+                    }
+                    GamepadButton::DpadDown => {
+                        dpad.down = *pressed;
+                        return hat_events(dpad);
+                    }
+                    GamepadButton::DpadLeft => {
+                        dpad.left = *pressed;
+                        return hat_events(dpad);
+                    }
+                    GamepadButton::DpadRight => {
+                        dpad.right = *pressed;
+                        return hat_events(dpad);
+                    }
+                };
+                vec![(EV_KEY, code, *pressed as i32)]
+            }
+            GamepadEvent::Stick { stick, x, y } => {
+                let (x_axis, y_axis) = match stick {
+                    GamepadSide::Left => (ABS_X, ABS_Y),
+                    GamepadSide::Right => (ABS_RX, ABS_RY),
+                };
+                vec![(EV_ABS, x_axis, *x as i32), (EV_ABS, y_axis, *y as i32)]
+            }
+            GamepadEvent::Trigger { trigger, value } => {
+                let axis = match trigger {
+                    GamepadSide::Left => ABS_Z,
+                    GamepadSide::Right => ABS_RZ,
+                };
+                vec![(EV_ABS, axis, *value as i32)]
+            }
+            GamepadEvent::Reset => {
+                *dpad = Dpad::default();
+                BUTTONS
+                    .iter()
+                    .map(|code| (EV_KEY, *code, 0))
+                    .chain(
+                        STICK_AXES
+                            .iter()
+                            .chain(&TRIGGER_AXES)
+                            .chain(&HAT_AXES)
+                            .map(|axis| (EV_ABS, *axis, 0)),
+                    )
+                    .collect()
+            }
+        }
+    }
+
+    fn hat_events(dpad: &Dpad) -> Vec<(u16, u16, i32)> {
+        let (x, y) = dpad.hat();
+        vec![(EV_ABS, ABS_HAT0X, x), (EV_ABS, ABS_HAT0Y, y)]
+    }
+
+    pub struct UinputGamepad {
+        device: UinputDevice,
+        dpad: Dpad,
+    }
+
+    impl UinputGamepad {
+        pub fn new(client_id: usize) -> Result<Self, String> {
+            let axis = |code, minimum, maximum, fuzz, flat| AbsoluteAxis {
+                code,
+                minimum,
+                maximum,
+                fuzz,
+                flat,
+                resolution: 0,
+            };
+            let sticks = STICK_AXES.map(|code| axis(code, -32768, 32767, 16, 128));
+            let triggers = TRIGGER_AXES.map(|code| axis(code, 0, 255, 0, 0));
+            let hat = HAT_AXES.map(|code| axis(code, -1, 1, 0, 0));
+
+            let device = UinputDevice::builder(&format!("Laze gamepad (client {})", client_id))
+                .with_id(BUS_USB, XBOX_360_VENDOR_ID, XBOX_360_PRODUCT_ID)
+                .with_keys(BUTTONS)
+                .with_absolute_axes(sticks.into_iter().chain(triggers).chain(hat))
+                .build()?;
+
+            Ok(Self {
+                device,
+                dpad: Dpad::default(),
+            })
+        }
+    }
+
+    impl VirtualGamepad for UinputGamepad {
+        fn send(&mut self, event: &GamepadEvent) -> Result<(), String> {
+            for (event_type, code, value) in evdev_events(&mut self.dpad, event) {
+                self.device.emit(event_type, code, value)?;
+            }
+            self.device.sync()
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::{evdev_events, Dpad};
+    use crate::actions::{GamepadButton, GamepadEvent, GamepadSide};
+    use crate::input::evdev::*;
+
+    fn button(button: GamepadButton, pressed: bool) -> GamepadEvent {
+        GamepadEvent::Button { button, pressed }
+    }
+
+    #[test]
+    fn buttons_and_axes() {
+        let mut dpad = Dpad::default();
+        assert_eq!(
+            evdev_events(&mut dpad, &button(GamepadButton::A, true)),
+            vec![(EV_KEY, BTN_A, 1)]
+        );
+        assert_eq!(
+            evdev_events(
+                &mut dpad,
+                &GamepadEvent::Stick {
+                    stick: GamepadSide::Right,
+                    x: -100,
+                    y: 200
+                }
+            ),
+            vec![(EV_ABS, ABS_RX, -100), (EV_ABS, ABS_RY, 200)]
+        );
+        assert_eq!(
+            evdev_events(
+                &mut dpad,
+                &GamepadEvent::Trigger {
+                    trigger: GamepadSide::Left,
+                    value: 255
+                }
+            ),
+            vec![(EV_ABS, ABS_Z, 255)]
+        );
+    }
+
+    #[test]
+    fn dpad_maps_to_hat_axes() {
+        let mut dpad = Dpad::default();
+        evdev_events(&mut dpad, &button(GamepadButton::DpadUp, true));
+        assert_eq!(
+            evdev_events(&mut dpad, &button(GamepadButton::DpadRight, true)),
+            vec![(EV_ABS, ABS_HAT0X, 1), (EV_ABS, ABS_HAT0Y, -1)]
+        );
+        assert_eq!(
+            evdev_events(&mut dpad, &button(GamepadButton::DpadUp, false)),
+            vec![(EV_ABS, ABS_HAT0X, 1), (EV_ABS, ABS_HAT0Y, 0)]
+        );
+    }
+
+    #[test]
+    fn reset_releases_everything() {
+        let mut dpad = Dpad::default();
+        evdev_events(&mut dpad, &button(GamepadButton::DpadLeft, true));
+
+        let events = evdev_events(&mut dpad, &GamepadEvent::Reset);
+        assert!(events.iter().all(|(_, _, value)| *value == 0));
+        assert!(events.contains(&(EV_KEY, BTN_START, 0)));
+        assert!(events.contains(&(EV_ABS, ABS_HAT0X, 0)));
+        assert_eq!(dpad, Dpad::default());
+    }
+}
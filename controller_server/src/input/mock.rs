@@ -0,0 +1,123 @@
+//! Input backend that records every event instead of emitting it.
+
+use std::sync::{Arc, Mutex};
+
+use super::{
+    gamepad::VirtualGamepad, tablet::VirtualTablet, Axis, Direction, InputBackend, InputButton,
+    InputKey,
+};
+use crate::actions::{GamepadEvent, PenEvent};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    MoveRelative(i32, i32),
+    Button(InputButton, Direction),
+    Key(InputKey, Direction),
+    Text(String),
+    Scroll(i32, Axis),
+    /// Event sent to the gamepad of a client
+    Gamepad(usize, GamepadEvent),
+    /// The gamepad of a client was unplugged
+    GamepadUnplugged(usize),
+    Pen(PenEvent),
+}
+
+/// Keeps every event in a shared list.
+/// Grab the list with `events()` before handing the backend over.
+#[derive(Default)]
+pub struct RecordingBackend {
+    events: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Arc<Mutex<Vec<InputEvent>>> {
+        Arc::clone(&self.events)
+    }
+
+    fn record(&self, event: InputEvent) -> Result<(), String> {
+        self.events.lock().unwrap().push(event);
+        Ok(())
+    }
+}
+
+impl InputBackend for RecordingBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) -> Result<(), String> {
+        self.record(InputEvent::MoveRelative(dx, dy))
+    }
+
+    fn button(&mut self, button: InputButton, direction: Direction) -> Result<(), String> {
+        self.record(InputEvent::Button(button, direction))
+    }
+
+    fn key(&mut self, key: InputKey, direction: Direction) -> Result<(), String> {
+        self.record(InputEvent::Key(key, direction))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        self.record(InputEvent::Text(text.to_string()))
+    }
+
+    fn scroll(&mut self, amount: i32, axis: Axis) -> Result<(), String> {
+        self.record(InputEvent::Scroll(amount, axis))
+    }
+
+    fn create_gamepad(&mut self, client_id: usize) -> Result<Box<dyn VirtualGamepad>, String> {
+        Ok(Box::new(RecordingGamepad {
+            client_id,
+            events: self.events(),
+        }))
+    }
+
+    fn create_tablet(&mut self) -> Result<Box<dyn VirtualTablet>, String> {
+        Ok(Box::new(RecordingTablet {
+            events: self.events(),
+        }))
+    }
+}
+
+struct RecordingTablet {
+    events: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl VirtualTablet for RecordingTablet {
+    fn send(&mut self, event: &PenEvent) -> Result<(), String> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(InputEvent::Pen(event.clone()));
+        Ok(())
+    }
+}
+
+/// Gamepad recording into the list of the backend that created it
+struct RecordingGamepad {
+    client_id: usize,
+    events: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl VirtualGamepad for RecordingGamepad {
+    fn send(&mut self, event: &GamepadEvent) -> Result<(), String> {
+        self.events
+            .lock()
+            .unwrap()
+            .push(InputEvent::Gamepad(self.client_id, event.clone()));
+        Ok(())
+    }
+}
+
+impl Drop for RecordingGamepad {
+    fn drop(&mut self) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(InputEvent::GamepadUnplugged(self.client_id));
+    }
+}
@@ -0,0 +1,246 @@
+//! Pluggable virtual input.
+//!
+//! `MobileController` only talks to an `InputBackend`, so the way input reaches the
+//! OS can be swapped:
+//! - `enigo` (default feature) - X11 / Windows / macOS through the enigo crate
+//! - `uinput` (Linux) - kernel virtual devices, works regardless of the display server
+//! - `mock` - records everything, used by the tests to run headless
+
+#[cfg(feature = "enigo")]
+pub mod enigo_backend;
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod gamepad;
+pub mod mock;
+pub mod tablet;
+#[cfg(target_os = "linux")]
+pub mod uinput;
+
+use std::{env, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use gamepad::VirtualGamepad;
+use tablet::VirtualTablet;
+
+/// Environment variable used to force a backend, e.g. `LAZE_INPUT_BACKEND=uinput`
+pub const BACKEND_ENV_VAR: &str = "LAZE_INPUT_BACKEND";
+
+/// Whether a key or button is pressed, released, or both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Press,
+    Release,
+    Click,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// Keys the backends know how to emit.
+/// Each backend translates these into its own key codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputKey {
+    Backspace,
+    Return,
+    Tab,
+    Escape,
+    Space,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    UpArrow,
+    DownArrow,
+    LeftArrow,
+    RightArrow,
+    Control,
+    Shift,
+    Alt,
+    Meta,
+    F4,
+    F5,
+    F11,
+    VolumeMute,
+    VolumeDown,
+    VolumeUp,
+    MediaPlayPause,
+    MediaStop,
+    MediaNext,
+    MediaPrevious,
+    BrightnessUp,
+    BrightnessDown,
+    /// A character key, e.g. `w` in Ctrl+W
+    Unicode(char),
+}
+
+/// Something able to inject pointer, button, key, text and scroll input into the OS.
+///
+/// Errors are returned as human readable messages - a failed input is logged,
+/// it never takes the client connection down.
+pub trait InputBackend: Send + Sync {
+    /// Short name used in logs, e.g. `enigo` or `uinput`
+    fn name(&self) -> &'static str;
+
+    fn move_relative(&mut self, dx: i32, dy: i32) -> Result<(), String>;
+
+    fn button(&mut self, button: InputButton, direction: Direction) -> Result<(), String>;
+
+    fn key(&mut self, key: InputKey, direction: Direction) -> Result<(), String>;
+
+    fn text(&mut self, text: &str) -> Result<(), String>;
+
+    /// Scrolls by `amount` wheel notches. Positive values scroll down / right.
+    fn scroll(&mut self, amount: i32, axis: Axis) -> Result<(), String>;
+
+    /// Presses every key in order, then releases them in reverse order.
+    fn key_combo(&mut self, keys: &[InputKey]) -> Result<(), String> {
+        for key in keys {
+            self.key(*key, Direction::Press)?;
+        }
+        for key in keys.iter().rev() {
+            self.key(*key, Direction::Release)?;
+        }
+        Ok(())
+    }
+
+    /// Plugs in a virtual gamepad for a client. Dropping it unplugs the gamepad.
+    fn create_gamepad(&mut self, client_id: usize) -> Result<Box<dyn VirtualGamepad>, String> {
+        gamepad::create_gamepad(client_id)
+    }
+
+    /// Plugs in a virtual pen tablet. Dropping it unplugs the tablet.
+    fn create_tablet(&mut self) -> Result<Box<dyn VirtualTablet>, String> {
+        tablet::create_tablet()
+    }
+}
+
+/// The backends that can be selected at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Enigo,
+    Uinput,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "enigo" => Ok(BackendKind::Enigo),
+            "uinput" => Ok(BackendKind::Uinput),
+            other => Err(format!("Unknown input backend: {}", other)),
+        }
+    }
+}
+
+/// Creates a specific backend.
+pub fn create_backend(kind: BackendKind) -> Result<Box<dyn InputBackend>, String> {
+    match kind {
+        #[cfg(feature = "enigo")]
+        BackendKind::Enigo => Ok(Box::new(enigo_backend::EnigoBackend::new()?)),
+        #[cfg(not(feature = "enigo"))]
+        BackendKind::Enigo => Err("This build does not include the enigo backend".to_string()),
+
+        #[cfg(target_os = "linux")]
+        BackendKind::Uinput => Ok(Box::new(uinput::UinputBackend::new()?)),
+        #[cfg(not(target_os = "linux"))]
+        BackendKind::Uinput => Err("The uinput backend is only available on Linux".to_string()),
+    }
+}
+
+/// Whether input has to go through uinput because there is no X server to talk to:
+/// a Wayland session (XWayland only reaches X clients), or no `DISPLAY` at all.
+pub fn needs_uinput(display: Option<&str>, session_type: Option<&str>) -> bool {
+    let wayland = session_type.is_some_and(|session| session.eq_ignore_ascii_case("wayland"));
+    let no_display = display.is_none_or(|display| display.trim().is_empty());
+    wayland || no_display
+}
+
+/// Creates the backend picked through `LAZE_INPUT_BACKEND`, or the best one for the
+/// session: uinput on Linux without an X server, enigo otherwise.
+/// If enigo can't start on Linux, uinput is tried before giving up.
+pub fn default_backend() -> Result<Box<dyn InputBackend>, String> {
+    if let Ok(requested) = env::var(BACKEND_ENV_VAR) {
+        return create_backend(requested.parse()?);
+    }
+
+    if !cfg!(feature = "enigo") {
+        return create_backend(BackendKind::Uinput);
+    }
+    if cfg!(target_os = "linux")
+        && needs_uinput(
+            env::var("DISPLAY").ok().as_deref(),
+            env::var("XDG_SESSION_TYPE").ok().as_deref(),
+        )
+    {
+        return create_backend(BackendKind::Uinput);
+    }
+
+    match create_backend(BackendKind::Enigo) {
+        Ok(backend) => Ok(backend),
+        Err(enigo_error) if cfg!(target_os = "linux") => create_backend(BackendKind::Uinput)
+            .map_err(|uinput_error| {
+                format!(
+                    "No input backend available. enigo: {}. uinput: {}",
+                    enigo_error, uinput_error
+                )
+            }),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::{InputEvent, RecordingBackend};
+
+    #[test]
+    fn parse_backend_kind() {
+        assert_eq!("enigo".parse(), Ok(BackendKind::Enigo));
+        assert_eq!(" UInput ".parse(), Ok(BackendKind::Uinput));
+        assert!("xdotool".parse::<BackendKind>().is_err());
+    }
+
+    #[test]
+    fn uinput_needed_without_x_server() {
+        assert!(needs_uinput(None, None));
+        assert!(needs_uinput(Some(""), Some("tty")));
+        assert!(needs_uinput(Some(":0"), Some("wayland")));
+        assert!(!needs_uinput(Some(":0"), Some("x11")));
+        assert!(!needs_uinput(Some(":1"), None));
+    }
+
+    #[test]
+    fn key_combo_releases_in_reverse_order() {
+        let mut backend = RecordingBackend::new();
+        let events = backend.events();
+
+        backend
+            .key_combo(&[InputKey::Control, InputKey::Unicode('w')])
+            .unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(InputKey::Control, Direction::Press),
+                InputEvent::Key(InputKey::Unicode('w'), Direction::Press),
+                InputEvent::Key(InputKey::Unicode('w'), Direction::Release),
+                InputEvent::Key(InputKey::Control, Direction::Release),
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Virtual pen tablet, turning the phone screen into a graphics tablet.
+//!
+//! On Linux this is a uinput tablet (`BTN_TOOL_PEN`, `ABS_PRESSURE`, `ABS_TILT_X/Y`)
+//! mapped onto the whole desktop, which Krita, GIMP and other drawing programs read
+//! like any stylus.
+
+use crate::actions::PenEvent;
+
+/// A plugged-in virtual tablet. Dropping it unplugs the device.
+pub trait VirtualTablet: Send + Sync {
+    fn send(&mut self, event: &PenEvent) -> Result<(), String>;
+}
+
+/// Plugs in a new virtual tablet
+pub fn create_tablet() -> Result<Box<dyn VirtualTablet>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(platform::UinputTablet::new()?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Pen tablets are only supported on Linux".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::VirtualTablet;
+    use crate::actions::PenEvent;
+    use crate::input::{
+        evdev::*,
+        uinput::{AbsoluteAxis, UinputDevice},
+    };
+
+    /// Pen position range on both axes
+    pub(super) const POSITION_MAX: i32 = u16::MAX as i32;
+    pub(super) const PRESSURE_MAX: i32 = u16::MAX as i32;
+    /// Tilt range in degrees
+    pub(super) const TILT_MAX: i32 = 90;
+
+    /// Translates the pen state into `(type, code, value)` evdev events
+    pub(super) fn evdev_events(event: &PenEvent) -> Vec<(u16, u16, i32)> {
+        if !event.in_range {
+            // the pen left proximity
+            return vec![
+                (EV_ABS, ABS_PRESSURE, 0),
+                (EV_KEY, BTN_TOUCH, 0),
+                (EV_KEY, BTN_STYLUS, 0),
+                (EV_KEY, BTN_TOOL_PEN, 0),
+            ];
+        }
+
+        let pressure = if event.touching {
+            event.pressure as i32
+        } else {
+            0
+        };
+        vec![
+            (EV_ABS, ABS_X, event.x as i32),
+            (EV_ABS, ABS_Y, event.y as i32),
+            (EV_ABS, ABS_PRESSURE, pressure),
+            (
+                EV_ABS,
+                ABS_TILT_X,
+                (event.tilt_x as i32).clamp(-TILT_MAX, TILT_MAX),
+            ),
+            (
+                EV_ABS,
+                ABS_TILT_Y,
+                (event.tilt_y as i32).clamp(-TILT_MAX, TILT_MAX),
+            ),
+            (EV_KEY, BTN_TOOL_PEN, 1),
+            (EV_KEY, BTN_TOUCH, event.touching as i32),
+            (EV_KEY, BTN_STYLUS, event.barrel_button as i32),
+        ]
+    }
+
+    pub struct UinputTablet {
+        device: UinputDevice,
+    }
+
+    impl UinputTablet {
+        pub fn new() -> Result<Self, String> {
+            let axis = |code, minimum, maximum, resolution| AbsoluteAxis {
+                code,
+                minimum,
+                maximum,
+                fuzz: 0,
+                flat: 0,
+                resolution,
+            };
+            // libinput needs a resolution to accept a tablet: pretend it is ~33cm wide,
+            // and report tilt in degrees (57 per radian)
+            let device = UinputDevice::builder("Laze pen tablet")
+                .with_keys([BTN_TOOL_PEN, BTN_TOUCH, BTN_STYLUS])
+                .with_absolute_axes([
+                    axis(ABS_X, 0, POSITION_MAX, 200),
+                    axis(ABS_Y, 0, POSITION_MAX, 200),
+                    axis(ABS_PRESSURE, 0, PRESSURE_MAX, 0),
+                    axis(ABS_TILT_X, -TILT_MAX, TILT_MAX, 57),
+                    axis(ABS_TILT_Y, -TILT_MAX, TILT_MAX, 57),
+                ])
+                .with_properties([INPUT_PROP_DIRECT])
+                .build()?;
+            Ok(Self { device })
+        }
+    }
+
+    impl VirtualTablet for UinputTablet {
+        fn send(&mut self, event: &PenEvent) -> Result<(), String> {
+            for (event_type, code, value) in evdev_events(event) {
+                self.device.emit(event_type, code, value)?;
+            }
+            self.device.sync()
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::platform::evdev_events;
+    use crate::actions::PenEvent;
+    use crate::input::evdev::*;
+
+    fn pen(touching: bool) -> PenEvent {
+        PenEvent {
+            x: 1000,
+            y: 2000,
+            pressure: 30000,
+            tilt_x: -120,
+            tilt_y: 45,
+            in_range: true,
+            touching,
+            barrel_button: false,
+        }
+    }
+
+    #[test]
+    fn drawing_reports_pressure_and_tilt() {
+        let events = evdev_events(&pen(true));
+        assert!(events.contains(&(EV_ABS, ABS_X, 1000)));
+        assert!(events.contains(&(EV_ABS, ABS_PRESSURE, 30000)));
+        assert!(events.contains(&(EV_ABS, ABS_TILT_X, -90)));
+        assert!(events.contains(&(EV_ABS, ABS_TILT_Y, 45)));
+        assert!(events.contains(&(EV_KEY, BTN_TOUCH, 1)));
+    }
+
+    #[test]
+    fn hovering_has_no_pressure() {
+        let events = evdev_events(&pen(false));
+        assert!(events.contains(&(EV_KEY, BTN_TOOL_PEN, 1)));
+        assert!(events.contains(&(EV_KEY, BTN_TOUCH, 0)));
+        assert!(events.contains(&(EV_ABS, ABS_PRESSURE, 0)));
+    }
+
+    #[test]
+    fn leaving_proximity_releases_the_pen() {
+        let mut away = pen(false);
+        away.in_range = false;
+        let events = evdev_events(&away);
+        assert_eq!(events.last(), Some(&(EV_KEY, BTN_TOOL_PEN, 0)));
+        assert!(!events.iter().any(|(_, _, value)| *value != 0));
+    }
+}
@@ -0,0 +1,445 @@
+//! Virtual input devices through the Linux `/dev/uinput` interface.
+//!
+//! Works on X11, Wayland and the console alike, since the events are injected
+//! at the kernel level. Requires write access to `/dev/uinput`.
+//!
+//! The backend registers three devices - a keyboard, a mouse and a scroll wheel with
+//! high-resolution scrolling - so compositors classify each one correctly.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use super::{
+    evdev::{self, EV_ABS, EV_KEY, EV_REL, EV_SYN, SYN_REPORT},
+    Axis, Direction, InputBackend, InputButton, InputKey,
+};
+
+pub const UINPUT_PATH: &str = "/dev/uinput";
+
+pub const BUS_USB: u16 = 0x03;
+pub const BUS_VIRTUAL: u16 = 0x06;
+
+/// Vendor / product ids of the Laze input devices
+const LAZE_VENDOR_ID: u16 = 0x1209;
+const LAZE_PRODUCT_ID: u16 = 0x1a2e;
+
+// ioctl request codes from `linux/uinput.h`, computed as _IO / _IOW('U', nr, size)
+const fn ioc(direction: u64, nr: u64, size: u64) -> u64 {
+    (direction << 30) | (size << 16) | ((b'U' as u64) << 8) | nr
+}
+const IOC_NONE: u64 = 0;
+const IOC_WRITE: u64 = 1;
+const INT_SIZE: u64 = std::mem::size_of::<libc::c_int>() as u64;
+
+const UI_DEV_CREATE: u64 = ioc(IOC_NONE, 1, 0);
+const UI_DEV_DESTROY: u64 = ioc(IOC_NONE, 2, 0);
+const UI_DEV_SETUP: u64 = ioc(
+    IOC_WRITE,
+    3,
+    std::mem::size_of::<libc::uinput_setup>() as u64,
+);
+const UI_ABS_SETUP: u64 = ioc(
+    IOC_WRITE,
+    4,
+    std::mem::size_of::<libc::uinput_abs_setup>() as u64,
+);
+const UI_SET_EVBIT: u64 = ioc(IOC_WRITE, 100, INT_SIZE);
+const UI_SET_KEYBIT: u64 = ioc(IOC_WRITE, 101, INT_SIZE);
+const UI_SET_RELBIT: u64 = ioc(IOC_WRITE, 102, INT_SIZE);
+const UI_SET_ABSBIT: u64 = ioc(IOC_WRITE, 103, INT_SIZE);
+const UI_SET_PROPBIT: u64 = ioc(IOC_WRITE, 110, INT_SIZE);
+
+/// Range of an absolute axis, such as a gamepad stick
+#[derive(Debug, Clone, Copy)]
+pub struct AbsoluteAxis {
+    pub code: u16,
+    pub minimum: i32,
+    pub maximum: i32,
+    /// Noise filtered out by the kernel
+    pub fuzz: i32,
+    /// Dead zone around the center
+    pub flat: i32,
+    /// Units per millimeter (per radian for tilt), 0 when meaningless
+    pub resolution: i32,
+}
+
+/// Describes the capabilities of a device before creating it
+pub struct UinputDeviceBuilder {
+    name: String,
+    bus_type: u16,
+    vendor: u16,
+    product: u16,
+    keys: Vec<u16>,
+    relative_axes: Vec<u16>,
+    absolute_axes: Vec<AbsoluteAxis>,
+    properties: Vec<u16>,
+}
+
+impl UinputDeviceBuilder {
+    pub fn with_keys(mut self, keys: impl IntoIterator<Item = u16>) -> Self {
+        self.keys.extend(keys);
+        self
+    }
+
+    pub fn with_relative_axes(mut self, axes: impl IntoIterator<Item = u16>) -> Self {
+        self.relative_axes.extend(axes);
+        self
+    }
+
+    pub fn with_absolute_axes(mut self, axes: impl IntoIterator<Item = AbsoluteAxis>) -> Self {
+        self.absolute_axes.extend(axes);
+        self
+    }
+
+    pub fn with_properties(mut self, properties: impl IntoIterator<Item = u16>) -> Self {
+        self.properties.extend(properties);
+        self
+    }
+
+    /// Identifies the device as a specific piece of hardware, so applications apply
+    /// their known mappings to it
+    pub fn with_id(mut self, bus_type: u16, vendor: u16, product: u16) -> Self {
+        self.bus_type = bus_type;
+        self.vendor = vendor;
+        self.product = product;
+        self
+    }
+
+    pub fn build(self) -> Result<UinputDevice, String> {
+        self.build_at(Path::new(UINPUT_PATH))
+    }
+
+    pub fn build_at(self, path: &Path) -> Result<UinputDevice, String> {
+        let file = OpenOptions::new().write(true).open(path).map_err(|e| {
+            format!(
+                "Failed to open {}: {}. {}",
+                path.display(),
+                e,
+                open_error_hint(&e)
+            )
+        })?;
+        let device = UinputDevice {
+            file,
+            name: self.name,
+        };
+
+        if !self.keys.is_empty() {
+            device.ioctl_int(UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+            for key in &self.keys {
+                device.ioctl_int(UI_SET_KEYBIT, *key as libc::c_int)?;
+            }
+        }
+        if !self.relative_axes.is_empty() {
+            device.ioctl_int(UI_SET_EVBIT, EV_REL as libc::c_int)?;
+            for axis in &self.relative_axes {
+                device.ioctl_int(UI_SET_RELBIT, *axis as libc::c_int)?;
+            }
+        }
+        if !self.absolute_axes.is_empty() {
+            device.ioctl_int(UI_SET_EVBIT, EV_ABS as libc::c_int)?;
+            for axis in &self.absolute_axes {
+                device.ioctl_int(UI_SET_ABSBIT, axis.code as libc::c_int)?;
+            }
+        }
+        for property in &self.properties {
+            device.ioctl_int(UI_SET_PROPBIT, *property as libc::c_int)?;
+        }
+
+        // SAFETY: uinput_setup is a plain C struct, all-zero is a valid value
+        let mut setup: libc::uinput_setup = unsafe { std::mem::zeroed() };
+        setup.id.bustype = self.bus_type;
+        setup.id.vendor = self.vendor;
+        setup.id.product = self.product;
+        setup.id.version = 1;
+        for (dst, src) in setup
+            .name
+            .iter_mut()
+            .zip(device.name.bytes().take(libc::UINPUT_MAX_NAME_SIZE - 1))
+        {
+            *dst = src as libc::c_char;
+        }
+
+        // SAFETY: the fd is open for the lifetime of `device`, and `setup` outlives the call
+        let result = unsafe { libc::ioctl(device.file.as_raw_fd(), UI_DEV_SETUP as _, &setup) };
+        if result < 0 {
+            return Err(device.last_error("UI_DEV_SETUP"));
+        }
+        for axis in &self.absolute_axes {
+            // SAFETY: uinput_abs_setup is a plain C struct, all-zero is a valid value
+            let mut abs_setup: libc::uinput_abs_setup = unsafe { std::mem::zeroed() };
+            abs_setup.code = axis.code;
+            abs_setup.absinfo.minimum = axis.minimum;
+            abs_setup.absinfo.maximum = axis.maximum;
+            abs_setup.absinfo.fuzz = axis.fuzz;
+            abs_setup.absinfo.flat = axis.flat;
+            abs_setup.absinfo.resolution = axis.resolution;
+            // SAFETY: see above
+            let result =
+                unsafe { libc::ioctl(device.file.as_raw_fd(), UI_ABS_SETUP as _, &abs_setup) };
+            if result < 0 {
+                return Err(device.last_error("UI_ABS_SETUP"));
+            }
+        }
+        // SAFETY: see above
+        let result = unsafe { libc::ioctl(device.file.as_raw_fd(), UI_DEV_CREATE as _) };
+        if result < 0 {
+            return Err(device.last_error("UI_DEV_CREATE"));
+        }
+
+        Ok(device)
+    }
+}
+
+/// A virtual device registered with the kernel. Destroyed when dropped.
+pub struct UinputDevice {
+    file: File,
+    name: String,
+}
+
+impl UinputDevice {
+    pub fn builder(name: &str) -> UinputDeviceBuilder {
+        UinputDeviceBuilder {
+            name: name.to_string(),
+            bus_type: BUS_VIRTUAL,
+            vendor: LAZE_VENDOR_ID,
+            product: LAZE_PRODUCT_ID,
+            keys: Vec::new(),
+            relative_axes: Vec::new(),
+            absolute_axes: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    fn ioctl_int(&self, request: u64, value: libc::c_int) -> Result<(), String> {
+        // SAFETY: the fd is valid, and these requests take an int by value
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, value) };
+        if result < 0 {
+            Err(self.last_error("ioctl"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn last_error(&self, operation: &str) -> String {
+        format!(
+            "{} failed for uinput device '{}': {}",
+            operation,
+            self.name,
+            std::io::Error::last_os_error()
+        )
+    }
+
+    /// Queues a single event. Call `sync` to flush a group of events.
+    pub fn emit(&mut self, event_type: u16, code: u16, value: i32) -> Result<(), String> {
+        // SAFETY: input_event is a plain C struct, all-zero is a valid value
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.type_ = event_type;
+        event.code = code;
+        event.value = value;
+
+        // SAFETY: input_event is repr(C) and fully initialized
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const libc::input_event as *const u8,
+                std::mem::size_of::<libc::input_event>(),
+            )
+        };
+        self.file
+            .write_all(bytes)
+            .map_err(|e| format!("Failed to write to uinput device '{}': {}", self.name, e))
+    }
+
+    pub fn sync(&mut self) -> Result<(), String> {
+        self.emit(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        // SAFETY: the fd is still open
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY as _);
+        }
+    }
+}
+
+/// Explains how to fix a failure to open `/dev/uinput`
+pub fn open_error_hint(error: &io::Error) -> &'static str {
+    match error.kind() {
+        io::ErrorKind::NotFound => {
+            "The uinput kernel module is not loaded, load it with `sudo modprobe uinput`"
+        }
+        io::ErrorKind::PermissionDenied => {
+            "The current user can't write to it. Add a udev rule such as \
+             `KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", TAG+=\"uaccess\"` in \
+             /etc/udev/rules.d/60-laze-uinput.rules, add the user to the `input` group, \
+             then log in again"
+        }
+        _ => "Make sure uinput is enabled in the kernel",
+    }
+}
+
+/// Input backend emitting through virtual keyboard, mouse and scroll wheel devices
+pub struct UinputBackend {
+    keyboard: UinputDevice,
+    mouse: UinputDevice,
+    scroll_wheel: UinputDevice,
+}
+
+impl UinputBackend {
+    pub fn new() -> Result<Self, String> {
+        let keyboard = UinputDevice::builder("Laze virtual keyboard")
+            .with_keys(1..=evdev::KEY_MAX_KEYBOARD)
+            .build()?;
+        let mouse = UinputDevice::builder("Laze virtual mouse")
+            .with_keys([evdev::BTN_LEFT, evdev::BTN_RIGHT, evdev::BTN_MIDDLE])
+            .with_relative_axes([evdev::REL_X, evdev::REL_Y])
+            .build()?;
+        let scroll_wheel = UinputDevice::builder("Laze virtual scroll wheel")
+            .with_relative_axes([
+                evdev::REL_WHEEL,
+                evdev::REL_HWHEEL,
+                evdev::REL_WHEEL_HI_RES,
+                evdev::REL_HWHEEL_HI_RES,
+            ])
+            .build()?;
+
+        Ok(Self {
+            keyboard,
+            mouse,
+            scroll_wheel,
+        })
+    }
+
+    fn press(device: &mut UinputDevice, code: u16, direction: Direction) -> Result<(), String> {
+        match direction {
+            Direction::Press => device.emit(EV_KEY, code, 1)?,
+            Direction::Release => device.emit(EV_KEY, code, 0)?,
+            Direction::Click => {
+                device.emit(EV_KEY, code, 1)?;
+                device.sync()?;
+                device.emit(EV_KEY, code, 0)?;
+            }
+        }
+        device.sync()
+    }
+
+    fn type_char(&mut self, c: char) -> Result<(), String> {
+        let (code, shift) =
+            evdev::char_code(c).ok_or_else(|| format!("Cannot type '{}' through uinput", c))?;
+        if shift {
+            Self::press(&mut self.keyboard, evdev::KEY_LEFTSHIFT, Direction::Press)?;
+        }
+        Self::press(&mut self.keyboard, code, Direction::Click)?;
+        if shift {
+            Self::press(&mut self.keyboard, evdev::KEY_LEFTSHIFT, Direction::Release)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wheel events for scrolling `amount` notches: the legacy axis and its hi-res twin.
+/// Wheel values are positive when scrolling up / right.
+fn scroll_events(amount: i32, axis: Axis) -> [(u16, i32); 2] {
+    match axis {
+        Axis::Vertical => [
+            (evdev::REL_WHEEL, -amount),
+            (
+                evdev::REL_WHEEL_HI_RES,
+                -amount * evdev::WHEEL_HI_RES_PER_NOTCH,
+            ),
+        ],
+        Axis::Horizontal => [
+            (evdev::REL_HWHEEL, amount),
+            (
+                evdev::REL_HWHEEL_HI_RES,
+                amount * evdev::WHEEL_HI_RES_PER_NOTCH,
+            ),
+        ],
+    }
+}
+
+impl InputBackend for UinputBackend {
+    fn name(&self) -> &'static str {
+        "uinput"
+    }
+
+    fn move_relative(&mut self, dx: i32, dy: i32) -> Result<(), String> {
+        self.mouse.emit(EV_REL, evdev::REL_X, dx)?;
+        self.mouse.emit(EV_REL, evdev::REL_Y, dy)?;
+        self.mouse.sync()
+    }
+
+    fn button(&mut self, button: InputButton, direction: Direction) -> Result<(), String> {
+        Self::press(&mut self.mouse, evdev::button_code(button), direction)
+    }
+
+    fn key(&mut self, key: InputKey, direction: Direction) -> Result<(), String> {
+        let (code, _) =
+            evdev::key_code(key).ok_or_else(|| format!("Key {:?} has no key code", key))?;
+        Self::press(&mut self.keyboard, code, direction)
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        text.chars().try_for_each(|c| self.type_char(c))
+    }
+
+    fn scroll(&mut self, amount: i32, axis: Axis) -> Result<(), String> {
+        for (code, value) in scroll_events(amount, axis) {
+            self.scroll_wheel.emit(EV_REL, code, value)?;
+        }
+        self.scroll_wheel.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_request_codes() {
+        // values from linux/uinput.h on x86_64
+        assert_eq!(UI_DEV_CREATE, 0x5501);
+        assert_eq!(UI_DEV_DESTROY, 0x5502);
+        assert_eq!(UI_DEV_SETUP, 0x405c_5503);
+        assert_eq!(UI_SET_EVBIT, 0x4004_5564);
+        assert_eq!(UI_SET_KEYBIT, 0x4004_5565);
+        assert_eq!(UI_SET_RELBIT, 0x4004_5566);
+        assert_eq!(UI_SET_ABSBIT, 0x4004_5567);
+        assert_eq!(UI_SET_PROPBIT, 0x4004_556e);
+        assert_eq!(UI_ABS_SETUP, 0x401c_5504);
+    }
+
+    #[test]
+    fn missing_device_node_is_reported() {
+        let result = UinputDevice::builder("test")
+            .with_keys([evdev::KEY_ESC])
+            .build_at(Path::new("/nonexistent/uinput"));
+        let error = result.err().unwrap();
+        assert!(error.contains("/nonexistent/uinput"));
+        assert!(error.contains("modprobe uinput"));
+    }
+
+    #[test]
+    fn permission_denied_suggests_udev_rule() {
+        let hint = open_error_hint(&io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(hint.contains("udev rule"));
+        assert!(hint.contains("input"));
+    }
+
+    #[test]
+    fn scroll_emits_legacy_and_hi_res_wheel() {
+        assert_eq!(
+            scroll_events(2, Axis::Vertical),
+            [(evdev::REL_WHEEL, -2), (evdev::REL_WHEEL_HI_RES, -240)]
+        );
+        assert_eq!(
+            scroll_events(-1, Axis::Horizontal),
+            [(evdev::REL_HWHEEL, -1), (evdev::REL_HWHEEL_HI_RES, -120)]
+        );
+    }
+}
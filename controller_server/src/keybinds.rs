@@ -1,12 +1,27 @@
+//! Key combos pressed for the keys of the phone.
+//!
+//! The defaults follow the OS conventions. The file named by `key_bindings` in the
+//! server config replaces the combos of the keys it lists:
+//!
+//! ```json
+//! {
+//!     "CloseTab": ["control", "shift", { "unicode": "w" }],
+//!     "AltTab": ["alt", "tab"]
+//! }
+//! ```
+
 use crate::actions;
-use std::collections::HashMap;
+use crate::input::{InputButton, InputKey};
+use crate::logger::Loggable;
+use crate::paths;
+use std::{collections::HashMap, path::Path};
 
-type KeyCombo = Vec<enigo::Key>;
+type KeyCombo = Vec<InputKey>;
 
 pub struct KeyBindings {
-    // binds an action into the respective OS key code
+    // binds an action into the respective OS key combo
     key_bindings: HashMap<actions::Key, KeyCombo>,
-    button_bindings: HashMap<actions::Button, enigo::Button>,
+    button_bindings: HashMap<actions::Button, InputButton>,
 }
 
 impl KeyBindings {
@@ -17,11 +32,30 @@ impl KeyBindings {
         }
     }
 
+    /// The defaults, overridden by the key bindings file if there is one
+    pub fn load(file: Option<&Path>) -> Self {
+        let mut bindings = Self::new();
+        if let Some(path) = file {
+            if let Err(e) = bindings.override_from(path) {
+                Self::static_log_warn(&format!("Ignoring {}: {}", path.display(), e));
+            }
+        }
+        bindings
+    }
+
+    /// Replaces the combos of the keys listed in the file
+    pub fn override_from(&mut self, path: &Path) -> Result<(), String> {
+        let overrides: HashMap<actions::Key, KeyCombo> =
+            paths::load_json(path)?.ok_or_else(|| "No such file".to_string())?;
+        self.key_bindings.extend(overrides);
+        Ok(())
+    }
+
     pub fn translate_to_os_key(&self, key: &actions::Key) -> Option<KeyCombo> {
         self.key_bindings.get(key).cloned()
     }
 
-    pub fn translate_to_os_button(&self, button: &actions::Button) -> Option<enigo::Button> {
+    pub fn translate_to_os_button(&self, button: &actions::Button) -> Option<InputButton> {
         self.button_bindings.get(button).cloned()
     }
 }
@@ -30,52 +64,92 @@ impl KeyBindings {
 /// underlying OS keys
 impl KeyBindings {
     fn get_key_mapping() -> HashMap<actions::Key, KeyCombo> {
-        return HashMap::from([
+        HashMap::from([
             // keyboard
-            (actions::Key::Backspace, vec![enigo::Key::Backspace]),
-            (actions::Key::VolumeMute, vec![enigo::Key::VolumeMute]),
-            (actions::Key::VolumeDown, vec![enigo::Key::VolumeDown]),
-            (actions::Key::VolumeUp, vec![enigo::Key::VolumeUp]),
-            (actions::Key::Pause, vec![enigo::Key::MediaStop]),
-            (actions::Key::Play, vec![enigo::Key::MediaPlayPause]),
-            (actions::Key::Enter, vec![enigo::Key::Return]),
-            (actions::Key::Fullscreen, vec![enigo::Key::F11]),
+            (actions::Key::Backspace, vec![InputKey::Backspace]),
+            (actions::Key::VolumeMute, vec![InputKey::VolumeMute]),
+            (actions::Key::VolumeDown, vec![InputKey::VolumeDown]),
+            (actions::Key::VolumeUp, vec![InputKey::VolumeUp]),
+            (actions::Key::Pause, vec![InputKey::MediaStop]),
+            (actions::Key::Play, vec![InputKey::MediaPlayPause]),
+            (actions::Key::Enter, vec![InputKey::Return]),
+            (actions::Key::Fullscreen, vec![InputKey::F11]),
             (
                 actions::Key::CloseTab,
-                vec![enigo::Key::Control, enigo::Key::Unicode('w')],
+                vec![InputKey::Control, InputKey::Unicode('w')],
             ),
             // NextTab = Ctrl+Tab (standard: move forward/right), PreviousTab = Ctrl+Shift+Tab (standard: move backward/left)
             (
                 actions::Key::NextTab,
-                vec![enigo::Key::Control, enigo::Key::Tab],
+                vec![InputKey::Control, InputKey::Tab],
             ),
             (
                 actions::Key::PreviousTab,
-                vec![enigo::Key::Control, enigo::Key::Shift, enigo::Key::Tab],
+                vec![InputKey::Control, InputKey::Shift, InputKey::Tab],
             ),
             // OS-specific "show all apps" shortcut:
             // - Linux: Super key (GNOME overview / activities)
             // - Windows: Win+Tab (Task View)
             // - macOS: Control+Up (Mission Control)
             #[cfg(target_os = "linux")]
-            (actions::Key::AltTab, vec![enigo::Key::Meta]),
+            (actions::Key::AltTab, vec![InputKey::Meta]),
             #[cfg(target_os = "windows")]
-            (
-                actions::Key::AltTab,
-                vec![enigo::Key::Meta, enigo::Key::Tab],
-            ),
+            (actions::Key::AltTab, vec![InputKey::Meta, InputKey::Tab]),
             #[cfg(target_os = "macos")]
             (
                 actions::Key::AltTab,
-                vec![enigo::Key::Control, enigo::Key::UpArrow],
+                vec![InputKey::Control, InputKey::UpArrow],
             ),
-        ]);
+            // Brightness keys - only a fallback, used when the backlight can't be set directly.
+            #[cfg(not(target_os = "windows"))]
+            (actions::Key::BrightnessUp, vec![InputKey::BrightnessUp]),
+            #[cfg(not(target_os = "windows"))]
+            (actions::Key::BrightnessDown, vec![InputKey::BrightnessDown]),
+        ])
     }
 
-    fn get_button_mapping() -> HashMap<actions::Button, enigo::Button> {
-        return HashMap::from([
-            // keyboard
-            (actions::Button::Left, enigo::Button::Left),
-        ]);
+    fn get_button_mapping() -> HashMap<actions::Button, InputButton> {
+        HashMap::from([
+            // mouse
+            (actions::Button::Left, InputButton::Left),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn file_overrides_the_listed_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        fs::write(
+            &path,
+            r#"{ "CloseTab": ["control", "shift", { "unicode": "w" }] }"#,
+        )
+        .unwrap();
+
+        let bindings = KeyBindings::load(Some(&path));
+        assert_eq!(
+            bindings.translate_to_os_key(&actions::Key::CloseTab),
+            Some(vec![
+                InputKey::Control,
+                InputKey::Shift,
+                InputKey::Unicode('w')
+            ])
+        );
+        assert_eq!(
+            bindings.translate_to_os_key(&actions::Key::Enter),
+            Some(vec![InputKey::Return])
+        );
+
+        fs::write(&path, r#"{ "CloseTab": "nothing" }"#).unwrap();
+        let bindings = KeyBindings::load(Some(&path));
+        assert_eq!(
+            bindings.translate_to_os_key(&actions::Key::CloseTab),
+            Some(vec![InputKey::Control, InputKey::Unicode('w')])
+        );
     }
 }
@@ -0,0 +1,773 @@
+//! Application launcher built from the XDG desktop entries.
+//!
+//! Installed applications are found through their `.desktop` files in the
+//! `applications` directory of `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`. The phone
+//! searches them by name and launches one by its desktop file id, e.g.
+//! `org.gnome.Nautilus.desktop`. The `Exec` line is split into arguments and run
+//! directly, never through a shell, so nothing the phone sends ends up in a command line.
+//!
+//! The directories are scanned once and again only when they change or the scan
+//! gets old, not on every search. Each phone gets an app's icon once and keeps it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::logger::Loggable;
+
+/// Icon sizes looked up in the hicolor theme, best first
+const ICON_SIZES: [&str; 6] = ["48x48", "64x64", "32x32", "96x96", "128x128", "256x256"];
+
+/// Icons bigger than this are left out of the list, they would weigh it down
+const MAX_ICON_BYTES: u64 = 64 * 1024;
+
+/// Most apps sent for one search, the phone asks again as the query narrows
+const MAX_RESULTS: usize = 50;
+
+/// Apps installed into subdirectories don't change the mtime of `applications`,
+/// so the index is also rebuilt once it is this old
+const MAX_SCAN_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// An application as listed on the phone
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AppInfo {
+    /// Desktop file id, used to launch the app
+    pub id: String,
+    pub name: String,
+    /// What kind of app it is, e.g. `Web Browser`
+    pub generic_name: Option<String>,
+    pub comment: Option<String>,
+    /// Base64 encoded PNG of the app icon. Only sent the first time the app is
+    /// listed to a client, which keeps it by app id.
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct DesktopEntry {
+    info: AppInfo,
+    keywords: Vec<String>,
+    exec: String,
+    /// Icon name or path, as written in the file
+    icon_name: Option<String>,
+    /// Working directory to start the app in
+    working_dir: Option<PathBuf>,
+    terminal: bool,
+    file: PathBuf,
+}
+
+/// Index of the installed applications
+pub struct AppLauncher {
+    /// Data directories, most important first
+    data_dirs: Vec<PathBuf>,
+    entries: Vec<DesktopEntry>,
+    /// When the last refresh happened and the mtimes of the `applications`
+    /// directories it saw
+    scanned: Option<(Instant, Vec<Option<SystemTime>>)>,
+    /// Ids of the apps whose icon each client already has
+    icons_sent: HashMap<usize, HashSet<String>>,
+}
+
+impl AppLauncher {
+    /// Launcher over the XDG data directories. Nothing is read until `refresh`.
+    pub fn new() -> Self {
+        Self::with_data_dirs(xdg_data_dirs())
+    }
+
+    /// Launcher over the given data directories, most important first
+    pub fn with_data_dirs(data_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            data_dirs,
+            entries: Vec::new(),
+            scanned: None,
+            icons_sent: HashMap::new(),
+        }
+    }
+
+    /// Refreshes the index if it was never read, an `applications` directory
+    /// changed since, or it is older than `MAX_SCAN_AGE`
+    pub fn refresh_if_stale(&mut self) {
+        let stale = match &self.scanned {
+            Some((at, mtimes)) => at.elapsed() > MAX_SCAN_AGE || *mtimes != self.mtimes(),
+            None => true,
+        };
+        if stale {
+            self.refresh();
+        }
+    }
+
+    /// Reads the desktop entries again, picking up installed and removed apps
+    pub fn refresh(&mut self) {
+        self.scanned = Some((Instant::now(), self.mtimes()));
+        // icons may have changed with the apps
+        self.icons_sent.clear();
+        let languages = languages();
+        let desktops = current_desktops();
+        let mut seen = HashSet::new();
+        let mut icons = HashMap::new();
+        self.entries.clear();
+
+        for data_dir in &self.data_dirs {
+            let applications = data_dir.join("applications");
+            let mut files = Vec::new();
+            collect_desktop_files(&applications, &mut files);
+            files.sort();
+
+            for file in files {
+                let id = desktop_file_id(&applications, &file);
+                // the first directory defining an id wins, even to hide the app
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let entry = match fs::read_to_string(&file) {
+                    Ok(content) => parse_entry(&id, &file, &content, &languages, &desktops),
+                    Err(e) => {
+                        self.log_debug(&format!("Skipping {}: {}", file.display(), e));
+                        continue;
+                    }
+                };
+                if let Some(mut entry) = entry {
+                    if let Some(name) = &entry.icon_name {
+                        entry.info.icon = icons
+                            .entry(name.clone())
+                            .or_insert_with(|| load_icon(&self.data_dirs, name))
+                            .clone();
+                    }
+                    self.entries.push(entry);
+                }
+            }
+        }
+        self.entries
+            .sort_by_key(|entry| entry.info.name.to_lowercase());
+    }
+
+    /// Apps matching the query, best matches first, at most `MAX_RESULTS`.
+    /// An empty query lists the first apps by name.
+    pub fn search(&self, query: &str) -> Vec<AppInfo> {
+        let query = query.trim().to_lowercase();
+        let mut matches: Vec<(u8, &DesktopEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| match_rank(entry, &query).map(|rank| (rank, entry)))
+            .collect();
+        // entries are sorted by name already, keep that order within a rank
+        matches.sort_by_key(|(rank, _)| *rank);
+        matches
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, entry)| entry.info.clone())
+            .collect()
+    }
+
+    /// `search` for a client, leaving out the icons it was already sent
+    pub fn search_for(&mut self, client_id: usize, query: &str) -> Vec<AppInfo> {
+        let mut apps = self.search(query);
+        let icons_sent = self.icons_sent.entry(client_id).or_default();
+        for app in &mut apps {
+            if app.icon.is_some() && !icons_sent.insert(app.id.clone()) {
+                app.icon = None;
+            }
+        }
+        apps
+    }
+
+    /// Forgets which icons the client has, its next search sends them again
+    pub fn client_disconnected(&mut self, client_id: usize) {
+        self.icons_sent.remove(&client_id);
+    }
+
+    /// Starts the app with the given desktop file id
+    pub fn launch(&self, id: &str) -> Result<(), String> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.info.id == id)
+            .ok_or_else(|| format!("No application with id '{}'", id))?;
+
+        let mut args = exec_args(entry)?;
+        if entry.terminal {
+            let terminal = env::var("TERMINAL").unwrap_or_else(|_| "x-terminal-emulator".into());
+            args.splice(0..0, [terminal, "-e".to_string()]);
+        }
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+        if let Some(dir) = entry.working_dir.as_ref().filter(|dir| dir.is_dir()) {
+            command.current_dir(dir);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start '{}': {}", args[0], e))?;
+        std::thread::spawn(move || child.wait());
+        self.log_info(&format!("Launched {}", entry.info.name));
+        Ok(())
+    }
+
+    /// Whether an app with this id was found by the last refresh
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries.iter().any(|entry| entry.info.id == id)
+    }
+
+    fn mtimes(&self) -> Vec<Option<SystemTime>> {
+        self.data_dirs
+            .iter()
+            .map(|dir| {
+                fs::metadata(dir.join("applications"))
+                    .and_then(|m| m.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+impl Default for AppLauncher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the spec's defaults
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn xdg_data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    data_home
+        .into_iter()
+        .chain(
+            data_dirs
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        )
+        .collect()
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn xdg_data_dirs() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Locale keys to look for in the localized values, most specific first.
+/// `pt_BR.UTF-8` gives `pt_BR` and `pt`.
+fn languages() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+    let mut languages = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once('_') {
+        languages.push(language.to_string());
+    }
+    languages
+}
+
+/// Desktop names from `$XDG_CURRENT_DESKTOP`, e.g. `GNOME`
+fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn collect_desktop_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_desktop_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "desktop") {
+            files.push(path);
+        }
+    }
+}
+
+/// Path relative to the applications directory with `/` turned into `-`,
+/// e.g. `kde/konsole.desktop` becomes `kde-konsole.desktop`
+fn desktop_file_id(applications: &Path, file: &Path) -> String {
+    file.strip_prefix(applications)
+        .unwrap_or(file)
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Reads an application entry. `None` for entries that must not be shown.
+fn parse_entry(
+    id: &str,
+    file: &Path,
+    content: &str,
+    languages: &[String],
+    desktops: &[String],
+) -> Option<DesktopEntry> {
+    let mut values: HashMap<&str, &str> = HashMap::new();
+    let mut in_entry_group = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_entry_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=').filter(|_| in_entry_group) {
+            values.entry(key.trim()).or_insert(value.trim());
+        }
+    }
+
+    let get = |key: &str| values.get(key).map(|value| unescape(value));
+    let localized = |key: &str| {
+        languages
+            .iter()
+            .find_map(|language| values.get(format!("{}[{}]", key, language).as_str()))
+            .or_else(|| values.get(key))
+            .map(|value| unescape(value))
+    };
+    let flag = |key: &str| get(key).is_some_and(|value| value == "true");
+    let list = |value: Option<String>| -> Vec<String> {
+        value
+            .unwrap_or_default()
+            .split(';')
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    if get("Type").as_deref() != Some("Application") || flag("NoDisplay") || flag("Hidden") {
+        return None;
+    }
+    let only_show_in = list(get("OnlyShowIn"));
+    if !only_show_in.is_empty() && !only_show_in.iter().any(|d| desktops.contains(d)) {
+        return None;
+    }
+    if list(get("NotShowIn")).iter().any(|d| desktops.contains(d)) {
+        return None;
+    }
+    // apps whose program is gone leave their entry behind
+    if let Some(try_exec) = get("TryExec") {
+        find_executable(&try_exec)?;
+    }
+
+    Some(DesktopEntry {
+        info: AppInfo {
+            id: id.to_string(),
+            name: localized("Name")?,
+            generic_name: localized("GenericName"),
+            comment: localized("Comment"),
+            icon: None,
+        },
+        keywords: list(localized("Keywords")),
+        exec: get("Exec")?,
+        icon_name: get("Icon").filter(|icon| !icon.is_empty()),
+        working_dir: get("Path").filter(|dir| !dir.is_empty()).map(PathBuf::from),
+        terminal: flag("Terminal"),
+        file: file.to_path_buf(),
+    })
+}
+
+/// Undoes the escapes allowed in desktop entry values
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                // left for the Exec quoting rules, e.g. `\"` inside quotes
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits the `Exec` line into arguments and expands its field codes.
+/// No files or URLs are passed, so `%f`, `%F`, `%u` and `%U` are dropped.
+fn exec_args(entry: &DesktopEntry) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for arg in split_exec(&entry.exec)? {
+        match arg.as_str() {
+            "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+            "%i" => {
+                if let Some(icon) = &entry.icon_name {
+                    args.push("--icon".to_string());
+                    args.push(icon.clone());
+                }
+            }
+            _ => args.push(expand_field_codes(&arg, entry)),
+        }
+    }
+    if args.is_empty() {
+        return Err(format!("{} has an empty Exec line", entry.file.display()));
+    }
+    Ok(args)
+}
+
+/// Splits on unquoted spaces. Inside double quotes, `\` escapes `"`, `` ` ``, `$` and `\`.
+fn split_exec(exec: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => arg.push(escaped),
+                            Some(other) => {
+                                arg.push('\\');
+                                arg.push(other);
+                            }
+                            None => return Err("Unterminated quote in Exec".to_string()),
+                        },
+                        Some(other) => arg.push(other),
+                        None => return Err("Unterminated quote in Exec".to_string()),
+                    }
+                }
+            }
+            other => current.get_or_insert_with(String::new).push(other),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Expands the field codes found inside an argument
+fn expand_field_codes(arg: &str, entry: &DesktopEntry) -> String {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('c') => expanded.push_str(&entry.info.name),
+            Some('k') => expanded.push_str(&entry.file.to_string_lossy()),
+            Some('i') => expanded.push_str(entry.icon_name.as_deref().unwrap_or_default()),
+            // file, URL and deprecated codes expand to nothing
+            Some(_) | None => {}
+        }
+    }
+    expanded
+}
+
+/// Full path of a program, looked up in `$PATH` unless it is a path already
+fn find_executable(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program)).filter(|path| path.is_file());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Lower ranks are better matches. `None` when the entry doesn't match at all.
+fn match_rank(entry: &DesktopEntry, query: &str) -> Option<u8> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let name = entry.info.name.to_lowercase();
+    if name.starts_with(query) {
+        return Some(0);
+    }
+    if name.split_whitespace().any(|word| word.starts_with(query)) {
+        return Some(1);
+    }
+    if name.contains(query) {
+        return Some(2);
+    }
+    let other_fields = entry
+        .info
+        .generic_name
+        .iter()
+        .chain(&entry.keywords)
+        .chain(&entry.info.comment);
+    for field in other_fields {
+        if field.to_lowercase().contains(query) {
+            return Some(3);
+        }
+    }
+    None
+}
+
+/// Base64 PNG of the icon, from a path or from the hicolor theme and pixmaps.
+/// Other formats, such as SVG, are left to the phone's generic icon.
+fn load_icon(data_dirs: &[PathBuf], icon: &str) -> Option<String> {
+    let path = if icon.starts_with('/') {
+        Some(PathBuf::from(icon))
+    } else {
+        data_dirs.iter().find_map(|dir| {
+            ICON_SIZES
+                .iter()
+                .map(|size| dir.join(format!("icons/hicolor/{}/apps/{}.png", size, icon)))
+                .chain([dir.join(format!("pixmaps/{}.png", icon))])
+                .find(|path| path.is_file())
+        })
+    }?;
+    if path.extension().is_none_or(|ext| ext != "png") {
+        return None;
+    }
+    let size = fs::metadata(&path).ok()?.len();
+    if size > MAX_ICON_BYTES {
+        return None;
+    }
+    encode_icon(&fs::read(&path).ok()?)
+}
+
+#[cfg(target_os = "linux")]
+fn encode_icon(png: &[u8]) -> Option<String> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    Some(STANDARD.encode(png))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn encode_icon(_png: &[u8]) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn entry(exec: &str) -> DesktopEntry {
+        let content = format!(
+            "[Desktop Entry]\nType=Application\nName=Viewer\nIcon=viewer\nExec={}\n",
+            exec
+        );
+        parse_entry(
+            "viewer.desktop",
+            Path::new("/apps/viewer.desktop"),
+            &content,
+            &[],
+            &[],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn field_codes() {
+        assert_eq!(
+            exec_args(&entry("viewer %U --name=%c %i --from %k 100%%")).unwrap(),
+            vec![
+                "viewer",
+                "--name=Viewer",
+                "--icon",
+                "viewer",
+                "--from",
+                "/apps/viewer.desktop",
+                "100%"
+            ]
+        );
+    }
+
+    #[test]
+    fn exec_quoting() {
+        assert_eq!(
+            exec_args(&entry(r#""/opt/My App/run" --title "say \\"hi\\"" %f"#)).unwrap(),
+            vec!["/opt/My App/run", "--title", "say \"hi\""]
+        );
+        assert!(exec_args(&entry(r#"viewer "unterminated"#)).is_err());
+        assert!(exec_args(&entry("%F")).is_err());
+    }
+
+    #[test]
+    fn entries_that_are_not_shown() {
+        let parse = |content: &str| {
+            parse_entry(
+                "a.desktop",
+                Path::new("a.desktop"),
+                content,
+                &[],
+                &["GNOME".to_string()],
+            )
+        };
+        let app = "[Desktop Entry]\nType=Application\nName=A\nExec=a\n";
+        assert!(parse(app).is_some());
+        assert!(parse(&format!("{}NoDisplay=true\n", app)).is_none());
+        assert!(parse(&format!("{}OnlyShowIn=KDE;\n", app)).is_none());
+        assert!(parse(&format!("{}NotShowIn=GNOME;\n", app)).is_none());
+        assert!(parse(&format!("{}TryExec=/nonexistent/a\n", app)).is_none());
+        assert!(parse("[Desktop Entry]\nType=Link\nName=A\nURL=x\n").is_none());
+        // keys of the other groups are ignored
+        assert!(
+            parse("[Desktop Entry]\nType=Application\nName=A\n[Desktop Action x]\nExec=b\n")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn localized_names() {
+        let content = "[Desktop Entry]\nType=Application\nExec=files\n\
+                       Name=Files\nName[pt]=Ficheiros\nName[pt_BR]=Arquivos\n";
+        let parse = |languages: &[&str]| {
+            let languages: Vec<String> = languages.iter().map(|l| l.to_string()).collect();
+            parse_entry("f.desktop", Path::new("f"), content, &languages, &[])
+                .unwrap()
+                .info
+                .name
+        };
+        assert_eq!(parse(&["pt_BR", "pt"]), "Arquivos");
+        assert_eq!(parse(&["pt_PT", "pt"]), "Ficheiros");
+        assert_eq!(parse(&[]), "Files");
+    }
+
+    #[test]
+    fn index_and_search() {
+        let home = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        let app = |name: &str, extra: &str| {
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=true\n{}",
+                name, extra
+            )
+        };
+        write(
+            system.path(),
+            "applications/firefox.desktop",
+            &app("Firefox", "GenericName=Web Browser\n"),
+        );
+        write(
+            system.path(),
+            "applications/kde/konsole.desktop",
+            &app("Konsole", "Keywords=terminal;shell;\n"),
+        );
+        write(
+            system.path(),
+            "applications/hidden.desktop",
+            &app("Hidden", ""),
+        );
+        write(
+            system.path(),
+            "applications/gnome-terminal.desktop",
+            &app("GNOME Terminal", ""),
+        );
+        // the user's copy hides the system one
+        write(
+            home.path(),
+            "applications/hidden.desktop",
+            &app("Hidden", "Hidden=true\n"),
+        );
+
+        let mut launcher = AppLauncher::with_data_dirs(vec![
+            home.path().to_path_buf(),
+            system.path().to_path_buf(),
+        ]);
+        launcher.refresh();
+
+        let ids = |apps: Vec<AppInfo>| apps.into_iter().map(|app| app.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(launcher.search("")),
+            vec![
+                "firefox.desktop",
+                "gnome-terminal.desktop",
+                "kde-konsole.desktop"
+            ]
+        );
+        assert_eq!(
+            ids(launcher.search("TERM")),
+            vec!["gnome-terminal.desktop", "kde-konsole.desktop"]
+        );
+        assert_eq!(ids(launcher.search("browser")), vec!["firefox.desktop"]);
+        assert!(launcher.contains("firefox.desktop"));
+        assert!(launcher.launch("hidden.desktop").is_err());
+    }
+
+    #[test]
+    fn rescans_when_apps_change() {
+        let data = tempfile::tempdir().unwrap();
+        let app = |name: &str| {
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=true\n",
+                name
+            )
+        };
+        write(data.path(), "applications/a.desktop", &app("A"));
+
+        let mut launcher = AppLauncher::with_data_dirs(vec![data.path().to_path_buf()]);
+        launcher.refresh_if_stale();
+        assert!(launcher.contains("a.desktop"));
+
+        write(data.path(), "applications/b.desktop", &app("B"));
+        launcher.refresh_if_stale();
+        assert!(launcher.contains("b.desktop"));
+
+        for i in 0..MAX_RESULTS {
+            write(
+                data.path(),
+                &format!("applications/c{}.desktop", i),
+                &app("C"),
+            );
+        }
+        launcher.refresh_if_stale();
+        assert_eq!(launcher.search("").len(), MAX_RESULTS);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn icons_sent_once_per_client() {
+        let data = tempfile::tempdir().unwrap();
+        write(
+            data.path(),
+            "applications/a.desktop",
+            "[Desktop Entry]\nType=Application\nName=A\nExec=true\nIcon=a\n",
+        );
+        write(data.path(), "icons/hicolor/48x48/apps/a.png", "png");
+
+        let mut launcher = AppLauncher::with_data_dirs(vec![data.path().to_path_buf()]);
+        launcher.refresh_if_stale();
+        let icon = |apps: Vec<AppInfo>| apps[0].icon.clone();
+        assert!(icon(launcher.search_for(1, "a")).is_some());
+        assert!(icon(launcher.search_for(1, "")).is_none());
+        assert!(icon(launcher.search_for(2, "a")).is_some());
+
+        launcher.client_disconnected(1);
+        assert!(icon(launcher.search_for(1, "a")).is_some());
+    }
+}
@@ -1,15 +1,48 @@
 // Declaration of the controller_server library
 mod actions;
+mod air_mouse;
+mod audio;
+mod brightness;
+mod client_messages;
+mod desktop_state;
+mod file_transfer;
+mod gestures;
+pub mod input;
 mod keybinds;
+mod launcher;
 pub mod logger;
+mod macros;
+mod media;
 mod mobile_controller;
+mod monitors;
+mod notifications;
+mod paths;
+mod power;
+mod presentation;
+mod screen_preview;
 mod server;
+mod shortcuts;
+mod url_opener;
+mod window_manager;
 
 // Re-exported types
 pub use server::{
     application::Application,
-    commands::{ServerRequest, ServerResponse},
-    core::{ClientInfo, Server, ServerConfig, ServerEvent, ServerHandler},
+    commands::{
+        ClientDetails, ClientList, ConfigUpdated, ServerRequest, ServerResponse, ServerStatus,
+    },
+    config::{Heartbeat, Permissions, ServerConfig},
+    core::{ClientInfo, Server, ServerEvent, ServerHandler, TransferredFile},
+    notifier::Notifier,
 };
 
+#[cfg(unix)]
+pub use paths::control_socket;
+#[cfg(unix)]
+pub use server::control::{ControlClient, ControlReply, ControlRequest, ControlSocket};
+
+pub use file_transfer::FileTransfers;
+pub use macros::{Macro, MacroLibrary, MacroStep, MacroSummary};
 pub use mobile_controller::MobileController;
+pub use presentation::LaserOverlay;
+pub use shortcuts::{Shortcut, ShortcutAction, ShortcutCatalog, ShortcutIcon};
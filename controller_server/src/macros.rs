@@ -0,0 +1,357 @@
+//! Named sequences of actions, run from the phone by id.
+//!
+//! Macros live in `macros.json` in the config directory. The desktop app edits them
+//! through a shared `MacroLibrary`, and can record a new one from the actions the
+//! phone sends while recording is on.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::{InputButton, InputKey},
+    paths,
+};
+
+pub const MACROS_FILE: &str = "macros.json";
+
+/// Longest single delay a macro may wait, so a typo can't freeze the input for hours
+pub const MAX_DELAY_MS: u64 = 10_000;
+
+/// Pauses shorter than this are not recorded
+const MIN_RECORDED_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Press the keys together, e.g. Ctrl+Shift+T
+    Keys {
+        keys: Vec<InputKey>,
+    },
+    Text {
+        text: String,
+    },
+    Delay {
+        ms: u64,
+    },
+    MouseMove {
+        dx: i32,
+        dy: i32,
+    },
+    Click {
+        button: InputButton,
+    },
+    Scroll {
+        amount: i32,
+    },
+    /// Shell command, run in the background
+    Command {
+        command: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub id: String,
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+}
+
+/// What the phone needs to show a macro button
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MacroSummary {
+    pub id: String,
+    pub name: String,
+}
+
+struct Recording {
+    steps: Vec<MacroStep>,
+    last_step: Instant,
+}
+
+#[derive(Default)]
+struct Library {
+    macros: Vec<Macro>,
+    /// Where the macros are saved, `None` to keep them in memory only
+    path: Option<PathBuf>,
+    recording: Option<Recording>,
+}
+
+/// The macros. The desktop app edits and records them in the library the controller
+/// runs them from, through a clone.
+#[derive(Clone, Default)]
+pub struct MacroLibrary {
+    inner: Arc<Mutex<Library>>,
+}
+
+impl MacroLibrary {
+    /// Library backed by `macros.json` in the config directory
+    pub fn load() -> Self {
+        paths::load_config(MACROS_FILE, Self::open).unwrap_or_else(Self::in_memory)
+    }
+
+    /// Library backed by the given file, which doesn't need to exist yet
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let macros = paths::load_json(path)?.unwrap_or_default();
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Library {
+                macros,
+                path: Some(path.to_path_buf()),
+                recording: None,
+            })),
+        })
+    }
+
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    pub fn macros(&self) -> Vec<Macro> {
+        self.inner.lock().unwrap().macros.clone()
+    }
+
+    pub fn summaries(&self) -> Vec<MacroSummary> {
+        self.inner
+            .lock()
+            .unwrap()
+            .macros
+            .iter()
+            .map(|m| MacroSummary {
+                id: m.id.clone(),
+                name: m.name.clone(),
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Macro> {
+        self.inner
+            .lock()
+            .unwrap()
+            .macros
+            .iter()
+            .find(|m| m.id == id)
+            .cloned()
+    }
+
+    /// Adds the macro, or replaces the one with the same id, and saves the library
+    pub fn save_macro(&self, new_macro: Macro) -> Result<(), String> {
+        if new_macro.id.trim().is_empty() {
+            return Err("Macro id can't be empty".to_string());
+        }
+        let mut library = self.inner.lock().unwrap();
+        match library.macros.iter_mut().find(|m| m.id == new_macro.id) {
+            Some(existing) => *existing = new_macro,
+            None => library.macros.push(new_macro),
+        }
+        library.save()
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut library = self.inner.lock().unwrap();
+        let before = library.macros.len();
+        library.macros.retain(|m| m.id != id);
+        if library.macros.len() == before {
+            return Err(format!("No macro with id '{}'", id));
+        }
+        library.save()
+    }
+
+    /// Starts capturing the actions performed through the controller,
+    /// dropping any recording in progress.
+    pub fn start_recording(&self) {
+        self.inner.lock().unwrap().recording = Some(Recording {
+            steps: Vec::new(),
+            last_step: Instant::now(),
+        });
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.inner.lock().unwrap().recording.is_some()
+    }
+
+    /// Appends a step to the recording, if there is one, preceded by the pause since
+    /// the previous step. Consecutive text and mouse moves are merged.
+    pub fn record(&self, step: MacroStep) {
+        let mut library = self.inner.lock().unwrap();
+        let Some(recording) = library.recording.as_mut() else {
+            return;
+        };
+
+        let pause = recording.last_step.elapsed();
+        recording.last_step = Instant::now();
+        if pause >= MIN_RECORDED_DELAY && !recording.steps.is_empty() {
+            recording.steps.push(MacroStep::Delay {
+                ms: (pause.as_millis() as u64).min(MAX_DELAY_MS),
+            });
+        }
+
+        match (recording.steps.last_mut(), step) {
+            (Some(MacroStep::Text { text }), MacroStep::Text { text: more }) => {
+                text.push_str(&more)
+            }
+            (Some(MacroStep::MouseMove { dx, dy }), MacroStep::MouseMove { dx: x, dy: y }) => {
+                *dx += x;
+                *dy += y;
+            }
+            (_, step) => recording.steps.push(step),
+        }
+    }
+
+    /// Stops recording and saves the captured steps as a macro
+    pub fn stop_recording(&self, id: &str, name: &str) -> Result<Macro, String> {
+        let recording = self
+            .inner
+            .lock()
+            .unwrap()
+            .recording
+            .take()
+            .ok_or_else(|| "Not recording".to_string())?;
+        if recording.steps.is_empty() {
+            return Err("Nothing was recorded".to_string());
+        }
+
+        let recorded = Macro {
+            id: id.to_string(),
+            name: name.to_string(),
+            steps: recording.steps,
+        };
+        self.save_macro(recorded.clone())?;
+        Ok(recorded)
+    }
+
+    pub fn cancel_recording(&self) {
+        self.inner.lock().unwrap().recording = None;
+    }
+}
+
+impl Library {
+    fn save(&self) -> Result<(), String> {
+        match &self.path {
+            Some(path) => paths::save_json(path, &self.macros),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy_macro() -> Macro {
+        Macro {
+            id: "copy".to_string(),
+            name: "Copy".to_string(),
+            steps: vec![MacroStep::Keys {
+                keys: vec![InputKey::Control, InputKey::Unicode('c')],
+            }],
+        }
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join(MACROS_FILE);
+
+        let library = MacroLibrary::open(&path).unwrap();
+        library.save_macro(copy_macro()).unwrap();
+
+        let reloaded = MacroLibrary::open(&path).unwrap();
+        assert_eq!(reloaded.get("copy"), Some(copy_macro()));
+        assert_eq!(
+            reloaded.summaries(),
+            vec![MacroSummary {
+                id: "copy".to_string(),
+                name: "Copy".to_string()
+            }]
+        );
+
+        reloaded.remove("copy").unwrap();
+        assert!(MacroLibrary::open(&path).unwrap().macros().is_empty());
+        assert!(reloaded.remove("copy").is_err());
+    }
+
+    #[test]
+    fn file_format() {
+        let json = r#"[{
+            "id": "greet",
+            "name": "Greet",
+            "steps": [
+                { "type": "text", "text": "hello" },
+                { "type": "delay", "ms": 200 },
+                { "type": "keys", "keys": ["return"] },
+                { "type": "click", "button": "left" }
+            ]
+        }]"#;
+        let macros: Vec<Macro> = serde_json::from_str(json).unwrap();
+        assert_eq!(macros[0].steps.len(), 4);
+        assert_eq!(
+            macros[0].steps[2],
+            MacroStep::Keys {
+                keys: vec![InputKey::Return]
+            }
+        );
+    }
+
+    #[test]
+    fn recording_merges_text_and_moves() {
+        let library = MacroLibrary::in_memory();
+        library.record(MacroStep::Text {
+            text: "ignored".to_string(),
+        });
+
+        library.start_recording();
+        for c in ["h", "i"] {
+            library.record(MacroStep::Text {
+                text: c.to_string(),
+            });
+        }
+        library.record(MacroStep::MouseMove { dx: 1, dy: 2 });
+        library.record(MacroStep::MouseMove { dx: 3, dy: -1 });
+
+        let recorded = library.stop_recording("hi", "Say hi").unwrap();
+        assert_eq!(
+            recorded.steps,
+            vec![
+                MacroStep::Text {
+                    text: "hi".to_string()
+                },
+                MacroStep::MouseMove { dx: 4, dy: 1 },
+            ]
+        );
+        assert_eq!(library.get("hi"), Some(recorded));
+        assert!(!library.is_recording());
+    }
+
+    #[test]
+    fn recording_keeps_pauses() {
+        let library = MacroLibrary::in_memory();
+        library.start_recording();
+        library.record(MacroStep::Click {
+            button: InputButton::Left,
+        });
+        std::thread::sleep(MIN_RECORDED_DELAY);
+        library.record(MacroStep::Click {
+            button: InputButton::Left,
+        });
+
+        let steps = library
+            .stop_recording("double", "Slow click")
+            .unwrap()
+            .steps;
+        assert_eq!(steps.len(), 3);
+        assert!(matches!(steps[1], MacroStep::Delay { ms } if ms >= 50));
+    }
+
+    #[test]
+    fn empty_recording_is_not_saved() {
+        let library = MacroLibrary::in_memory();
+        library.start_recording();
+        assert!(library.stop_recording("empty", "Empty").is_err());
+        assert!(library.stop_recording("empty", "Empty").is_err());
+        assert!(library.macros().is_empty());
+    }
+}
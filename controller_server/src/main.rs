@@ -1,69 +1,314 @@
-//! Demo/test binary for running the controller server standalone.
-//! The primary consumer is the Tauri desktop_app which uses the library.
+//! `laze-server`: runs the controller server without the desktop app, e.g. on a
+//! media PC. The primary consumer of the server is still the Tauri desktop_app,
+//! which uses the library.
+//!
+//! SIGINT and SIGTERM (Ctrl+C on Windows) terminate the server, so the phones are
+//! told it is gone. A second signal exits right away. On Unix, `lazectl` controls the
+//! running server through its control socket.
 #![allow(dead_code)]
 
-use std::thread::sleep;
-use std::time::Duration;
+use std::{
+    env, fs,
+    net::IpAddr,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+};
+
+use clap::Parser;
 
 use mobile_controller::MobileController;
-use server::core::{Server, ServerConfig};
+#[cfg(unix)]
+use server::control::ControlSocket;
+use server::{
+    commands::{ServerRequest, ServerResponse},
+    config::ServerConfig,
+    core::Server,
+};
 
 mod actions;
+mod air_mouse;
+mod audio;
+mod brightness;
+mod client_messages;
+mod desktop_state;
+mod file_transfer;
+mod gestures;
+mod input;
 mod keybinds;
+mod launcher;
 mod logger;
+mod macros;
+mod media;
 mod mobile_controller;
+mod monitors;
+mod notifications;
+mod paths;
+mod power;
+mod presentation;
+mod screen_preview;
 mod server;
+mod shortcuts;
+mod url_opener;
+mod window_manager;
 
-const PORT: usize = 7878;
+// Exit codes, from sysexits.h so service managers can tell failures apart
+/// Bad arguments
+const EXIT_USAGE: u8 = 64;
+/// No input backend, e.g. no X server and no access to uinput
+const EXIT_UNAVAILABLE: u8 = 69;
+/// The server did not start or stop as asked
+const EXIT_SOFTWARE: u8 = 70;
+/// The port can't be listened on, it may be free later
+const EXIT_TEMPFAIL: u8 = 75;
+/// The config directory can't be used
+const EXIT_CONFIG: u8 = 78;
+/// A second signal arrived while stopping
+const EXIT_INTERRUPTED: i32 = 130;
 
-fn main() {
-    let config = ServerConfig::new(PORT, 10);
-    let app = MobileController::new().unwrap();
+#[derive(Parser, Debug)]
+#[command(name = "laze-server", version, about = "Laze remote control server")]
+struct Args {
+    /// Port the phones connect to first. Each phone then gets the port `n` above it.
+    /// [default: 7878, or the one in server.json]
+    #[arg(short, long)]
+    port: Option<u16>,
 
-    // server has started here
-    let mut handle = Server::start(config, app);
+    /// Address to listen on [default: the local network address, or the one in server.json]
+    #[arg(short, long)]
+    bind: Option<IpAddr>,
 
-    // emulates main thread communication with server
-    handle.start_server().unwrap();
-    match handle.receive_response() {
-        Ok(server::commands::ServerResponse::ServerStarted(_)) => {
-            println!("Server initialized successfully.");
-        }
-        Ok(server::commands::ServerResponse::Error(err)) => {
-            println!("Error initializing server: {}", err);
-        }
-        _ => {
-            println!("Unexpected response during initialization.");
+    /// Most phones connected at once [default: 10, or the number in server.json]
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    max_clients: Option<u16>,
+
+    /// Don't answer the phones looking for servers on the network
+    #[arg(long)]
+    no_discovery: bool,
+
+    /// Directory of the configuration files [default: the user's config directory]
+    #[arg(short, long, value_name = "DIR")]
+    config_dir: Option<PathBuf>,
+
+    /// off, error, warn, info, debug or trace. RUST_LOG takes precedence.
+    #[arg(short, long, default_value_t = log::LevelFilter::Info)]
+    log_level: log::LevelFilter,
+
+    /// Where `lazectl` reaches the server [default: $XDG_RUNTIME_DIR/laze/control.sock]
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// Don't listen for `lazectl`
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "control_socket")]
+    no_control_socket: bool,
+}
+
+/// Why the server is stopping
+enum Stop {
+    /// SIGINT or SIGTERM, the server is still running
+    Signal,
+    /// `lazectl` has terminated the server already
+    Terminated,
+}
+
+/// Why the daemon stopped early, and the exit code telling it
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
         }
     }
-    sleep(Duration::from_secs(6));
+}
 
-    handle.terminate_client(2).unwrap();
-    match handle.receive_response() {
-        Ok(server::commands::ServerResponse::ClientTerminated(t)) => {
-            println!("Client {} terminated successfully.", t.client_id);
-        }
-        Ok(server::commands::ServerResponse::Error(err)) => {
-            println!("Error terminating client: {}", err);
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            // --help and --version aren't failures
+            return if e.use_stderr() {
+                ExitCode::from(EXIT_USAGE)
+            } else {
+                ExitCode::SUCCESS
+            };
         }
-        _ => {
-            println!("Unexpected response during client termination.");
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("laze-server: {}", failure.message);
+            ExitCode::from(failure.code)
         }
     }
+}
+
+fn run(args: Args) -> Result<(), Failure> {
+    // read by the logger once the server starts
+    if env::var_os("RUST_LOG").is_none() {
+        env::set_var("RUST_LOG", args.log_level.to_string());
+    }
+    if let Some(dir) = &args.config_dir {
+        fs::create_dir_all(dir).map_err(|e| {
+            Failure::new(EXIT_CONFIG, format!("Can't use {}: {}", dir.display(), e))
+        })?;
+        env::set_var(paths::CONFIG_DIR_ENV_VAR, dir);
+    }
+
+    let config =
+        server_config(&args, ServerConfig::load()).map_err(|e| Failure::new(EXIT_USAGE, e))?;
 
-    sleep(Duration::from_secs(6));
+    let app = MobileController::new().map_err(|e| Failure::new(EXIT_UNAVAILABLE, e.to_string()))?;
 
-    handle.terminate_server().unwrap();
-    match handle.receive_response() {
-        Ok(server::commands::ServerResponse::ServerTerminated(_)) => {
-            println!("Server terminated successfully.");
+    let (stop_sender, stop_requested) = mpsc::channel();
+    let stopping = AtomicBool::new(false);
+    ctrlc::set_handler({
+        let stop_sender = stop_sender.clone();
+        move || {
+            if stopping.swap(true, Ordering::SeqCst) {
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+            let _ = stop_sender.send(Stop::Signal);
         }
-        Ok(server::commands::ServerResponse::Error(err)) => {
-            println!("Error terminating server: {}", err);
+    })
+    .map_err(|e| Failure::new(EXIT_SOFTWARE, format!("Can't handle signals: {}", e)))?;
+
+    let port = config.starting_port;
+    let handle = Arc::new(Server::start(config, app));
+
+    match handle.request(ServerRequest::InitServer) {
+        Ok(ServerResponse::ServerStarted(_)) => {
+            log::info!("Listening for phones on port {}", port)
+        }
+        // the port is taken or the network is down, trying later may work
+        Ok(ServerResponse::Error(e)) => return Err(Failure::new(EXIT_TEMPFAIL, e)),
+        Err(e) => {
+            return Err(Failure::new(
+                EXIT_SOFTWARE,
+                format!("Failed to start: {}", e),
+            ))
         }
-        _ => {
-            println!("Unexpected response during server termination.");
+        Ok(other) => {
+            return Err(Failure::new(
+                EXIT_SOFTWARE,
+                format!("Unexpected reply to start: {:?}", other),
+            ))
         }
     }
-    sleep(Duration::from_secs(2));
+
+    #[cfg(unix)]
+    let _control_socket = if args.no_control_socket {
+        None
+    } else {
+        let path = args
+            .control_socket
+            .clone()
+            .unwrap_or_else(paths::control_socket);
+        let socket = ControlSocket::bind(&path, Arc::clone(&handle), move || {
+            let _ = stop_sender.send(Stop::Terminated);
+        })
+        .map_err(|e| Failure::new(EXIT_TEMPFAIL, format!("Can't listen for lazectl: {}", e)))?;
+        log::info!("Listening for lazectl on {}", path.display());
+        Some(socket)
+    };
+
+    if let Ok(Stop::Terminated) = stop_requested.recv() {
+        log::info!("Terminated through the control socket");
+        return Ok(());
+    }
+    log::info!("Stopping");
+
+    match handle.request(ServerRequest::TerminateServer) {
+        Ok(ServerResponse::ServerTerminated(_)) => Ok(()),
+        other => Err(Failure::new(
+            EXIT_SOFTWARE,
+            format!("Unexpected reply to terminate: {:?}", other),
+        )),
+    }
+}
+
+/// The saved config, e.g. `server.json`, with the arguments given taking precedence
+fn server_config(args: &Args, mut config: ServerConfig) -> Result<ServerConfig, String> {
+    if let Some(port) = args.port {
+        config.starting_port = port as usize;
+    }
+    if let Some(address) = args.bind {
+        config.bind_address = Some(address);
+    }
+    if let Some(max_clients) = args.max_clients {
+        config.max_clients = max_clients as usize;
+    }
+    if args.no_discovery {
+        config.discovery = false;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let args = Args::try_parse_from(["laze-server"]).unwrap();
+        assert_eq!((args.port, args.max_clients), (None, None));
+        assert_eq!(args.bind, None);
+        assert!(!args.no_discovery);
+        assert_eq!(args.log_level, log::LevelFilter::Info);
+
+        let args = Args::try_parse_from([
+            "laze-server",
+            "--port",
+            "9000",
+            "--bind",
+            "0.0.0.0",
+            "-m",
+            "2",
+            "--no-discovery",
+            "--log-level",
+            "debug",
+        ])
+        .unwrap();
+        assert_eq!((args.port, args.max_clients), (Some(9000), Some(2)));
+        assert_eq!(args.bind, Some(IpAddr::from([0, 0, 0, 0])));
+        assert!(args.no_discovery);
+        assert_eq!(args.log_level, log::LevelFilter::Debug);
+
+        let mut saved = ServerConfig::new(7000, 5);
+        saved.permissions.power = false;
+        let config = server_config(&args, saved.clone()).unwrap();
+        assert_eq!((config.starting_port, config.max_clients), (9000, 2));
+        assert_eq!(config.bind_address, args.bind);
+        assert!(!config.discovery);
+        assert!(!config.permissions.power);
+
+        let args = Args::try_parse_from(["laze-server", "-m", "3"]).unwrap();
+        let config = server_config(&args, saved).unwrap();
+        assert_eq!((config.starting_port, config.max_clients), (7000, 3));
+        let args = Args::try_parse_from(["laze-server", "-p", "65530"]).unwrap();
+        assert!(server_config(&args, ServerConfig::default()).is_err());
+
+        assert!(Args::try_parse_from(["laze-server", "-m", "0"]).is_err());
+        assert!(Args::try_parse_from(["laze-server", "--bind", "localhost"]).is_err());
+        #[cfg(unix)]
+        assert!(Args::try_parse_from([
+            "laze-server",
+            "--control-socket",
+            "/tmp/laze.sock",
+            "--no-control-socket"
+        ])
+        .is_err());
+    }
 }
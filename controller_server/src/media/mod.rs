@@ -0,0 +1,186 @@
+//! Media player integration.
+//!
+//! On Linux, players are reached through MPRIS over the D-Bus session bus, which
+//! lets the mobile client target a specific player instead of whichever application
+//! grabs the media keys.
+
+#[cfg(target_os = "linux")]
+pub mod mpris;
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::actions::MediaCommand;
+
+/// Track currently loaded in a media player
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct NowPlaying {
+    /// Bus name suffix identifying the player, e.g. `spotify` or `vlc`
+    pub player: String,
+    /// `Playing`, `Paused` or `Stopped`
+    pub status: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+/// Everything the mobile client shows about a player in the player list
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlayerInfo {
+    /// Bus name suffix, used by the client to select the player
+    pub id: String,
+    /// Human readable name, e.g. `VLC media player`
+    pub identity: String,
+    pub track: NowPlaying,
+    pub shuffle: Option<bool>,
+    pub position_ms: Option<i64>,
+    pub length_ms: Option<i64>,
+}
+
+/// Returns the track of the most relevant player: the first one playing,
+/// otherwise the first one found.
+#[cfg(target_os = "linux")]
+pub fn now_playing() -> Option<NowPlaying> {
+    mpris::MprisClient::session().ok()?.now_playing()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn now_playing() -> Option<NowPlaying> {
+    None
+}
+
+/// Executes `MediaCommand`s against the player each mobile client targeted.
+#[derive(Default)]
+pub struct MediaController {
+    /// Id of the player selected by each client.
+    /// Without one, or when that player has gone away, the most relevant player is used.
+    targets: HashMap<usize, String>,
+}
+
+impl MediaController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn selected_player(&self, client_id: usize) -> Option<&str> {
+        self.targets.get(&client_id).map(String::as_str)
+    }
+
+    /// Forgets the player the client selected
+    pub fn client_disconnected(&mut self, client_id: usize) {
+        self.targets.remove(&client_id);
+    }
+
+    /// Executes a playback command of the client.
+    /// `ListPlayers` has no effect here - see `MediaController::players`.
+    #[cfg(target_os = "linux")]
+    pub fn handle(&mut self, client_id: usize, command: &MediaCommand) -> Result<(), String> {
+        match command {
+            MediaCommand::SelectPlayer(id) => {
+                self.targets.insert(client_id, id.clone());
+                return Ok(());
+            }
+            MediaCommand::Unknown => return Ok(()),
+            _ => {}
+        }
+
+        let client = mpris::MprisClient::session()?;
+        let player = self.resolve_target(&client, self.selected_player(client_id))?;
+
+        match command {
+            MediaCommand::PlayPause => client.play_pause(&player),
+            MediaCommand::Play => client.play(&player),
+            MediaCommand::Pause => client.pause(&player),
+            MediaCommand::Stop => client.stop(&player),
+            MediaCommand::Next => client.next(&player),
+            MediaCommand::Previous => client.previous(&player),
+            MediaCommand::Seek(offset_ms) => client.seek(&player, *offset_ms as i64),
+            MediaCommand::SetPosition(position_ms) => {
+                client.set_position(&player, *position_ms as i64)
+            }
+            MediaCommand::SetShuffle(shuffle) => client.set_shuffle(&player, *shuffle),
+            MediaCommand::ListPlayers | MediaCommand::SelectPlayer(_) | MediaCommand::Unknown => {
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn handle(&mut self, client_id: usize, command: &MediaCommand) -> Result<(), String> {
+        match command {
+            MediaCommand::SelectPlayer(id) => {
+                self.targets.insert(client_id, id.clone());
+                return Ok(());
+            }
+            MediaCommand::Unknown => return Ok(()),
+            _ => {}
+        }
+        Err("Media player control is not supported on this OS".to_string())
+    }
+
+    /// Lists every running player.
+    #[cfg(target_os = "linux")]
+    pub fn players(&self) -> Result<Vec<PlayerInfo>, String> {
+        let client = mpris::MprisClient::session()?;
+        Ok(client
+            .players()?
+            .iter()
+            .filter_map(|name| client.info(name).ok())
+            .collect())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn players(&self) -> Result<Vec<PlayerInfo>, String> {
+        Ok(Vec::new())
+    }
+
+    /// Bus name of the player commands should go to.
+    #[cfg(target_os = "linux")]
+    fn resolve_target(
+        &self,
+        client: &mpris::MprisClient,
+        target: Option<&str>,
+    ) -> Result<String, String> {
+        let players = client.players()?;
+
+        if let Some(target) = target {
+            if let Some(player) = players.iter().find(|p| mpris::player_id(p) == target) {
+                return Ok(player.clone());
+            }
+        }
+
+        players
+            .iter()
+            .find(|player| {
+                client
+                    .track(player)
+                    .is_ok_and(|track| track.status == "Playing")
+            })
+            .or_else(|| players.first())
+            .cloned()
+            .ok_or_else(|| "No media player is running".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_client_selects_its_player() {
+        let mut media = MediaController::new();
+        media
+            .handle(1, &MediaCommand::SelectPlayer("vlc".to_string()))
+            .unwrap();
+        media
+            .handle(2, &MediaCommand::SelectPlayer("spotify".to_string()))
+            .unwrap();
+        assert_eq!(media.selected_player(1), Some("vlc"));
+        assert_eq!(media.selected_player(2), Some("spotify"));
+
+        media.client_disconnected(1);
+        assert_eq!(media.selected_player(1), None);
+        assert_eq!(media.selected_player(2), Some("spotify"));
+    }
+}
@@ -0,0 +1,490 @@
+//! MPRIS (Media Player Remote Interfacing Specification) client.
+//!
+//! Talks to every player exposing `org.mpris.MediaPlayer2.*` on the session bus.
+//! See <https://specifications.freedesktop.org/mpris-spec/latest/>.
+
+use std::collections::HashMap;
+
+use zbus::{
+    blocking::Connection,
+    proxy::CacheProperties,
+    zvariant::{ObjectPath, OwnedValue},
+};
+
+use super::{NowPlaying, PlayerInfo};
+
+/// Every MPRIS player owns a bus name with this prefix
+pub const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_async = false
+)]
+trait MediaPlayer2 {
+    #[zbus(property)]
+    fn identity(&self) -> zbus::Result<String>;
+}
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_async = false
+)]
+trait Player {
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn play(&self) -> zbus::Result<()>;
+    fn pause(&self) -> zbus::Result<()>;
+    fn stop(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+
+    /// Seeks forward (or backwards, if negative) by `offset` microseconds
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+    /// Moves to an absolute `position` in microseconds within the track `track_id`
+    fn set_position(&self, track_id: &ObjectPath<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+    /// Position in microseconds
+    #[zbus(property)]
+    fn position(&self) -> zbus::Result<i64>;
+
+    #[zbus(property)]
+    fn shuffle(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()>;
+}
+
+pub struct MprisClient {
+    connection: Connection,
+}
+
+impl MprisClient {
+    /// Connects to the user's session bus.
+    pub fn session() -> Result<Self, String> {
+        Connection::session()
+            .map(Self::with_connection)
+            .map_err(|e| format!("Failed to connect to the session bus: {}", e))
+    }
+
+    /// Uses an already established bus connection (e.g. a private bus in tests).
+    pub fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    /// Lists the bus names of every running MPRIS player.
+    pub fn players(&self) -> Result<Vec<String>, String> {
+        let dbus = zbus::blocking::fdo::DBusProxy::new(&self.connection)
+            .map_err(|e| format!("Failed to reach the D-Bus daemon: {}", e))?;
+        let mut players: Vec<String> = dbus
+            .list_names()
+            .map_err(|e| format!("Failed to list bus names: {}", e))?
+            .into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| name.starts_with(MPRIS_PREFIX))
+            .collect();
+        players.sort();
+        Ok(players)
+    }
+
+    fn player(&self, bus_name: &str) -> Result<PlayerProxy<'_>, String> {
+        PlayerProxy::builder(&self.connection)
+            .destination(bus_name.to_owned())
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build())
+            .map_err(|e| format!("Failed to create proxy for {}: {}", bus_name, e))
+    }
+
+    /// Reads the track loaded in a given player.
+    pub fn track(&self, bus_name: &str) -> Result<NowPlaying, String> {
+        let player = self.player(bus_name)?;
+        let status = player
+            .playback_status()
+            .map_err(|e| dbus_error(bus_name, "read playback status", e))?;
+        let metadata = player.metadata().unwrap_or_default();
+
+        Ok(NowPlaying {
+            player: player_id(bus_name).to_string(),
+            status,
+            title: metadata_string(&metadata, "xesam:title"),
+            artist: metadata_string(&metadata, "xesam:artist"),
+            album: metadata_string(&metadata, "xesam:album"),
+        })
+    }
+
+    /// Reads everything the mobile client shows about a player.
+    pub fn info(&self, bus_name: &str) -> Result<PlayerInfo, String> {
+        let player = self.player(bus_name)?;
+        let identity = MediaPlayer2Proxy::builder(&self.connection)
+            .destination(bus_name.to_owned())
+            .and_then(|builder| builder.cache_properties(CacheProperties::No).build())
+            .and_then(|root| root.identity())
+            .unwrap_or_else(|_| player_id(bus_name).to_string());
+        let metadata = player.metadata().unwrap_or_default();
+
+        Ok(PlayerInfo {
+            id: player_id(bus_name).to_string(),
+            identity,
+            track: self.track(bus_name)?,
+            shuffle: player.shuffle().ok(),
+            position_ms: player.position().ok().map(|us| us / 1000),
+            length_ms: metadata_i64(&metadata, "mpris:length").map(|us| us / 1000),
+        })
+    }
+
+    /// Returns the track of the first playing player, or of the first player found.
+    pub fn now_playing(&self) -> Option<NowPlaying> {
+        let tracks: Vec<NowPlaying> = self
+            .players()
+            .ok()?
+            .iter()
+            .filter_map(|name| self.track(name).ok())
+            .collect();
+
+        let playing = tracks.iter().position(|track| track.status == "Playing");
+        tracks.into_iter().nth(playing.unwrap_or(0))
+    }
+
+    pub fn play_pause(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .play_pause()
+            .map_err(|e| dbus_error(bus_name, "toggle playback", e))
+    }
+
+    pub fn play(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .play()
+            .map_err(|e| dbus_error(bus_name, "play", e))
+    }
+
+    pub fn pause(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .pause()
+            .map_err(|e| dbus_error(bus_name, "pause", e))
+    }
+
+    pub fn stop(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .stop()
+            .map_err(|e| dbus_error(bus_name, "stop", e))
+    }
+
+    pub fn next(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .next()
+            .map_err(|e| dbus_error(bus_name, "skip to next track", e))
+    }
+
+    pub fn previous(&self, bus_name: &str) -> Result<(), String> {
+        self.player(bus_name)?
+            .previous()
+            .map_err(|e| dbus_error(bus_name, "skip to previous track", e))
+    }
+
+    /// Seeks relative to the current position.
+    pub fn seek(&self, bus_name: &str, offset_ms: i64) -> Result<(), String> {
+        self.player(bus_name)?
+            .seek(offset_ms * 1000)
+            .map_err(|e| dbus_error(bus_name, "seek", e))
+    }
+
+    /// Moves to an absolute position within the current track.
+    pub fn set_position(&self, bus_name: &str, position_ms: i64) -> Result<(), String> {
+        let player = self.player(bus_name)?;
+        let metadata = player
+            .metadata()
+            .map_err(|e| dbus_error(bus_name, "read metadata", e))?;
+
+        // SetPosition is ignored by the player unless the track id matches the current track
+        let track_id = metadata
+            .get("mpris:trackid")
+            .and_then(|value| ObjectPath::try_from(value.try_clone().ok()?).ok())
+            .ok_or_else(|| format!("{} did not report a track id", bus_name))?;
+
+        player
+            .set_position(&track_id, position_ms * 1000)
+            .map_err(|e| dbus_error(bus_name, "set position", e))
+    }
+
+    pub fn set_shuffle(&self, bus_name: &str, shuffle: bool) -> Result<(), String> {
+        self.player(bus_name)?
+            .set_shuffle(shuffle)
+            .map_err(|e| dbus_error(bus_name, "set shuffle", e))
+    }
+}
+
+/// `org.mpris.MediaPlayer2.vlc` -> `vlc`
+pub fn player_id(bus_name: &str) -> &str {
+    bus_name.trim_start_matches(MPRIS_PREFIX)
+}
+
+fn dbus_error(bus_name: &str, operation: &str, error: zbus::Error) -> String {
+    format!("Failed to {} on {}: {}", operation, bus_name, error)
+}
+
+/// Reads a metadata entry that is either a string, or a list of strings
+/// (`xesam:artist` is a list). Lists are joined with ", ".
+fn metadata_string(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    let value = metadata.get(key)?.try_clone().ok()?;
+    if let Ok(text) = String::try_from(value.try_clone().ok()?) {
+        return Some(text);
+    }
+    Vec::<String>::try_from(value)
+        .ok()
+        .filter(|list| !list.is_empty())
+        .map(|list| list.join(", "))
+}
+
+/// Reads an integer metadata entry. `mpris:length` is specified as `x` (i64),
+/// but some players send it as `t` (u64).
+fn metadata_i64(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<i64> {
+    let value = metadata.get(key)?;
+    i64::try_from(value)
+        .ok()
+        .or_else(|| u64::try_from(value).ok().map(|v| v as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::{Arc, Mutex},
+    };
+    use zbus::zvariant::Value;
+
+    /// Object path every MPRIS player is served at
+    const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+
+    /// A dbus-daemon private to a single test
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Returns `None` when `dbus-daemon` is not installed
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            zbus::blocking::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        calls: Vec<String>,
+        shuffle: bool,
+    }
+
+    struct MockPlayer {
+        state: Arc<Mutex<MockState>>,
+    }
+
+    impl MockPlayer {
+        fn record(&self, call: String) {
+            self.state.lock().unwrap().calls.push(call);
+        }
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl MockPlayer {
+        fn play_pause(&self) {
+            self.record("PlayPause".to_string());
+        }
+        fn play(&self) {
+            self.record("Play".to_string());
+        }
+        fn pause(&self) {
+            self.record("Pause".to_string());
+        }
+        fn stop(&self) {
+            self.record("Stop".to_string());
+        }
+        fn next(&self) {
+            self.record("Next".to_string());
+        }
+        fn previous(&self) {
+            self.record("Previous".to_string());
+        }
+        fn seek(&self, offset: i64) {
+            self.record(format!("Seek {}", offset));
+        }
+        fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+            self.record(format!("SetPosition {} {}", track_id, position));
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            "Playing".to_string()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            HashMap::from([
+                (
+                    "mpris:trackid".to_string(),
+                    OwnedValue::try_from(Value::from(
+                        ObjectPath::try_from("/org/mock/track/1").unwrap(),
+                    ))
+                    .unwrap(),
+                ),
+                (
+                    "mpris:length".to_string(),
+                    OwnedValue::from(180_000_000i64),
+                ),
+                (
+                    "xesam:title".to_string(),
+                    OwnedValue::try_from(Value::from("Mock Song")).unwrap(),
+                ),
+                (
+                    "xesam:artist".to_string(),
+                    OwnedValue::try_from(Value::from(vec!["Mock Artist"])).unwrap(),
+                ),
+            ])
+        }
+
+        #[zbus(property)]
+        fn position(&self) -> i64 {
+            42_000_000
+        }
+
+        #[zbus(property)]
+        fn shuffle(&self) -> bool {
+            self.state.lock().unwrap().shuffle
+        }
+
+        #[zbus(property)]
+        fn set_shuffle(&mut self, shuffle: bool) {
+            self.state.lock().unwrap().shuffle = shuffle;
+        }
+    }
+
+    /// Serves a mock player as `org.mpris.MediaPlayer2.mock` on the private bus
+    fn serve_mock_player(bus: &PrivateBus) -> (Connection, Arc<Mutex<MockState>>) {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let connection = zbus::blocking::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name("org.mpris.MediaPlayer2.mock")
+            .unwrap()
+            .serve_at(
+                MPRIS_PATH,
+                MockPlayer {
+                    state: Arc::clone(&state),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        (connection, state)
+    }
+
+    #[test]
+    fn metadata_strings_and_lists() {
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "xesam:title".to_string(),
+            OwnedValue::try_from(Value::from("Song")).unwrap(),
+        );
+        metadata.insert(
+            "xesam:artist".to_string(),
+            OwnedValue::try_from(Value::from(vec!["A", "B"])).unwrap(),
+        );
+
+        assert_eq!(
+            metadata_string(&metadata, "xesam:title"),
+            Some("Song".to_string())
+        );
+        assert_eq!(
+            metadata_string(&metadata, "xesam:artist"),
+            Some("A, B".to_string())
+        );
+        assert_eq!(metadata_string(&metadata, "xesam:album"), None);
+    }
+
+    #[test]
+    fn lists_and_reads_mock_player() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_server, _state) = serve_mock_player(&bus);
+        let client = MprisClient::with_connection(bus.connect());
+
+        assert_eq!(
+            client.players().unwrap(),
+            vec!["org.mpris.MediaPlayer2.mock".to_string()]
+        );
+
+        let info = client.info("org.mpris.MediaPlayer2.mock").unwrap();
+        assert_eq!(info.id, "mock");
+        assert_eq!(info.track.status, "Playing");
+        assert_eq!(info.track.title.as_deref(), Some("Mock Song"));
+        assert_eq!(info.track.artist.as_deref(), Some("Mock Artist"));
+        assert_eq!(info.position_ms, Some(42_000));
+        assert_eq!(info.length_ms, Some(180_000));
+        assert_eq!(client.now_playing().unwrap().player, "mock");
+    }
+
+    #[test]
+    fn controls_mock_player() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_server, state) = serve_mock_player(&bus);
+        let client = MprisClient::with_connection(bus.connect());
+        let player = "org.mpris.MediaPlayer2.mock";
+
+        client.play_pause(player).unwrap();
+        client.next(player).unwrap();
+        client.previous(player).unwrap();
+        client.seek(player, -5_000).unwrap();
+        client.set_position(player, 60_000).unwrap();
+        client.set_shuffle(player, true).unwrap();
+
+        let state = state.lock().unwrap();
+        assert_eq!(
+            state.calls,
+            vec![
+                "PlayPause",
+                "Next",
+                "Previous",
+                "Seek -5000000",
+                "SetPosition /org/mock/track/1 60000000",
+            ]
+        );
+        assert!(state.shuffle);
+    }
+}
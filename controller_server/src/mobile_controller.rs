@@ -1,75 +1,721 @@
-use enigo::{Axis, Coordinate, Direction, Enigo, Keyboard, Mouse, Settings};
+use std::collections::HashMap;
 use std::error::Error;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use crate::{
-    actions::{Action, TerminalCommand},
+    actions::{
+        self, Action, AirMouseEvent, GamepadEvent, GestureEvent, MediaCommand, MonitorCommand,
+        PenEvent, PowerCommand, PresentationCommand, PreviewCommand, TerminalCommand,
+        WindowCommand,
+    },
+    air_mouse::AirMouse,
+    audio,
+    brightness::{Backlight, BRIGHTNESS_STEP},
+    client_messages::ClientMessage,
+    desktop_state::{DesktopState, DesktopStateMonitor},
+    file_transfer::FileTransfers,
+    gestures::{GestureAction, GestureBindings},
+    input::{
+        self, gamepad::VirtualGamepad, tablet::VirtualTablet, Axis, Direction, InputBackend,
+        InputButton, InputKey,
+    },
     keybinds::KeyBindings,
+    launcher::AppLauncher,
     logger::Loggable,
-    server::application::{Application, ConnectionStatus},
+    macros::{Macro, MacroLibrary, MacroStep, MAX_DELAY_MS},
+    media::MediaController,
+    monitors::{self, MonitorInfo, MonitorLayout},
+    notifications::{NotificationMirror, Subscribers},
+    power,
+    presentation::{self, LaserOverlay, Presentation, PresentationProfiles},
+    screen_preview::{PreviewStreams, ScreenPreview},
+    server::{
+        application::{Application, ConnectionStatus},
+        config::{NotificationFilter, Permissions, ServerConfig},
+        notifier::Notifier,
+    },
+    shortcuts::{ShortcutAction, ShortcutCatalog, ShortcutSubscription},
+    url_opener::UrlOpener,
+    window_manager::{self, WindowListMonitor, WindowManager},
 };
 
+/// Input backend shared with the thread running the macros
+type SharedInput = Arc<Mutex<Box<dyn InputBackend>>>;
+
 pub struct MobileController {
-    enigo: Enigo,
+    /// Where the emulated keyboard / mouse input goes
+    input: SharedInput,
     key_bindings: KeyBindings,
-}
 
-// now device can be shared across threads
-unsafe impl Send for MobileController {}
-unsafe impl Sync for MobileController {}
+    /// What the server config allows the clients to do
+    permissions: Permissions,
+
+    gesture_bindings: GestureBindings,
+    media: MediaController,
+
+    /// Files sent to and from the clients. Shared with the desktop app, which sends files.
+    files: FileTransfers,
+
+    /// Bytes of an action split across reads, by client, waiting for the rest
+    partial_input: HashMap<usize, Vec<u8>>,
+
+    /// Installed applications the clients can search and launch
+    apps: AppLauncher,
+
+    /// Opens the links shared from the phones, if their scheme is allowed
+    url_opener: UrlOpener,
+
+    /// Which desktop notifications are mirrored to the phones
+    notification_filter: NotificationFilter,
+
+    /// Clients that asked for the desktop notifications
+    notification_subscribers: Subscribers,
+
+    /// Watches the desktop notifications while a client is subscribed
+    notification_mirror: Option<NotificationMirror>,
+
+    /// Screen preview settings of the clients that asked for one
+    preview_streams: PreviewStreams,
+
+    /// Captures and sends the screen preview while a client wants it
+    screen_preview: Option<ScreenPreview>,
+
+    /// Macros the clients can run. Shared with the desktop app, which edits and records them.
+    macros: MacroLibrary,
+
+    /// Runs the macros off the client thread, started on the first macro
+    macro_runner: Option<MacroRunner>,
+
+    /// Shortcut buttons shown on every phone. Shared with the desktop app, which edits them.
+    shortcuts: ShortcutCatalog,
+
+    /// Pushes the shortcut catalog to the clients whenever it is edited
+    shortcut_subscription: Option<ShortcutSubscription>,
+
+    /// Slide counter, timer and laser pointer of the presentation mode
+    presentation: Presentation,
+
+    /// Draws the laser pointer. Without one the laser moves the cursor.
+    laser_overlay: Option<Box<dyn LaserOverlay>>,
+
+    /// Gyroscope filter of each client using the air mouse
+    air_mice: HashMap<usize, AirMouse>,
+
+    /// Virtual gamepad of each client, plugged in on its first gamepad action.
+    /// `None` when the gamepad could not be created, so it isn't retried on every event.
+    gamepads: HashMap<usize, Option<Box<dyn VirtualGamepad>>>,
+
+    /// Pen tablet shared by all clients, plugged in on the first pen event.
+    /// Holds `None` inside when the tablet could not be created.
+    tablet: Option<Option<Box<dyn VirtualTablet>>>,
+
+    /// Drives the desktop windows directly. Without one, window commands are sent as
+    /// key bindings.
+    window_manager: Option<Box<dyn WindowManager>>,
+
+    /// Pushes the window list to the clients when it changes
+    window_monitor: Option<WindowListMonitor>,
+
+    /// Reads the monitor layout and places the pointer. `None` outside X11.
+    monitor_layout: Option<Box<dyn MonitorLayout>>,
+
+    /// Monitor the pointer moved from the phones is kept on
+    confined_to: Option<MonitorInfo>,
+
+    /// Pushes data back to the mobile clients
+    notifier: Notifier,
+
+    /// Keeps the clients up to date with the desktop state. Started once the server
+    /// hands us a notifier, and stopped when the controller is dropped.
+    state_monitor: Option<DesktopStateMonitor>,
+}
 
 impl MobileController {
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        Ok(MobileController {
-            enigo: Enigo::new(&Settings::default())?,
+        Ok(Self::with_backend(input::default_backend()?))
+    }
+
+    /// Creates a controller emitting its input through the given backend
+    pub fn with_backend(input: Box<dyn InputBackend>) -> Self {
+        Self::static_log_info(&format!("Using the {} input backend", input.name()));
+        MobileController {
+            input: Arc::new(Mutex::new(input)),
             key_bindings: KeyBindings::new(),
-        })
+            permissions: Permissions::default(),
+            gesture_bindings: GestureBindings::load(),
+            media: MediaController::new(),
+            files: FileTransfers::load(),
+            partial_input: HashMap::new(),
+            apps: AppLauncher::new(),
+            url_opener: UrlOpener::load(),
+            notification_filter: NotificationFilter::default(),
+            notification_subscribers: Subscribers::default(),
+            notification_mirror: None,
+            preview_streams: PreviewStreams::default(),
+            screen_preview: None,
+            macros: MacroLibrary::load(),
+            macro_runner: None,
+            shortcuts: ShortcutCatalog::load(),
+            shortcut_subscription: None,
+            presentation: Presentation::new(PresentationProfiles::load()),
+            laser_overlay: None,
+            air_mice: HashMap::new(),
+            window_manager: match window_manager::connect() {
+                Ok(manager) => {
+                    Self::static_log_info(&format!("Managing windows through {}", manager.name()));
+                    Some(manager)
+                }
+                Err(e) => {
+                    Self::static_log_info(&format!("Using key bindings for windows: {}", e));
+                    None
+                }
+            },
+            window_monitor: None,
+            monitor_layout: match monitors::connect() {
+                Ok(layout) => Some(layout),
+                Err(e) => {
+                    Self::static_log_info(&format!("Not placing the pointer on monitors: {}", e));
+                    None
+                }
+            },
+            confined_to: None,
+            gamepads: HashMap::new(),
+            tablet: None,
+            notifier: Notifier::default(),
+            state_monitor: None,
+        }
     }
 
-    pub fn mouse_move_relative(&mut self, move_x: i8, move_y: i8) {
-        self.enigo
-            .move_mouse(move_x as i32, move_y as i32, Coordinate::Rel)
-            .unwrap();
+    /// Uses the given macro library instead of the one loaded from the config directory
+    pub fn with_macro_library(mut self, macros: MacroLibrary) -> Self {
+        self.macros = macros;
+        self
+    }
+
+    /// Handle to the macro library, e.g. for the desktop app to edit or record macros
+    pub fn macro_library(&self) -> MacroLibrary {
+        self.macros.clone()
+    }
+
+    /// Uses the given shortcut catalog instead of the one loaded from the config directory
+    pub fn with_shortcut_catalog(mut self, shortcuts: ShortcutCatalog) -> Self {
+        self.shortcuts = shortcuts;
+        self
+    }
+
+    /// Handle to the shortcut catalog, e.g. for the desktop app to edit it
+    pub fn shortcut_catalog(&self) -> ShortcutCatalog {
+        self.shortcuts.clone()
+    }
+
+    /// Uses the given file transfers instead of the ones configured in the config directory
+    pub fn with_file_transfers(mut self, files: FileTransfers) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Handle to the file transfers, e.g. for the desktop app to send a file to a phone
+    pub fn file_transfers(&self) -> FileTransfers {
+        self.files.clone()
+    }
+
+    /// Draws the presentation laser pointer with the given overlay
+    pub fn with_laser_overlay(mut self, overlay: Box<dyn LaserOverlay>) -> Self {
+        self.laser_overlay = Some(overlay);
+        self
+    }
+
+    pub fn mouse_move_relative(&mut self, move_x: i32, move_y: i32) {
+        let (move_x, move_y) = self.confine_move(move_x, move_y);
+        let result = self.input.lock().unwrap().move_relative(move_x, move_y);
+        self.log_input_error(result);
+    }
+
+    /// Shortens the move so the pointer stays on the monitor it is confined to,
+    /// bringing it back first if it got out some other way
+    fn confine_move(&mut self, move_x: i32, move_y: i32) -> (i32, i32) {
+        let (Some(monitor), Some(layout)) = (&self.confined_to, self.monitor_layout.as_mut())
+        else {
+            return (move_x, move_y);
+        };
+        let position = match layout.pointer() {
+            Ok(position) => position,
+            Err(e) => {
+                Self::static_log_warn(&format!("Failed to read the pointer position: {}", e));
+                return (move_x, move_y);
+            }
+        };
+        let inside = monitor.clamp(position);
+        if inside != position {
+            if let Err(e) = layout.warp_pointer(inside) {
+                Self::static_log_warn(&format!("Failed to move the pointer back: {}", e));
+            }
+        }
+        let target = monitor.clamp((inside.0 + move_x, inside.1 + move_y));
+        (target.0 - inside.0, target.1 - inside.1)
     }
 
     pub fn scroll(&mut self, wheel_delta: i8) {
-        self.enigo
-            .scroll(wheel_delta as i32, Axis::Vertical)
-            .unwrap();
+        let result = self
+            .input
+            .lock()
+            .unwrap()
+            .scroll(wheel_delta as i32, Axis::Vertical);
+        self.log_input_error(result);
     }
 
     #[allow(dead_code)]
-    pub fn press_key(&mut self, key: enigo::Key) {
-        self.enigo.key(key, Direction::Click).unwrap();
+    pub fn press_key(&mut self, key: InputKey) {
+        let result = self.input.lock().unwrap().key(key, Direction::Click);
+        self.log_input_error(result);
     }
 
-    pub fn press_key_combo(&mut self, keys: &[enigo::Key]) {
-        for key in keys {
-            self.enigo.key(*key, Direction::Press).unwrap();
+    pub fn press_key_combo(&mut self, keys: &[InputKey]) {
+        let result = self.input.lock().unwrap().key_combo(keys);
+        self.log_input_error(result);
+    }
+
+    fn mouse_button(&mut self, button: InputButton, direction: Direction) {
+        let result = self.input.lock().unwrap().button(button, direction);
+        self.log_input_error(result);
+    }
+
+    fn type_string(&mut self, text: &str) {
+        let result = self.input.lock().unwrap().text(text);
+        self.log_input_error(result);
+    }
+
+    fn log_input_error(&self, result: Result<(), String>) {
+        if let Err(e) = result {
+            self.log_warn(&format!(
+                "{} input failed: {}",
+                self.input.lock().unwrap().name(),
+                e
+            ));
         }
-        for key in keys.iter().rev() {
-            self.enigo.key(*key, Direction::Release).unwrap();
+    }
+
+    /// Routes media keys to the MPRIS player the client targeted.
+    /// Returns false if no player could handle it, so the key should be emulated instead.
+    fn try_media_key(&mut self, client_id: usize, key: &actions::Key) -> bool {
+        let command = match key {
+            actions::Key::Play => MediaCommand::PlayPause,
+            actions::Key::Pause => MediaCommand::Pause,
+            _ => return false,
+        };
+        match self.media.handle(client_id, &command) {
+            Ok(()) => true,
+            Err(e) => {
+                self.log_debug(&format!("Falling back to media keys: {}", e));
+                false
+            }
         }
     }
 
-    fn mouse_button(&mut self, button: enigo::Button) {
-        self.enigo.button(button, Direction::Click).unwrap();
+    /// Sets the backlight directly for the brightness keys.
+    /// Returns false if there is no usable backlight, so the key should be emulated instead.
+    fn try_brightness_key(&mut self, key: &actions::Key) -> bool {
+        let delta = match key {
+            actions::Key::BrightnessUp => BRIGHTNESS_STEP,
+            actions::Key::BrightnessDown => -BRIGHTNESS_STEP,
+            _ => return false,
+        };
+        let result = Backlight::detect()
+            .ok_or_else(|| "No backlight device found".to_string())
+            .and_then(|backlight| backlight.adjust(delta));
+        match result {
+            Ok(_) => {
+                DesktopState::publish(&self.notifier);
+                true
+            }
+            Err(e) => {
+                self.log_debug(&format!("Falling back to brightness keys: {}", e));
+                false
+            }
+        }
     }
 
-    fn mouse_button_down(&mut self, button: enigo::Button) {
-        self.enigo.button(button, Direction::Press).unwrap();
+    fn set_brightness(&mut self, percent: u8) {
+        let result = Backlight::detect()
+            .ok_or_else(|| "No backlight device found".to_string())
+            .and_then(|backlight| backlight.set_percent(percent));
+        if let Err(e) = result {
+            self.log_warn(&format!("Failed to set brightness: {}", e));
+        }
+        DesktopState::publish(&self.notifier);
     }
 
-    fn mouse_button_up(&mut self, button: enigo::Button) {
-        self.enigo.button(button, Direction::Release).unwrap();
+    fn handle_power(&mut self, client_id: usize, command: PowerCommand) {
+        let error = power::execute(&command).err();
+        if let Some(e) = &error {
+            self.log_warn(&format!("Power command {:?} failed: {}", command, e));
+        }
+        self.notifier
+            .send_to(client_id, &ClientMessage::PowerResult { command, error });
     }
 
-    fn type_string(&mut self, text: &str) {
-        self.enigo.text(text).unwrap();
+    fn handle_media(&mut self, client_id: usize, command: MediaCommand) {
+        if let Err(e) = self.media.handle(client_id, &command) {
+            self.log_warn(&format!("Media command {:?} failed: {}", command, e));
+        }
+
+        if matches!(
+            command,
+            MediaCommand::ListPlayers | MediaCommand::SelectPlayer(_)
+        ) {
+            match self.media.players() {
+                Ok(players) => self.notifier.send_to(
+                    client_id,
+                    &ClientMessage::MediaPlayers {
+                        players,
+                        selected: self.media.selected_player(client_id).map(str::to_string),
+                    },
+                ),
+                Err(e) => self.log_warn(&format!("Failed to list media players: {}", e)),
+            }
+        }
+    }
+
+    fn handle_gamepad(&mut self, client_id: usize, event: GamepadEvent) {
+        let input = &self.input;
+        let gamepad = self.gamepads.entry(client_id).or_insert_with(|| {
+            match input.lock().unwrap().create_gamepad(client_id) {
+                Ok(gamepad) => {
+                    Self::static_log_info(&format!("Plugged in gamepad for client {}", client_id));
+                    Some(gamepad)
+                }
+                Err(e) => {
+                    Self::static_log_warn(&format!(
+                        "Failed to create gamepad for client {}: {}",
+                        client_id, e
+                    ));
+                    None
+                }
+            }
+        });
+
+        if let Some(gamepad) = gamepad {
+            if let Err(e) = gamepad.send(&event) {
+                self.log_warn(&format!("Gamepad event {:?} failed: {}", event, e));
+            }
+        }
+    }
+
+    fn handle_pen(&mut self, event: PenEvent) {
+        let input = &self.input;
+        let tablet =
+            self.tablet
+                .get_or_insert_with(|| match input.lock().unwrap().create_tablet() {
+                    Ok(tablet) => {
+                        Self::static_log_info("Plugged in pen tablet");
+                        Some(tablet)
+                    }
+                    Err(e) => {
+                        Self::static_log_warn(&format!("Failed to create pen tablet: {}", e));
+                        None
+                    }
+                });
+
+        if let Some(tablet) = tablet {
+            if let Err(e) = tablet.send(&event) {
+                self.log_warn(&format!("Pen event failed: {}", e));
+            }
+        }
+    }
+
+    fn handle_gesture(&mut self, event: GestureEvent) {
+        let Some((action, repeat)) = self.gesture_bindings.resolve(&event) else {
+            self.log_debug(&format!("Gesture {:?} is not bound", event));
+            return;
+        };
+
+        let mut input = self.input.lock().unwrap();
+        let result = match action {
+            GestureAction::Keys(keys) => (0..repeat).try_for_each(|_| input.key_combo(keys)),
+            GestureAction::Click(button) => {
+                (0..repeat).try_for_each(|_| input.button(*button, Direction::Click))
+            }
+            GestureAction::ScrollWith(keys) => {
+                // spreading the fingers and rotating clockwise scroll up
+                let amount = match event {
+                    GestureEvent::Pinch { steps } => -(steps as i32),
+                    GestureEvent::Rotate { clockwise: true } => -1,
+                    _ => 1,
+                };
+                scroll_with(input.as_mut(), keys, amount)
+            }
+            GestureAction::None => Ok(()),
+        };
+        drop(input);
+        self.log_input_error(result);
+    }
+
+    fn handle_presentation(&mut self, command: PresentationCommand) {
+        match command {
+            PresentationCommand::Laser { dx, dy } => {
+                let appeared = !self.presentation.laser_visible();
+                let (x, y) = self.presentation.move_laser(dx, dy);
+                match &mut self.laser_overlay {
+                    Some(overlay) => {
+                        let result = overlay.show(x, y);
+                        self.log_input_error(result);
+                    }
+                    None => {
+                        let (dx, dy) = presentation::laser_cursor_delta(dx, dy);
+                        self.mouse_move_relative(dx, dy);
+                    }
+                }
+                // samples stream in continuously, only report the laser showing up
+                if !appeared {
+                    return;
+                }
+            }
+            PresentationCommand::LaserOff => {
+                if self.presentation.hide_laser() {
+                    if let Some(overlay) = &mut self.laser_overlay {
+                        let result = overlay.hide();
+                        self.log_input_error(result);
+                    }
+                }
+            }
+            command => {
+                if let Some(keys) = self.presentation.handle(&command) {
+                    self.press_key_combo(&keys);
+                }
+            }
+        }
+        self.notifier
+            .broadcast(&ClientMessage::Presentation(self.presentation.status()));
+    }
+
+    fn handle_air_mouse(&mut self, client_id: usize, event: AirMouseEvent) {
+        let air_mouse = self.air_mice.entry(client_id).or_default();
+        match event {
+            AirMouseEvent::Sample {
+                yaw,
+                pitch,
+                interval_ms,
+            } => {
+                let (dx, dy) = air_mouse.sample(yaw, pitch, interval_ms);
+                if (dx, dy) != (0, 0) {
+                    self.mouse_move_relative(dx, dy);
+                }
+            }
+            AirMouseEvent::Recenter => air_mouse.recenter(),
+            AirMouseEvent::Settings(settings) => air_mouse.set_settings(settings),
+        }
+    }
+
+    fn handle_window(&mut self, client_id: usize, command: WindowCommand) {
+        let Some(manager) = self.window_manager.as_mut() else {
+            match window_manager::fallback_keys(&command) {
+                Some(keys) => self.press_key_combo(&keys),
+                None => self.log_warn(&format!(
+                    "{:?} needs an X11 session, there is no key binding for it",
+                    command
+                )),
+            }
+            return;
+        };
+
+        if let Err(e) = window_manager::apply(manager.as_mut(), &command) {
+            Self::static_log_warn(&format!("Window command {:?} failed: {}", command, e));
+        }
+        // the monitor pushes later changes; answer list requests right away
+        if command == WindowCommand::List {
+            match manager.windows() {
+                Ok(windows) => self
+                    .notifier
+                    .send_to(client_id, &ClientMessage::Windows { windows }),
+                Err(e) => Self::static_log_warn(&format!("Failed to list windows: {}", e)),
+            }
+        }
+    }
+
+    fn handle_monitor(&mut self, client_id: usize, command: MonitorCommand) {
+        let Some(layout) = self.monitor_layout.as_mut() else {
+            self.log_warn(&format!("{:?} needs an X11 session", command));
+            return;
+        };
+        let monitors = match layout.monitors() {
+            Ok(monitors) => monitors,
+            Err(e) => {
+                Self::static_log_warn(&format!("Failed to list monitors: {}", e));
+                return;
+            }
+        };
+        let monitor = |index: u8| {
+            let found = monitors.get(index as usize).cloned();
+            if found.is_none() {
+                Self::static_log_warn(&format!("There is no monitor {}", index));
+            }
+            found
+        };
+
+        match command {
+            MonitorCommand::List => {
+                self.notifier
+                    .send_to(client_id, &ClientMessage::Monitors { monitors });
+            }
+            MonitorCommand::JumpTo(index) => {
+                if let Some(monitor) = monitor(index) {
+                    if let Err(e) = layout.warp_pointer(monitor.center()) {
+                        Self::static_log_warn(&format!("Failed to move the pointer: {}", e));
+                    }
+                }
+            }
+            MonitorCommand::Confine(index) => {
+                if let Some(monitor) = monitor(index) {
+                    self.confined_to = Some(monitor);
+                }
+            }
+            MonitorCommand::Release => self.confined_to = None,
+        }
+    }
+
+    fn list_apps(&mut self, client_id: usize, query: &str) {
+        self.apps.refresh_if_stale();
+        self.notifier.send_to(
+            client_id,
+            &ClientMessage::Apps {
+                apps: self.apps.search_for(client_id, query),
+            },
+        );
+    }
+
+    fn launch_app(&mut self, id: &str) {
+        // the app may have been installed since the phone's last search
+        if !self.apps.contains(id) {
+            self.apps.refresh();
+        }
+        if let Err(e) = self.apps.launch(id) {
+            self.log_warn(&format!("Failed to launch {}: {}", id, e));
+        }
     }
 
-    fn handle_input(&mut self, action: Action) -> ConnectionStatus {
+    /// Adds or removes the client from the ones receiving the desktop notifications.
+    /// The notifications are only watched while somebody is subscribed.
+    fn mirror_notifications(&mut self, client_id: usize, subscribe: bool) {
+        let mut subscribers = self.notification_subscribers.lock().unwrap();
+        if subscribe {
+            subscribers.insert(client_id);
+        } else {
+            subscribers.remove(&client_id);
+        }
+
+        if subscribers.is_empty() {
+            self.notification_mirror = None;
+        } else if self.notification_mirror.is_none() {
+            drop(subscribers);
+            self.notification_mirror = Some(self.start_notification_mirror());
+        }
+    }
+
+    fn start_notification_mirror(&self) -> NotificationMirror {
+        NotificationMirror::start(
+            self.notifier.clone(),
+            self.notification_filter.clone(),
+            self.notification_subscribers.clone(),
+        )
+    }
+
+    /// Starts, updates or stops the screen preview of the client. The screen is only
+    /// captured while a client has a preview running.
+    fn handle_preview(&mut self, client_id: usize, command: PreviewCommand) {
+        let mut streams = self.preview_streams.lock().unwrap();
+        match command {
+            PreviewCommand::Start(settings) => {
+                streams.insert(client_id, settings.clamped());
+            }
+            PreviewCommand::Stop => {
+                streams.remove(&client_id);
+            }
+        }
+
+        if streams.is_empty() {
+            self.screen_preview = None;
+        } else if self.screen_preview.is_none() {
+            self.screen_preview = Some(ScreenPreview::start(
+                self.notifier.clone(),
+                self.preview_streams.clone(),
+            ));
+        }
+    }
+
+    fn run_macro(&mut self, id: &str) {
+        let Some(to_run) = self.macros.get(id) else {
+            self.log_warn(&format!("No macro with id '{}'", id));
+            return;
+        };
+        let input = &self.input;
+        self.macro_runner
+            .get_or_insert_with(|| MacroRunner::start(input.clone()))
+            .run(to_run);
+    }
+
+    fn run_shortcut(&mut self, id: &str) {
+        let Some(shortcut) = self.shortcuts.get(id) else {
+            self.log_warn(&format!("No shortcut with id '{}'", id));
+            return;
+        };
+        match shortcut.action {
+            ShortcutAction::Keys { keys } => self.press_key_combo(&keys),
+            ShortcutAction::Text { text } => self.type_string(&text),
+            ShortcutAction::Command { command } => run_command(&command),
+            ShortcutAction::Macro { id } => self.run_macro(&id),
+        }
+    }
+
+    /// Adds the action to the macro being recorded, if any
+    fn record_macro_step(&self, action: &Action) {
+        if !self.macros.is_recording() {
+            return;
+        }
+        let step = match action {
+            Action::KeyPress(key) => match self.key_bindings.translate_to_os_key(key) {
+                Some(keys) => MacroStep::Keys { keys },
+                None => return,
+            },
+            Action::Text(c) => MacroStep::Text {
+                text: c.to_string(),
+            },
+            Action::MouseMove(coordinates) => MacroStep::MouseMove {
+                dx: coordinates.x as i32,
+                dy: coordinates.y as i32,
+            },
+            Action::MouseClick(button) => match self.key_bindings.translate_to_os_button(button) {
+                Some(button) => MacroStep::Click { button },
+                None => return,
+            },
+            Action::Scroll(delta) => MacroStep::Scroll {
+                amount: *delta as i32,
+            },
+            Action::TerminalCommand(TerminalCommand { command }) => MacroStep::Command {
+                command: command.clone(),
+            },
+            _ => return,
+        };
+        self.macros.record(step);
+    }
+
+    fn handle_input(&mut self, client_id: usize, action: Action) -> ConnectionStatus {
+        if !is_allowed(&self.permissions, &action) {
+            self.log_warn(&format!(
+                "Client {} is not allowed to {:?} by the server config",
+                client_id, action
+            ));
+            return ConnectionStatus::Connected;
+        }
+
         match action {
+            Action::KeyPress(key)
+                if self.try_media_key(client_id, &key) || self.try_brightness_key(&key) => {}
+
             Action::KeyPress(key) => {
                 if let Some(key_combo) = self.key_bindings.translate_to_os_key(&key) {
                     self.press_key_combo(&key_combo);
@@ -80,7 +726,7 @@ impl MobileController {
 
             Action::MouseClick(button) => {
                 if let Some(button) = self.key_bindings.translate_to_os_button(&button) {
-                    self.mouse_button(button);
+                    self.mouse_button(button, Direction::Click);
                 } else {
                     self.log_warn(&format!("Key: {:?} is not mapped for current OS", button));
                 }
@@ -88,13 +734,13 @@ impl MobileController {
 
             Action::MouseDown(button) => {
                 if let Some(button) = self.key_bindings.translate_to_os_button(&button) {
-                    self.mouse_button_down(button);
+                    self.mouse_button(button, Direction::Press);
                 }
             }
 
             Action::MouseUp(button) => {
                 if let Some(button) = self.key_bindings.translate_to_os_button(&button) {
-                    self.mouse_button_up(button);
+                    self.mouse_button(button, Direction::Release);
                 }
             }
 
@@ -103,14 +749,81 @@ impl MobileController {
             Action::Scroll(delta) => self.scroll(delta),
 
             Action::MouseMove(coordinates) => {
-                self.mouse_move_relative(coordinates.x, coordinates.y)
+                self.mouse_move_relative(coordinates.x as i32, coordinates.y as i32)
             }
 
             Action::Disconnect => return ConnectionStatus::Disconnected,
 
-            Action::Shutdown => shutdown_computer(),
+            Action::Shutdown => self.handle_power(client_id, PowerCommand::Shutdown),
+
+            Action::Power(command) => self.handle_power(client_id, command),
 
             Action::TerminalCommand(TerminalCommand { command }) => run_command(&command),
+
+            Action::SetVolume(volume) => {
+                if let Err(e) = audio::set_volume(volume) {
+                    self.log_warn(&format!("Failed to set volume: {}", e));
+                }
+                DesktopState::publish(&self.notifier);
+            }
+
+            Action::RequestDesktopState => {
+                DesktopState::publish(&self.notifier);
+            }
+
+            Action::Media(command) => self.handle_media(client_id, command),
+
+            Action::SetBrightness(percent) => self.set_brightness(percent),
+
+            Action::Gamepad(event) => self.handle_gamepad(client_id, event),
+
+            Action::Gesture(event) => self.handle_gesture(event),
+
+            Action::Pen(event) => self.handle_pen(event),
+
+            Action::RunMacro(id) => self.run_macro(&id),
+
+            Action::ListMacros => self.notifier.send_to(
+                client_id,
+                &ClientMessage::Macros {
+                    macros: self.macros.summaries(),
+                },
+            ),
+
+            Action::RunShortcut(id) => self.run_shortcut(&id),
+
+            Action::Presentation(command) => self.handle_presentation(command),
+
+            Action::AirMouse(event) => self.handle_air_mouse(client_id, event),
+
+            Action::Window(command) => self.handle_window(client_id, command),
+
+            Action::ListApps(query) => self.list_apps(client_id, &query),
+
+            Action::LaunchApp(id) => self.launch_app(&id),
+
+            Action::FileTransfer(command) => self.files.handle(client_id, command),
+
+            Action::OpenUrl(url) => {
+                if let Err(e) = self.url_opener.open(&url) {
+                    self.log_warn(&format!("Not opening link: {}", e));
+                }
+            }
+
+            Action::MirrorNotifications(subscribe) => {
+                self.mirror_notifications(client_id, subscribe)
+            }
+
+            Action::ScreenPreview(command) => self.handle_preview(client_id, command),
+
+            Action::Monitor(command) => self.handle_monitor(client_id, command),
+
+            Action::ListShortcuts => self.notifier.send_to(
+                client_id,
+                &ClientMessage::Shortcuts {
+                    shortcuts: self.shortcuts.shortcuts(),
+                },
+            ),
         };
 
         ConnectionStatus::Connected
@@ -118,79 +831,671 @@ impl MobileController {
 }
 
 impl Application for MobileController {
-    fn dispatch_to_device(&mut self, mut input: &[u8]) -> ConnectionStatus {
+    fn dispatch_to_device(&mut self, client_id: usize, input: &[u8]) -> ConnectionStatus {
+        // put back what was left of the previous read
+        let buffered;
+        let mut input = match self.partial_input.remove(&client_id) {
+            Some(mut partial) => {
+                partial.extend_from_slice(input);
+                buffered = partial;
+                &buffered[..]
+            }
+            None => input,
+        };
+
         // while there are bytes to be consumed -> consume.
         // each TCP may send buffered inputs within the same packet, thus we need to
         // check if there aren't any other commands within the bytes of the current packet
-        while input.len() > 0 {
+        while !input.is_empty() {
+            if Action::is_incomplete(input) {
+                self.partial_input.insert(client_id, input.to_vec());
+                break;
+            }
             let action = Action::decode(&mut input);
             self.log_debug(&format!("Action received: {:?}", action));
-            match self.handle_input(action) {
-                ConnectionStatus::Disconnected => return ConnectionStatus::Disconnected,
-                _ => (),
-            };
+            self.record_macro_step(&action);
+            if let ConnectionStatus::Disconnected = self.handle_input(client_id, action) {
+                return ConnectionStatus::Disconnected;
+            }
         }
         ConnectionStatus::Connected
     }
-}
 
-// Machine Shutdown OS dependent
-#[cfg(target_os = "windows")]
-fn shutdown_computer() {
-    Command::new("shutdown")
-        .args(&["/s", "/f", "/t", "0"])
-        .spawn()
-        .expect("Failed to execute shutdown command");
+    fn client_disconnected(&mut self, client_id: usize) {
+        self.air_mice.remove(&client_id);
+        self.partial_input.remove(&client_id);
+        self.files.client_disconnected(client_id);
+        self.media.client_disconnected(client_id);
+        self.apps.client_disconnected(client_id);
+        self.mirror_notifications(client_id, false);
+        self.handle_preview(client_id, PreviewCommand::Stop);
+        if let Some(Some(_)) = self.gamepads.remove(&client_id) {
+            self.log_info(&format!("Unplugged gamepad of client {}", client_id));
+        }
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.state_monitor = Some(DesktopStateMonitor::start(notifier.clone()));
+        self.files.set_notifier(notifier.clone());
+        if self.window_manager.is_some() {
+            self.window_monitor = Some(WindowListMonitor::start(notifier.clone()));
+        }
+        let shortcut_notifier = notifier.clone();
+        self.shortcut_subscription = Some(self.shortcuts.on_change(move |shortcuts| {
+            shortcut_notifier.broadcast(&ClientMessage::Shortcuts {
+                shortcuts: shortcuts.to_vec(),
+            })
+        }));
+        self.notifier = notifier;
+    }
+
+    fn configure(&mut self, config: &ServerConfig) {
+        self.permissions = config.permissions;
+        // reloaded every time, so saving the config again picks up an edited file
+        self.key_bindings = KeyBindings::load(config.key_bindings_file().as_deref());
+
+        // stop what the clients are no longer allowed to see
+        if !self.permissions.screen_preview {
+            self.preview_streams.lock().unwrap().clear();
+            self.screen_preview = None;
+        }
+        if !self.permissions.notifications {
+            self.notification_subscribers.lock().unwrap().clear();
+            self.notification_mirror = None;
+        }
+        if self.notification_filter != config.notifications {
+            self.notification_filter = config.notifications.clone();
+            // the mirror running keeps the filter it was started with
+            if self.notification_mirror.take().is_some() {
+                self.notification_mirror = Some(self.start_notification_mirror());
+            }
+        }
+    }
 }
 
-#[cfg(target_os = "linux")]
-fn shutdown_computer() {
-    Command::new("shutdown")
-        .arg("now")
-        .spawn()
-        .expect("Failed to execute shutdown command");
+/// Whether the permissions allow the action. Stopping what was allowed before is
+/// always allowed, as are the actions only reading the desktop state.
+fn is_allowed(permissions: &Permissions, action: &Action) -> bool {
+    match action {
+        Action::KeyPress(_)
+        | Action::Text(_)
+        | Action::Scroll(_)
+        | Action::MouseMove(_)
+        | Action::MouseClick(_)
+        | Action::MouseDown(_)
+        | Action::MouseUp(_)
+        | Action::Gamepad(_)
+        | Action::Gesture(_)
+        | Action::Pen(_)
+        | Action::AirMouse(_)
+        | Action::Presentation(_)
+        | Action::Window(_)
+        | Action::Monitor(_) => permissions.input,
+        Action::SetVolume(_) | Action::Media(_) | Action::SetBrightness(_) => permissions.media,
+        Action::Shutdown | Action::Power(_) => permissions.power,
+        Action::TerminalCommand(_)
+        | Action::RunMacro(_)
+        | Action::RunShortcut(_)
+        | Action::LaunchApp(_)
+        | Action::OpenUrl(_) => permissions.commands,
+        Action::FileTransfer(_) => permissions.files,
+        Action::ScreenPreview(PreviewCommand::Start(_)) => permissions.screen_preview,
+        Action::MirrorNotifications(true) => permissions.notifications,
+        Action::ScreenPreview(PreviewCommand::Stop)
+        | Action::MirrorNotifications(false)
+        | Action::Disconnect
+        | Action::RequestDesktopState
+        | Action::ListMacros
+        | Action::ListShortcuts
+        | Action::ListApps(_) => true,
+    }
 }
 
-#[cfg(target_os = "macos")]
-fn shutdown_computer() {
-    Command::new("shutdown")
-        .args(&["-h", "now"])
-        .spawn()
-        .expect("Failed to execute shutdown command");
+/// Scrolls while holding the keys, releasing them even if scrolling fails
+fn scroll_with(input: &mut dyn InputBackend, keys: &[InputKey], amount: i32) -> Result<(), String> {
+    for key in keys {
+        input.key(*key, Direction::Press)?;
+    }
+    let result = input.scroll(amount, Axis::Vertical);
+    for key in keys.iter().rev() {
+        input.key(*key, Direction::Release)?;
+    }
+    result
 }
 
 // OS-dependent terminal command issuer
 #[cfg(target_os = "windows")]
-fn run_command(command: &str) {
-    Command::new("cmd")
-        .args(&["/C", command])
-        .spawn()
-        .expect("Failed to execute command on Windows");
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
 }
 
 #[cfg(not(target_os = "windows"))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// Runs the macros one after the other on a worker thread, so their delays don't hold
+/// up the input of the clients. The input backend is only locked for each step.
+struct MacroRunner {
+    queue: Sender<Macro>,
+    shutdown_signal: Arc<AtomicBool>,
+}
+
+impl MacroRunner {
+    fn start(input: SharedInput) -> Self {
+        let (queue, macros) = mpsc::channel::<Macro>();
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::clone(&shutdown_signal);
+
+        thread::spawn(move || {
+            for to_run in macros {
+                Self::static_log_info(&format!("Running macro '{}'", to_run.name));
+                for step in &to_run.steps {
+                    if shutdown.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if let Err(e) = Self::run_step(&input, step) {
+                        Self::static_log_warn(&format!("Macro '{}' stopped: {}", to_run.name, e));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            queue,
+            shutdown_signal,
+        }
+    }
+
+    /// Runs the macro once the ones already queued are done
+    fn run(&self, to_run: Macro) {
+        if self.queue.send(to_run).is_err() {
+            self.log_warn("The macro runner has exited");
+        }
+    }
+
+    fn run_step(input: &SharedInput, step: &MacroStep) -> Result<(), String> {
+        match step {
+            MacroStep::Keys { keys } => input.lock().unwrap().key_combo(keys),
+            MacroStep::Text { text } => input.lock().unwrap().text(text),
+            MacroStep::Delay { ms } => {
+                thread::sleep(Duration::from_millis((*ms).min(MAX_DELAY_MS)));
+                Ok(())
+            }
+            MacroStep::MouseMove { dx, dy } => input.lock().unwrap().move_relative(*dx, *dy),
+            MacroStep::Click { button } => input.lock().unwrap().button(*button, Direction::Click),
+            MacroStep::Scroll { amount } => input.lock().unwrap().scroll(*amount, Axis::Vertical),
+            MacroStep::Command { command } => {
+                run_command(command);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for MacroRunner {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Runs the command in the background, reaping it once it exits
 fn run_command(command: &str) {
-    Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .spawn()
-        .expect("Failed to execute command");
+    match shell_command(command).spawn() {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => MobileController::static_log_warn(&format!(
+            "Failed to execute command '{}': {}",
+            command, e
+        )),
+    }
 }
 
+#[cfg(test)]
 mod tests {
+    use std::fs;
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
-    #![allow(unused_imports)]
     use super::run_command;
     use super::MobileController;
-    use crate::server::application::Application;
+    use crate::actions::{GamepadButton, GamepadEvent, PenEvent};
+    use crate::file_transfer::FileTransfers;
+    use crate::input::{
+        mock::{InputEvent, RecordingBackend},
+        Axis, Direction, InputButton, InputKey,
+    };
+    use crate::macros::{Macro, MacroLibrary, MacroStep};
+    use crate::monitors::{MonitorInfo, MonitorLayout};
+    use crate::server::application::{Application, ConnectionStatus};
+    use crate::server::config::ServerConfig;
+    use crate::shortcuts::{Shortcut, ShortcutAction, ShortcutCatalog, ShortcutIcon};
+
+    fn recording_controller() -> (
+        MobileController,
+        std::sync::Arc<std::sync::Mutex<Vec<InputEvent>>>,
+    ) {
+        let backend = RecordingBackend::new();
+        let events = backend.events();
+        let mut controller = MobileController::with_backend(Box::new(backend))
+            .with_macro_library(MacroLibrary::in_memory())
+            .with_shortcut_catalog(ShortcutCatalog::in_memory())
+            .with_file_transfers(FileTransfers::in_memory());
+        // keep the tests off the desktop they may run on
+        controller.window_manager = None;
+        controller.monitor_layout = None;
+        (controller, events)
+    }
+
+    /// Waits for the macro runner to emit the input
+    fn wait_for_events(events: &Mutex<Vec<InputEvent>>, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while events.lock().unwrap().len() < count && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn permissions_of_the_server_config() {
+        let (mut app, events) = recording_controller();
+        let mut config = ServerConfig::default();
+        config.permissions.input = false;
+        app.configure(&config);
+
+        //                  | key backspace  | scroll | disconnect |
+        let commands: &[u8] = &[0u8, 0u8, 2u8, 2u8, 5u8];
+        assert!(matches!(
+            app.dispatch_to_device(1, commands),
+            ConnectionStatus::Disconnected
+        ));
+        assert!(events.lock().unwrap().is_empty());
+
+        app.configure(&ServerConfig::default());
+        app.dispatch_to_device(1, &[2u8, 2u8]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![InputEvent::Scroll(2, Axis::Vertical)]
+        );
+    }
 
     #[test]
-    #[ignore] // Requires X11 DISPLAY environment
     fn parse_several_commands_at_once() {
         //                  | key backspace  | scroll | mouse move            |
         let commands: &[u8] = &[0u8, 0u8, 2u8, 2u8, 3u8, 2u8, (-8i8) as u8];
-        let mut app = MobileController::new().unwrap();
-        app.dispatch_to_device(commands);
+        let (mut app, events) = recording_controller();
+
+        assert!(matches!(
+            app.dispatch_to_device(1, commands),
+            ConnectionStatus::Connected
+        ));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(InputKey::Backspace, Direction::Press),
+                InputEvent::Key(InputKey::Backspace, Direction::Release),
+                InputEvent::Scroll(2, Axis::Vertical),
+                InputEvent::MoveRelative(2, -8),
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_buttons_and_key_combos() {
+        //                  | click left | down left | up left | close tab |
+        let commands: &[u8] = &[4u8, 0u8, 8u8, 0u8, 9u8, 0u8, 0u8, 8u8];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, commands);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Button(InputButton::Left, Direction::Click),
+                InputEvent::Button(InputButton::Left, Direction::Press),
+                InputEvent::Button(InputButton::Left, Direction::Release),
+                InputEvent::Key(InputKey::Control, Direction::Press),
+                InputEvent::Key(InputKey::Unicode('w'), Direction::Press),
+                InputEvent::Key(InputKey::Unicode('w'), Direction::Release),
+                InputEvent::Key(InputKey::Control, Direction::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn disconnect_stops_processing() {
+        //                  | disconnect | scroll |
+        let commands: &[u8] = &[5u8, 2u8, 2u8];
+        let (mut app, events) = recording_controller();
+
+        assert!(matches!(
+            app.dispatch_to_device(1, commands),
+            ConnectionStatus::Disconnected
+        ));
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn gamepad_per_client() {
+        //                  | A pressed          | A released         |
+        let press_a: &[u8] = &[15u8, 0u8, 0u8, 1u8];
+        let release_a: &[u8] = &[15u8, 0u8, 0u8, 0u8];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, press_a);
+        app.dispatch_to_device(2, press_a);
+        app.dispatch_to_device(1, release_a);
+        app.client_disconnected(1);
+        app.client_disconnected(3);
+
+        let a = |pressed| GamepadEvent::Button {
+            button: GamepadButton::A,
+            pressed,
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Gamepad(1, a(true)),
+                InputEvent::Gamepad(2, a(true)),
+                InputEvent::Gamepad(1, a(false)),
+                InputEvent::GamepadUnplugged(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn pinch_zooms_with_modified_scroll() {
+        //                  | pinch together 2 steps    | two-finger tap |
+        let commands: &[u8] = &[16u8, 1u8, (-2i8) as u8, 16u8, 3u8, 2u8];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, commands);
+
+        let modifier = if cfg!(target_os = "macos") {
+            InputKey::Meta
+        } else {
+            InputKey::Control
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(modifier, Direction::Press),
+                InputEvent::Scroll(2, Axis::Vertical),
+                InputEvent::Key(modifier, Direction::Release),
+                InputEvent::Button(InputButton::Right, Direction::Click),
+            ]
+        );
+    }
+
+    #[test]
+    fn pen_events_reach_the_tablet() {
+        //                  | pen | x    | y    | pressure | tilt | hovering |
+        let hover: &[u8] = &[17u8, 0, 10, 0, 20, 0, 0, 0, 0, 0b001];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, hover);
+        app.dispatch_to_device(2, hover);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            InputEvent::Pen(PenEvent {
+                x: 10,
+                y: 20,
+                in_range: true,
+                touching: false,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn record_and_run_macro() {
+        //                  | text 'o' | text 'k' | backspace |
+        let commands: &[u8] = &[1u8, b'o', 1u8, b'k', 0u8, 0u8];
+        let (mut app, events) = recording_controller();
+        let macros = app.macro_library();
+
+        macros.start_recording();
+        app.dispatch_to_device(1, commands);
+        let recorded = macros.stop_recording("ok", "Type ok").unwrap();
+        assert_eq!(
+            recorded.steps,
+            vec![
+                MacroStep::Text {
+                    text: "ok".to_string()
+                },
+                MacroStep::Keys {
+                    keys: vec![InputKey::Backspace]
+                },
+            ]
+        );
+
+        events.lock().unwrap().clear();
+        //                  | run macro "ok"     |
+        app.dispatch_to_device(1, &[18u8, 2u8, b'o', b'k']);
+        wait_for_events(&events, 3);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Text("ok".to_string()),
+                InputEvent::Key(InputKey::Backspace, Direction::Press),
+                InputEvent::Key(InputKey::Backspace, Direction::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_delays_dont_hold_up_the_input() {
+        let (mut app, events) = recording_controller();
+        app.macro_library()
+            .save_macro(Macro {
+                id: "slow".to_string(),
+                name: "Slow".to_string(),
+                steps: vec![
+                    MacroStep::Delay { ms: 300 },
+                    MacroStep::Text {
+                        text: "late".to_string(),
+                    },
+                ],
+            })
+            .unwrap();
+
+        //                  | run macro "slow"                 | scroll |
+        app.dispatch_to_device(1, &[18u8, 4u8, b's', b'l', b'o', b'w', 2u8, 2u8]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![InputEvent::Scroll(2, Axis::Vertical)]
+        );
+        wait_for_events(&events, 2);
+        assert_eq!(
+            events.lock().unwrap()[1],
+            InputEvent::Text("late".to_string())
+        );
+    }
+
+    #[test]
+    fn run_shortcut_by_id() {
+        let (mut app, events) = recording_controller();
+        app.shortcut_catalog()
+            .save_shortcut(Shortcut {
+                id: "tab".to_string(),
+                label: "New tab".to_string(),
+                icon: ShortcutIcon {
+                    code_point: 0xe145,
+                    font_family: None,
+                },
+                action: ShortcutAction::Keys {
+                    keys: vec![InputKey::Control, InputKey::Unicode('t')],
+                },
+            })
+            .unwrap();
+
+        //                  | run shortcut "tab"       | unknown shortcut |
+        app.dispatch_to_device(1, &[20u8, 3u8, b't', b'a', b'b', 20u8, 1u8, b'x']);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(InputKey::Control, Direction::Press),
+                InputEvent::Key(InputKey::Unicode('t'), Direction::Press),
+                InputEvent::Key(InputKey::Unicode('t'), Direction::Release),
+                InputEvent::Key(InputKey::Control, Direction::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn presentation_keys_and_laser_fallback() {
+        //                  | next slide | laser 1° right, 0.5° up |
+        let commands: &[u8] = &[22u8, 2u8, 22u8, 6u8, 0, 100, 0xff, 0xce];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, commands);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(InputKey::RightArrow, Direction::Press),
+                InputEvent::Key(InputKey::RightArrow, Direction::Release),
+                InputEvent::MoveRelative(30, -15),
+            ]
+        );
+    }
+
+    #[test]
+    fn air_mouse_moves_the_cursor() {
+        //                  | unsmoothed, no dead zone | 50°/s right for 20ms |
+        let commands: &[u8] = &[23u8, 2u8, 0, 0, 10, 23u8, 0u8, 0x01, 0xf4, 0, 0, 20];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, commands);
+        // another phone keeps its own, default filter
+        app.dispatch_to_device(2, &[23u8, 0u8, 0, 1, 0, 0, 20]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![InputEvent::MoveRelative(10, 0)]
+        );
+    }
+
+    #[test]
+    fn notifications_watched_while_subscribed() {
+        let (mut app, _) = recording_controller();
+
+        //                  | subscribe | subscribe |
+        app.dispatch_to_device(1, &[29u8, 1u8]);
+        app.dispatch_to_device(2, &[29u8, 1u8]);
+        assert!(app.notification_mirror.is_some());
+
+        //                  | unsubscribe |
+        app.dispatch_to_device(1, &[29u8, 0u8]);
+        assert!(app.notification_mirror.is_some());
+        app.client_disconnected(2);
+        assert!(app.notification_mirror.is_none());
+        assert!(app.notification_subscribers.lock().unwrap().is_empty());
+
+        let mut config = ServerConfig::default();
+        config.permissions.notifications = false;
+        app.configure(&config);
+        app.dispatch_to_device(1, &[29u8, 1u8]);
+        assert!(app.notification_mirror.is_none());
+    }
+
+    /// Two 1920x1080 monitors side by side, recording where the pointer is warped
+    struct FakeLayout {
+        pointer: (i32, i32),
+        warps: std::sync::Arc<std::sync::Mutex<Vec<(i32, i32)>>>,
+    }
+
+    impl MonitorLayout for FakeLayout {
+        fn monitors(&mut self) -> Result<Vec<MonitorInfo>, String> {
+            let monitor = |x, primary| MonitorInfo {
+                name: format!("DP-{}", x / 1920),
+                x,
+                y: 0,
+                width: 1920,
+                height: 1080,
+                scale: 1.0,
+                primary,
+            };
+            Ok(vec![monitor(0, true), monitor(1920, false)])
+        }
+
+        fn pointer(&mut self) -> Result<(i32, i32), String> {
+            Ok(self.pointer)
+        }
+
+        fn warp_pointer(&mut self, position: (i32, i32)) -> Result<(), String> {
+            self.warps.lock().unwrap().push(position);
+            self.pointer = position;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn jump_and_confine_to_monitor() {
+        let (mut app, events) = recording_controller();
+        let warps = std::sync::Arc::default();
+        app.monitor_layout = Some(Box::new(FakeLayout {
+            pointer: (1900, 500),
+            warps: std::sync::Arc::clone(&warps),
+        }));
+
+        //                  | confine to 0 | move 50 right, 5 down | jump to 1 | release |
+        app.dispatch_to_device(1, &[31u8, 2, 0, 3u8, 50, 5, 31u8, 1, 1, 31u8, 3]);
+        // out of the confined monitor, the pointer is brought back before moving
+        app.dispatch_to_device(1, &[31u8, 2, 0, 3u8, 2, 0]);
+
+        assert_eq!(*warps.lock().unwrap(), vec![(2880, 540), (1919, 540)]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::MoveRelative(19, 5),
+                InputEvent::MoveRelative(0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn screen_captured_while_previewing() {
+        let (mut app, _) = recording_controller();
+
+        //                  | start: monitor 0, jpeg, 30 fps, 4000x100, 100% |
+        app.dispatch_to_device(1, &[30u8, 0, 0, 0, 30, 0x0f, 0xa0, 0, 100, 100]);
+        assert!(app.screen_preview.is_some());
+        let settings = app.preview_streams.lock().unwrap()[&1];
+        assert_eq!((settings.fps, settings.width), (10, 1920));
+
+        app.client_disconnected(1);
+        assert!(app.screen_preview.is_none());
+        assert!(app.preview_streams.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn window_commands_fall_back_to_keys() {
+        //                  | close focused window  | focus window 3      |
+        let commands: &[u8] = &[24u8, 4u8, 0, 0, 0, 0, 24u8, 1u8, 0, 0, 0, 3];
+        let (mut app, events) = recording_controller();
+
+        app.dispatch_to_device(1, commands);
+        let close = if cfg!(target_os = "macos") {
+            [InputKey::Meta, InputKey::Unicode('w')]
+        } else {
+            [InputKey::Alt, InputKey::F4]
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                InputEvent::Key(close[0], Direction::Press),
+                InputEvent::Key(close[1], Direction::Press),
+                InputEvent::Key(close[1], Direction::Release),
+                InputEvent::Key(close[0], Direction::Release),
+            ]
+        );
     }
 
     #[test]
@@ -199,4 +1504,43 @@ mod tests {
         // In a headless environment, it will fail to open firefox but that's ok
         run_command("echo test");
     }
+
+    #[test]
+    fn file_chunks_split_across_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, events) = recording_controller();
+        let files = FileTransfers::in_memory();
+        files
+            .set_download_dir(Some(dir.path().to_path_buf()))
+            .unwrap();
+        let mut app = app.with_file_transfers(files);
+
+        // sha256 of "hi"
+        let sha256: [u8; 32] = [
+            0x8f, 0x43, 0x43, 0x46, 0x64, 0x8f, 0x6b, 0x96, 0xdf, 0x89, 0xdd, 0xa9, 0x01, 0xc5,
+            0x17, 0x6b, 0x10, 0xa6, 0xd8, 0x39, 0x61, 0xdd, 0x3c, 0x1a, 0xc8, 0x8b, 0x59, 0xb2,
+            0xdc, 0x32, 0x7a, 0xa4,
+        ];
+        //                  | offer transfer 1, 2 bytes                    |
+        let mut offer = vec![27u8, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2];
+        offer.extend_from_slice(&sha256);
+        offer.extend_from_slice(&[4, b'h', b'i', b'.', b't']);
+        app.dispatch_to_device(1, &offer);
+
+        //                  | chunk "hi" at offset 0, in two reads              |
+        let chunk: &[u8] = &[
+            27u8, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, b'h', b'i',
+        ];
+        app.dispatch_to_device(1, &chunk[..17]);
+        //                  | rest of the chunk | finish          | scroll |
+        let mut rest = chunk[17..].to_vec();
+        rest.extend_from_slice(&[27u8, 2, 0, 0, 0, 1, 2, 1]);
+        app.dispatch_to_device(1, &rest);
+
+        assert_eq!(fs::read(dir.path().join("hi.t")).unwrap(), b"hi");
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![InputEvent::Scroll(1, Axis::Vertical)]
+        );
+    }
 }
@@ -0,0 +1,105 @@
+//! Layout of the desktop monitors.
+//!
+//! Monitors are numbered in the order the display server lists them, the same
+//! numbers the window commands and the screen preview use. The list is sent to every
+//! client when it connects, and again in reply to the `List` monitor command.
+
+#[cfg(target_os = "linux")]
+pub(crate) mod x11;
+
+use std::env;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{input, logger::Loggable};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// Output name, e.g. `HDMI-1`
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Ratio of the desktop's DPI to 96, e.g. `2.0` on a HiDPI desktop
+    pub scale: f32,
+    pub primary: bool,
+}
+
+impl MonitorInfo {
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.x + self.width as i32 / 2,
+            self.y + self.height as i32 / 2,
+        )
+    }
+
+    /// The closest point on the monitor
+    pub fn clamp(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        (
+            x.clamp(self.x, self.x + self.width as i32 - 1),
+            y.clamp(self.y, self.y + self.height as i32 - 1),
+        )
+    }
+}
+
+/// Something that knows the monitor layout and where the pointer is
+pub trait MonitorLayout: Send + Sync {
+    fn monitors(&mut self) -> Result<Vec<MonitorInfo>, String>;
+
+    /// Pointer position on the desktop
+    fn pointer(&mut self) -> Result<(i32, i32), String>;
+
+    /// Moves the pointer to a position on the desktop
+    fn warp_pointer(&mut self, position: (i32, i32)) -> Result<(), String>;
+}
+
+/// Connects to the display of the current session, if its layout can be read
+pub fn connect() -> Result<Box<dyn MonitorLayout>, String> {
+    if input::needs_uinput(
+        env::var("DISPLAY").ok().as_deref(),
+        env::var("XDG_SESSION_TYPE").ok().as_deref(),
+    ) {
+        return Err("Monitors can only be listed on X11".to_string());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(x11::X11Monitors::connect()?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Monitors can only be listed on X11".to_string())
+    }
+}
+
+/// The monitors of the current session, or none if they can't be read
+pub fn list() -> Vec<MonitorInfo> {
+    connect()
+        .and_then(|mut layout| layout.monitors())
+        .unwrap_or_else(|e| {
+            MonitorInfo::static_log_debug(&format!("No monitor layout: {}", e));
+            Vec::new()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_and_clamp() {
+        let monitor = MonitorInfo {
+            name: "DP-1".to_string(),
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+            scale: 1.0,
+            primary: false,
+        };
+        assert_eq!(monitor.center(), (3200, 720));
+        assert_eq!(monitor.clamp((100, -5)), (1920, 0));
+        assert_eq!(monitor.clamp((5000, 2000)), (4479, 1439));
+        assert_eq!(monitor.clamp((2000, 10)), (2000, 10));
+    }
+}
@@ -0,0 +1,151 @@
+//! Monitor layout on X11, from RandR.
+
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        randr::ConnectionExt as _,
+        xproto::{AtomEnum, ConnectionExt as _, Window},
+    },
+    rust_connection::RustConnection,
+    NONE,
+};
+
+use super::{MonitorInfo, MonitorLayout};
+
+/// DPI of a desktop at scale 1
+const BASE_DPI: f32 = 96.0;
+
+pub struct X11Monitors {
+    connection: RustConnection,
+    root: Window,
+}
+
+impl X11Monitors {
+    pub fn connect() -> Result<Self, String> {
+        let (connection, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = connection.setup().roots[screen].root;
+        Ok(Self { connection, root })
+    }
+}
+
+impl MonitorLayout for X11Monitors {
+    fn monitors(&mut self) -> Result<Vec<MonitorInfo>, String> {
+        list_monitors(&self.connection, self.root)
+    }
+
+    fn pointer(&mut self) -> Result<(i32, i32), String> {
+        let pointer = self
+            .connection
+            .query_pointer(self.root)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        Ok((pointer.root_x as i32, pointer.root_y as i32))
+    }
+
+    fn warp_pointer(&mut self, (x, y): (i32, i32)) -> Result<(), String> {
+        self.connection
+            .warp_pointer(NONE, self.root, 0, 0, 0, 0, x as i16, y as i16)
+            .map_err(|e| e.to_string())?;
+        self.connection.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// The monitors of the screen of `root`. Without RandR, as on a bare Xvfb, the whole
+/// screen is a single monitor.
+pub(crate) fn list_monitors(
+    connection: &impl Connection,
+    root: Window,
+) -> Result<Vec<MonitorInfo>, String> {
+    let scale = desktop_scale(connection, root);
+    let monitors = connection
+        .randr_get_monitors(root, true)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map(|reply| reply.monitors)
+        .unwrap_or_default();
+
+    if monitors.is_empty() {
+        let screen = connection
+            .setup()
+            .roots
+            .iter()
+            .find(|screen| screen.root == root)
+            .ok_or("Unknown root window")?;
+        return Ok(vec![MonitorInfo {
+            name: "screen".to_string(),
+            x: 0,
+            y: 0,
+            width: screen.width_in_pixels as u32,
+            height: screen.height_in_pixels as u32,
+            scale,
+            primary: true,
+        }]);
+    }
+
+    monitors
+        .iter()
+        .map(|monitor| {
+            let name = connection
+                .get_atom_name(monitor.name)
+                .map_err(|e| e.to_string())?
+                .reply()
+                .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                .unwrap_or_default();
+            Ok(MonitorInfo {
+                name,
+                x: monitor.x as i32,
+                y: monitor.y as i32,
+                width: monitor.width as u32,
+                height: monitor.height as u32,
+                scale,
+                primary: monitor.primary,
+            })
+        })
+        .collect()
+}
+
+/// Scale set through `Xft.dpi`, which X11 desktops use for HiDPI. X11 has one scale
+/// for all monitors.
+fn desktop_scale(connection: &impl Connection, root: Window) -> f32 {
+    let resources = connection
+        .get_property(
+            false,
+            root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            u32::MAX / 4,
+        )
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+        .unwrap_or_default();
+    scale_from_resources(&resources)
+}
+
+fn scale_from_resources(resources: &str) -> f32 {
+    resources
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim() == "Xft.dpi")
+        .and_then(|(_, dpi)| dpi.trim().parse::<f32>().ok())
+        .filter(|dpi| *dpi > 0.0)
+        .map_or(1.0, |dpi| dpi / BASE_DPI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_from_xft_dpi() {
+        assert_eq!(scale_from_resources(""), 1.0);
+        assert_eq!(
+            scale_from_resources("Xcursor.size:\t24\nXft.dpi:\t192\nXft.hinting:\t1\n"),
+            2.0
+        );
+        assert_eq!(scale_from_resources("Xft.dpi: 144"), 1.5);
+        assert_eq!(scale_from_resources("Xft.dpi: none"), 1.0);
+    }
+}
@@ -0,0 +1,305 @@
+//! Desktop notifications mirrored to the phones.
+//!
+//! Once a client subscribes, the session bus is monitored for the `Notify` calls
+//! apps make to `org.freedesktop.Notifications`, and each notification is sent to
+//! the subscribed clients. Which apps are mirrored is set by the `notifications`
+//! filter of the server config.
+
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use serde::Serialize;
+
+use crate::{
+    client_messages::ClientMessage, logger::Loggable, server::config::NotificationFilter,
+    server::notifier::Notifier,
+};
+
+/// A notification shown on the desktop
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DesktopNotification {
+    pub app: String,
+    pub summary: String,
+    /// Plain text, with the markup some apps use removed
+    pub body: String,
+    /// 0 low, 1 normal, 2 critical
+    pub urgency: Option<u8>,
+}
+
+/// Ids of the clients that want the notifications
+pub type Subscribers = Arc<Mutex<HashSet<usize>>>;
+
+/// Watches the desktop notifications and sends them to the subscribed clients.
+/// Dropping the mirror closes its session bus connection, which stops the watching thread.
+pub struct NotificationMirror {
+    shutdown_signal: Arc<AtomicBool>,
+    /// Set once the watching thread is connected
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl NotificationMirror {
+    pub fn start(notifier: Notifier, filter: NotificationFilter, subscribers: Subscribers) -> Self {
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::clone(&shutdown_signal);
+        let connection: Arc<Mutex<Option<Connection>>> = Arc::default();
+        let shared_connection = Arc::clone(&connection);
+
+        thread::spawn(move || {
+            let connection = match session_bus() {
+                Ok(connection) => connection,
+                Err(e) => {
+                    Self::static_log_warn(&format!("Can't mirror notifications: {}", e));
+                    return;
+                }
+            };
+            *shared_connection.lock().unwrap() = Some(connection.clone());
+            // dropped while connecting, after the connection could have been closed
+            if shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let forward = |notification: DesktopNotification| {
+                if filter.allows(&notification.app) {
+                    Self::static_log_debug(&format!("Mirroring notification: {:?}", notification));
+                    let message = ClientMessage::Notification(notification);
+                    for client_id in subscribers.lock().unwrap().iter() {
+                        notifier.send_to(*client_id, &message);
+                    }
+                }
+                !shutdown.load(Ordering::SeqCst)
+            };
+            match watch(&connection, forward) {
+                Err(e) if !shutdown.load(Ordering::SeqCst) => {
+                    Self::static_log_warn(&format!("Can't mirror notifications: {}", e))
+                }
+                _ => Self::static_log_info("Notification mirror has exited."),
+            }
+        });
+
+        Self {
+            shutdown_signal,
+            connection,
+        }
+    }
+}
+
+impl Drop for NotificationMirror {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+        if let Some(connection) = self.connection.lock().unwrap().take() {
+            close(connection);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+type Connection = zbus::blocking::Connection;
+
+#[cfg(not(target_os = "linux"))]
+type Connection = ();
+
+#[cfg(target_os = "linux")]
+fn session_bus() -> Result<Connection, String> {
+    Connection::session().map_err(|e| e.to_string())
+}
+
+/// Closes the connection, ending the `watch` on it
+#[cfg(target_os = "linux")]
+fn close(connection: Connection) {
+    if let Err(e) = connection.close() {
+        NotificationMirror::static_log_debug(&format!("Failed to close the session bus: {}", e));
+    }
+}
+
+/// Turns the connection into a monitor of the `Notify` calls, passing each
+/// notification to `forward` until it returns false.
+#[cfg(target_os = "linux")]
+fn watch(
+    connection: &Connection,
+    mut forward: impl FnMut(DesktopNotification) -> bool,
+) -> Result<(), String> {
+    use std::collections::HashMap;
+    use zbus::zvariant::OwnedValue;
+
+    type Notify = (
+        String,
+        u32,
+        String,
+        String,
+        String,
+        Vec<String>,
+        HashMap<String, OwnedValue>,
+        i32,
+    );
+
+    let rule = "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+    connection
+        .call_method(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            Some("org.freedesktop.DBus.Monitoring"),
+            "BecomeMonitor",
+            &(vec![rule], 0u32),
+        )
+        .map_err(|e| format!("Failed to monitor the session bus: {}", e))?;
+
+    for message in zbus::blocking::MessageIterator::from(connection) {
+        let message = message.map_err(|e| e.to_string())?;
+        if message.header().member().map(|member| member.as_str()) != Some("Notify") {
+            continue;
+        }
+        let Ok((app, _, _, summary, body, _, hints, _)) = message.body().deserialize::<Notify>()
+        else {
+            continue;
+        };
+        let notification = DesktopNotification {
+            app,
+            summary,
+            body: strip_markup(&body),
+            urgency: hints
+                .get("urgency")
+                .and_then(|value| u8::try_from(value).ok()),
+        };
+        if !forward(notification) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn session_bus() -> Result<Connection, String> {
+    Err("Notifications can only be mirrored from D-Bus".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn close(_connection: Connection) {}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(
+    _connection: &Connection,
+    _forward: impl FnMut(DesktopNotification) -> bool,
+) -> Result<(), String> {
+    Ok(())
+}
+
+/// Removes the tags of the markup allowed in notification bodies and decodes the
+/// XML entities
+fn strip_markup(body: &str) -> String {
+    let mut text = String::with_capacity(body.len());
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_is_removed() {
+        assert_eq!(
+            strip_markup("<b>Build</b> passed &amp; <a href=\"x\">deployed</a> &lt;3"),
+            "Build passed & deployed <3"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn watch_notify_calls() {
+        use std::{
+            io::{BufRead, BufReader},
+            process::{Command, Stdio},
+            sync::mpsc,
+            time::Duration,
+        };
+
+        // a dbus-daemon of our own, skipped when it is not installed
+        let Ok(mut daemon) = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+        else {
+            return;
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let connect = || {
+            zbus::blocking::connection::Builder::address(address.trim())
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let monitor = connect();
+        let watched = monitor.clone();
+        let watcher = thread::spawn(move || {
+            let _ = watch(&watched, |notification| sender.send(notification).is_ok());
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        // nobody serves the notifications, the call is still seen on its way
+        let app = connect();
+        let hints =
+            std::collections::HashMap::from([("urgency", zbus::zvariant::Value::from(2u8))]);
+        let _ = app.call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "Mail",
+                0u32,
+                "",
+                "New message",
+                "<i>Hi</i> there",
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        );
+
+        let notification = receiver.recv_timeout(Duration::from_secs(5));
+        // closing the connection ends the watch without waiting for another notification
+        close(monitor);
+        for _ in 0..50 {
+            if watcher.is_finished() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let stopped = watcher.is_finished();
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        assert!(stopped);
+        assert_eq!(
+            notification.unwrap(),
+            DesktopNotification {
+                app: "Mail".to_string(),
+                summary: "New message".to_string(),
+                body: "Hi there".to_string(),
+                urgency: Some(2),
+            }
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Where the server keeps its files on disk.
+
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::logger::Loggable;
+
+/// Name of the directory holding the server files
+const APP_DIR: &str = "laze";
+
+/// Environment variable replacing the config directory, e.g. for a system-wide daemon
+pub const CONFIG_DIR_ENV_VAR: &str = "LAZE_CONFIG_DIR";
+
+/// Directory of the configuration files, `$LAZE_CONFIG_DIR` if set, otherwise:
+/// - Linux: `$XDG_CONFIG_HOME/laze`, or `~/.config/laze`
+/// - macOS: `~/Library/Application Support/laze`
+/// - Windows: `%APPDATA%\laze`
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os(CONFIG_DIR_ENV_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| base_config_dir().map(|base| base.join(APP_DIR)))
+}
+
+/// Path of a configuration file, e.g. `config_file("gestures.json")`
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(name))
+}
+
+/// Opens the configuration file `name` with `open`, whether the file exists or not.
+/// `None` when there is no config directory, or when `open` failed, which is logged.
+pub fn load_config<T>(name: &str, open: impl FnOnce(&Path) -> Result<T, String>) -> Option<T> {
+    let path = config_file(name)?;
+    open(&path)
+        .inspect_err(|e| T::static_log_warn(&format!("Ignoring {}: {}", path.display(), e)))
+        .ok()
+}
+
+/// Reads a JSON file, `None` if it doesn't exist
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Writes the value as indented JSON, creating the directory of the file if needed
+pub fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let content = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
+
+/// Where the running daemon listens for `lazectl`: `$XDG_RUNTIME_DIR/laze/control.sock`,
+/// or a directory of the user in the temp directory
+#[cfg(unix)]
+pub fn control_socket() -> PathBuf {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join(APP_DIR))
+        .unwrap_or_else(|| {
+            let user = env::var("USER").unwrap_or_else(|_| "default".to_string());
+            env::temp_dir().join(format!("{}-{}", APP_DIR, user))
+        });
+    runtime_dir.join("control.sock")
+}
+
+/// Where files received from the phones go by default: `$XDG_DOWNLOAD_DIR`,
+/// or `Downloads` in the home directory
+pub fn default_download_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DOWNLOAD_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join("Downloads"))
+        })
+}
+
+#[cfg(target_os = "windows")]
+fn base_config_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn base_config_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn base_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join("numbers.json");
+        assert_eq!(load_json::<Vec<u32>>(&path), Ok(None));
+
+        save_json(&path, &[1, 2, 3]).unwrap();
+        assert_eq!(load_json(&path), Ok(Some(vec![1u32, 2, 3])));
+
+        fs::write(&path, "[1, 2,").unwrap();
+        assert!(load_json::<Vec<u32>>(&path).is_err());
+    }
+}
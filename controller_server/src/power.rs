@@ -0,0 +1,165 @@
+//! Power management: lock screen, suspend, hibernate, reboot, log out and (scheduled) shutdown.
+//!
+//! On Linux everything goes through logind over the system bus, so the usual polkit
+//! rules apply and failures can be reported back to the mobile client.
+//! Other OSes use their stock command line tools, spawned with an argument vector.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::actions::PowerCommand;
+
+/// Executes a power command. Returns an error describing why it was refused.
+pub fn execute(command: &PowerCommand) -> Result<(), String> {
+    platform::execute(command)
+}
+
+/// Absolute `CLOCK_REALTIME` time, in microseconds, at which a shutdown scheduled
+/// `minutes` from `now` happens. This is the unit logind's `ScheduleShutdown` expects.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn shutdown_deadline_usec(now: SystemTime, minutes: u16) -> u64 {
+    let deadline = now + Duration::from_secs(minutes as u64 * 60);
+    deadline
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::time::SystemTime;
+
+    use zbus::blocking::Connection;
+
+    use super::shutdown_deadline_usec;
+    use crate::actions::PowerCommand;
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.login1.Manager",
+        default_service = "org.freedesktop.login1",
+        default_path = "/org/freedesktop/login1",
+        gen_async = false
+    )]
+    trait LogindManager {
+        fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+        fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+        fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+        fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+        fn schedule_shutdown(&self, kind: &str, usec: u64) -> zbus::Result<()>;
+        fn cancel_scheduled_shutdown(&self) -> zbus::Result<bool>;
+    }
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.login1.Session",
+        default_service = "org.freedesktop.login1",
+        default_path = "/org/freedesktop/login1/session/auto",
+        gen_async = false
+    )]
+    trait LogindSession {
+        fn lock(&self) -> zbus::Result<()>;
+        fn terminate(&self) -> zbus::Result<()>;
+    }
+
+    pub fn execute(command: &PowerCommand) -> Result<(), String> {
+        let connection = Connection::system()
+            .map_err(|e| format!("Failed to connect to the system bus: {}", e))?;
+        let manager = || {
+            LogindManagerProxy::new(&connection)
+                .map_err(|e| format!("Failed to reach logind: {}", e))
+        };
+        let session = || {
+            LogindSessionProxy::new(&connection)
+                .map_err(|e| format!("Failed to reach the logind session: {}", e))
+        };
+
+        // never prompt for authentication - nobody is in front of the screen to answer
+        let interactive = false;
+        let result = match command {
+            PowerCommand::LockScreen => session()?.lock(),
+            PowerCommand::LogOut => session()?.terminate(),
+            PowerCommand::Suspend => manager()?.suspend(interactive),
+            PowerCommand::Hibernate => manager()?.hibernate(interactive),
+            PowerCommand::Reboot => manager()?.reboot(interactive),
+            PowerCommand::Shutdown => manager()?.power_off(interactive),
+            PowerCommand::ScheduleShutdown(minutes) => manager()?.schedule_shutdown(
+                "poweroff",
+                shutdown_deadline_usec(SystemTime::now(), *minutes),
+            ),
+            PowerCommand::CancelScheduledShutdown => {
+                return match manager()?.cancel_scheduled_shutdown() {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err("No shutdown was scheduled".to_string()),
+                    Err(e) => Err(format!("logind refused to cancel the shutdown: {}", e)),
+                };
+            }
+        };
+        result.map_err(|e| format!("logind refused {:?}: {}", command, e))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::process::Command;
+
+    use crate::actions::PowerCommand;
+
+    fn run(program: &str, args: &[&str]) -> Result<(), String> {
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("{} exited with {}", program, status))
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn execute(command: &PowerCommand) -> Result<(), String> {
+        match command {
+            PowerCommand::LockScreen => run("rundll32.exe", &["user32.dll,LockWorkStation"]),
+            PowerCommand::LogOut => run("shutdown", &["/l"]),
+            PowerCommand::Suspend => {
+                run("rundll32.exe", &["powrprof.dll,SetSuspendState", "0,1,0"])
+            }
+            PowerCommand::Hibernate => run("shutdown", &["/h"]),
+            PowerCommand::Reboot => run("shutdown", &["/r", "/t", "0"]),
+            PowerCommand::Shutdown => run("shutdown", &["/s", "/f", "/t", "0"]),
+            PowerCommand::ScheduleShutdown(minutes) => {
+                let seconds = (*minutes as u32 * 60).to_string();
+                run("shutdown", &["/s", "/t", &seconds])
+            }
+            PowerCommand::CancelScheduledShutdown => run("shutdown", &["/a"]),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn execute(command: &PowerCommand) -> Result<(), String> {
+        match command {
+            PowerCommand::LockScreen => run("pmset", &["displaysleepnow"]),
+            PowerCommand::LogOut => run(
+                "osascript",
+                &["-e", "tell application \"System Events\" to log out"],
+            ),
+            PowerCommand::Suspend | PowerCommand::Hibernate => run("pmset", &["sleepnow"]),
+            PowerCommand::Reboot => run("shutdown", &["-r", "now"]),
+            PowerCommand::Shutdown => run("shutdown", &["-h", "now"]),
+            PowerCommand::ScheduleShutdown(minutes) => {
+                run("shutdown", &["-h", &format!("+{}", minutes)])
+            }
+            PowerCommand::CancelScheduledShutdown => run("killall", &["shutdown"]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline_is_minutes_from_now_in_microseconds() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        assert_eq!(shutdown_deadline_usec(now, 0), 1_000_000_000);
+        assert_eq!(shutdown_deadline_usec(now, 30), 2_800_000_000);
+    }
+}
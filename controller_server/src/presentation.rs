@@ -0,0 +1,344 @@
+//! Presentation mode: slide control, a talk timer and a laser pointer.
+//!
+//! Slide commands are sent to the presentation app as key combos from a profile,
+//! since LibreOffice, PowerPoint in a browser and PDF viewers don't all agree on
+//! them. The built-in profiles are `libreoffice`, `browser` and `pdf`. They can be
+//! overridden, or new ones added, in `presentation.json` in the config directory:
+//!
+//! ```json
+//! {
+//!     "okular": {
+//!         "start": ["control", "shift", { "unicode": "p" }],
+//!         "stop": ["escape"],
+//!         "next": ["page_down"],
+//!         "previous": ["page_up"],
+//!         "first": ["home"],
+//!         "blank": []
+//!     }
+//! }
+//! ```
+
+use std::{collections::HashMap, path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{actions::PresentationCommand, input::InputKey, logger::Loggable, paths};
+
+pub const PRESENTATION_FILE: &str = "presentation.json";
+
+pub const DEFAULT_PROFILE: &str = "libreoffice";
+
+/// Phone rotation that sweeps the laser across the whole screen, in degrees
+const LASER_SWEEP_DEGREES: (f32, f32) = (40.0, 25.0);
+
+/// Cursor speed of the laser when there is no overlay to draw it
+const LASER_PIXELS_PER_DEGREE: f32 = 30.0;
+
+/// Keys a presentation app uses for each slide command. Empty to do nothing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresentationKeys {
+    pub start: Vec<InputKey>,
+    pub stop: Vec<InputKey>,
+    pub next: Vec<InputKey>,
+    pub previous: Vec<InputKey>,
+    pub first: Vec<InputKey>,
+    pub blank: Vec<InputKey>,
+}
+
+/// Key profiles by name
+pub struct PresentationProfiles {
+    profiles: HashMap<String, PresentationKeys>,
+}
+
+impl PresentationProfiles {
+    /// The built-in profiles, overridden by the user's `presentation.json` if there is one
+    pub fn load() -> Self {
+        paths::load_config(PRESENTATION_FILE, |path| {
+            let mut profiles = Self::defaults();
+            profiles.override_from(path).map(|_| profiles)
+        })
+        .unwrap_or_else(Self::defaults)
+    }
+
+    pub fn defaults() -> Self {
+        Self {
+            profiles: default_profiles().into_iter().collect(),
+        }
+    }
+
+    /// Replaces or adds the profiles listed in the file, if there is one
+    pub fn override_from(&mut self, path: &Path) -> Result<(), String> {
+        let overrides: Option<HashMap<String, PresentationKeys>> = paths::load_json(path)?;
+        self.profiles.extend(overrides.unwrap_or_default());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PresentationKeys> {
+        self.profiles.get(name)
+    }
+
+    /// Profile names, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+fn default_profiles() -> Vec<(String, PresentationKeys)> {
+    use InputKey::*;
+
+    let libreoffice = PresentationKeys {
+        start: vec![F5],
+        stop: vec![Escape],
+        next: vec![RightArrow],
+        previous: vec![LeftArrow],
+        first: vec![Home],
+        blank: vec![Unicode('b')],
+    };
+    // PowerPoint for the web and Google Slides; plain F5 would reload the page
+    let browser = PresentationKeys {
+        start: vec![Control, F5],
+        stop: vec![Escape],
+        next: vec![RightArrow],
+        previous: vec![LeftArrow],
+        first: vec![Home],
+        blank: vec![Unicode('b')],
+    };
+    // Evince, Atril and most viewers with a presentation mode
+    let pdf = PresentationKeys {
+        start: vec![F5],
+        stop: vec![Escape],
+        next: vec![PageDown],
+        previous: vec![PageUp],
+        first: vec![Home],
+        blank: vec![Unicode('b')],
+    };
+
+    vec![
+        ("libreoffice".to_string(), libreoffice),
+        ("browser".to_string(), browser),
+        ("pdf".to_string(), pdf),
+    ]
+}
+
+/// Draws the laser pointer over the desktop, e.g. in a transparent window of the
+/// desktop app. Without one, the laser moves the mouse cursor instead.
+pub trait LaserOverlay: Send + Sync {
+    /// Shows the pointer at a position given as fractions of the screen,
+    /// from (0, 0) at the top left to (1, 1) at the bottom right
+    fn show(&mut self, x: f32, y: f32) -> Result<(), String>;
+
+    fn hide(&mut self) -> Result<(), String>;
+}
+
+/// What the phone shows while presenting
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PresentationStatus {
+    pub profile: String,
+    /// Profiles the phone can switch to
+    pub profiles: Vec<String>,
+    pub running: bool,
+    /// Counted from the slide commands, so it drifts if the slides are changed
+    /// on the desktop itself
+    pub slide: u32,
+    /// Time since the slideshow started. The phone keeps counting from it.
+    pub elapsed_secs: u64,
+    pub blanked: bool,
+    pub laser: bool,
+}
+
+pub struct Presentation {
+    profiles: PresentationProfiles,
+    profile: String,
+    started: Option<Instant>,
+    slide: u32,
+    blanked: bool,
+    /// Laser position as fractions of the screen, `None` while it is hidden
+    laser: Option<(f32, f32)>,
+}
+
+impl Presentation {
+    pub fn new(profiles: PresentationProfiles) -> Self {
+        Self {
+            profiles,
+            profile: DEFAULT_PROFILE.to_string(),
+            started: None,
+            slide: 1,
+            blanked: false,
+            laser: None,
+        }
+    }
+
+    /// Tracks a slide command, returning the keys to send to the presentation app
+    pub fn handle(&mut self, command: &PresentationCommand) -> Option<Vec<InputKey>> {
+        let keys = self.profiles.get(&self.profile)?.clone();
+        let keys = match command {
+            PresentationCommand::Start => {
+                self.started = Some(Instant::now());
+                self.slide = 1;
+                self.blanked = false;
+                keys.start
+            }
+            PresentationCommand::Stop => {
+                self.started = None;
+                self.blanked = false;
+                keys.stop
+            }
+            PresentationCommand::Next => {
+                self.slide += 1;
+                keys.next
+            }
+            PresentationCommand::Previous => {
+                self.slide = self.slide.saturating_sub(1).max(1);
+                keys.previous
+            }
+            PresentationCommand::First => {
+                self.slide = 1;
+                keys.first
+            }
+            PresentationCommand::ToggleBlank => {
+                self.blanked = !self.blanked;
+                keys.blank
+            }
+            PresentationCommand::SelectProfile(profile) => {
+                if self.profiles.get(profile).is_some() {
+                    self.profile = profile.clone();
+                } else {
+                    self.log_warn(&format!("Unknown presentation profile '{}'", profile));
+                }
+                return None;
+            }
+            PresentationCommand::Laser { .. }
+            | PresentationCommand::LaserOff
+            | PresentationCommand::Status => return None,
+        };
+        Some(keys).filter(|keys| !keys.is_empty())
+    }
+
+    /// Moves the laser by a rotation in hundredths of a degree, starting from the
+    /// middle of the screen when it was hidden. Returns the new position.
+    pub fn move_laser(&mut self, dx: i16, dy: i16) -> (f32, f32) {
+        let (x, y) = self.laser.unwrap_or((0.5, 0.5));
+        let position = (
+            (x + dx as f32 / 100.0 / LASER_SWEEP_DEGREES.0).clamp(0.0, 1.0),
+            (y + dy as f32 / 100.0 / LASER_SWEEP_DEGREES.1).clamp(0.0, 1.0),
+        );
+        self.laser = Some(position);
+        position
+    }
+
+    /// Hides the laser, returning whether it was showing
+    pub fn hide_laser(&mut self) -> bool {
+        self.laser.take().is_some()
+    }
+
+    pub fn laser_visible(&self) -> bool {
+        self.laser.is_some()
+    }
+
+    pub fn status(&self) -> PresentationStatus {
+        PresentationStatus {
+            profile: self.profile.clone(),
+            profiles: self.profiles.names(),
+            running: self.started.is_some(),
+            slide: self.slide,
+            elapsed_secs: self
+                .started
+                .map_or(0, |started| started.elapsed().as_secs()),
+            blanked: self.blanked,
+            laser: self.laser.is_some(),
+        }
+    }
+}
+
+/// Cursor motion for a laser rotation in hundredths of a degree, used without an overlay
+pub fn laser_cursor_delta(dx: i16, dy: i16) -> (i32, i32) {
+    let pixels = |delta: i16| (delta as f32 / 100.0 * LASER_PIXELS_PER_DEGREE).round() as i32;
+    (pixels(dx), pixels(dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn counts_slides() {
+        let mut presentation = Presentation::new(PresentationProfiles::defaults());
+        assert_eq!(
+            presentation.handle(&PresentationCommand::Start),
+            Some(vec![InputKey::F5])
+        );
+        presentation.handle(&PresentationCommand::Next);
+        presentation.handle(&PresentationCommand::Next);
+        presentation.handle(&PresentationCommand::Previous);
+        assert_eq!(presentation.status().slide, 2);
+
+        presentation.handle(&PresentationCommand::First);
+        presentation.handle(&PresentationCommand::Previous);
+        let status = presentation.status();
+        assert_eq!(status.slide, 1);
+        assert!(status.running);
+
+        presentation.handle(&PresentationCommand::ToggleBlank);
+        assert!(presentation.status().blanked);
+        presentation.handle(&PresentationCommand::Stop);
+        assert!(!presentation.status().running);
+        assert!(!presentation.status().blanked);
+    }
+
+    #[test]
+    fn profiles_change_the_keys() {
+        let mut presentation = Presentation::new(PresentationProfiles::defaults());
+        presentation.handle(&PresentationCommand::SelectProfile("pdf".to_string()));
+        assert_eq!(
+            presentation.handle(&PresentationCommand::Next),
+            Some(vec![InputKey::PageDown])
+        );
+
+        presentation.handle(&PresentationCommand::SelectProfile("nope".to_string()));
+        assert_eq!(presentation.status().profile, "pdf");
+    }
+
+    #[test]
+    fn override_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PRESENTATION_FILE);
+        fs::write(
+            &path,
+            r#"{ "pdf": {
+                "start": [], "stop": ["escape"], "next": ["space"],
+                "previous": ["backspace"], "first": ["home"], "blank": []
+            } }"#,
+        )
+        .unwrap();
+
+        let mut profiles = PresentationProfiles::defaults();
+        profiles.override_from(&path).unwrap();
+
+        let mut presentation = Presentation::new(profiles);
+        presentation.handle(&PresentationCommand::SelectProfile("pdf".to_string()));
+        assert_eq!(
+            presentation.handle(&PresentationCommand::Next),
+            Some(vec![InputKey::Space])
+        );
+        assert_eq!(presentation.handle(&PresentationCommand::Start), None);
+        assert!(presentation.status().running);
+    }
+
+    #[test]
+    fn laser_starts_centered_and_stays_on_screen() {
+        let mut presentation = Presentation::new(PresentationProfiles::defaults());
+        assert_eq!(presentation.move_laser(1000, 0), (0.75, 0.5));
+        assert_eq!(presentation.move_laser(-10_000, 10_000), (0.0, 1.0));
+        assert!(presentation.hide_laser());
+        assert!(!presentation.hide_laser());
+        assert_eq!(presentation.move_laser(0, 0), (0.5, 0.5));
+    }
+
+    #[test]
+    fn cursor_fallback() {
+        assert_eq!(laser_cursor_delta(100, -50), (30, -15));
+    }
+}
@@ -0,0 +1,490 @@
+//! Low frame rate preview of the screen around the cursor, for when the desktop is
+//! out of sight.
+//!
+//! A client starts its stream with the `Start` preview command. At the requested rate
+//! the area of the monitor around the cursor is captured, scaled down and sent to the
+//! client as a JPEG or PNG tile, see `Notifier::send_screen_tile`. Tiles looking the
+//! same as the last one sent are skipped. Capturing needs X11, a virtual framebuffer
+//! such as Xvfb will do.
+
+#[cfg(target_os = "linux")]
+mod x11;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{input, logger::Loggable, server::notifier::Notifier};
+
+pub const MAX_FPS: u8 = 10;
+
+/// Largest area sent, in desktop pixels
+pub const MAX_TILE_SIZE: (u16, u16) = (1920, 1080);
+
+const MIN_TILE_SIZE: u16 = 16;
+
+/// Smallest scale, in percent of the desktop pixels
+const MIN_SCALE: u8 = 10;
+
+const JPEG_QUALITY: u8 = 70;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub enum PreviewFormat {
+    Jpeg,
+    Png,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub struct PreviewSettings {
+    /// Index of the monitor shown
+    pub monitor: u8,
+    pub format: PreviewFormat,
+    /// Tiles per second, at most `MAX_FPS`
+    pub fps: u8,
+    /// Size of the area shown around the cursor, in desktop pixels
+    pub width: u16,
+    pub height: u16,
+    /// Size of the tile sent, in percent of the area shown
+    pub scale: u8,
+}
+
+impl PreviewSettings {
+    /// The settings brought within the limits
+    pub fn clamped(self) -> Self {
+        Self {
+            fps: self.fps.clamp(1, MAX_FPS),
+            width: self.width.clamp(MIN_TILE_SIZE, MAX_TILE_SIZE.0),
+            height: self.height.clamp(MIN_TILE_SIZE, MAX_TILE_SIZE.1),
+            scale: self.scale.clamp(MIN_SCALE, 100),
+            ..self
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(1000 / self.fps.max(1) as u64)
+    }
+}
+
+/// Position and size of an area of the desktop, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Pixels captured from the screen, 3 bytes per pixel in RGB order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+/// Something the desktop can be captured from
+pub trait ScreenSource: Send {
+    /// Monitors in the order the clients pick them by
+    fn monitors(&mut self) -> Result<Vec<Rect>, String>;
+
+    /// Cursor position on the desktop
+    fn cursor(&mut self) -> Result<(i32, i32), String>;
+
+    fn capture(&mut self, area: Rect) -> Result<Frame, String>;
+}
+
+/// An encoded capture, sent to a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenTile {
+    pub format: PreviewFormat,
+    pub monitor: u8,
+    /// Area shown, relative to the top left corner of the monitor
+    pub area: Rect,
+    /// Cursor position relative to the top left corner of the tile, in desktop
+    /// pixels. May fall outside the tile when the cursor is on another monitor.
+    pub cursor: (i32, i32),
+    pub image: Vec<u8>,
+}
+
+/// Connects to the display of the current session, if it can be captured
+pub fn connect() -> Result<Box<dyn ScreenSource>, String> {
+    if input::needs_uinput(
+        env::var("DISPLAY").ok().as_deref(),
+        env::var("XDG_SESSION_TYPE").ok().as_deref(),
+    ) {
+        return Err("The screen can only be captured on X11".to_string());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(x11::X11Screen::connect()?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("The screen can only be captured on X11".to_string())
+    }
+}
+
+/// Captures the area around the cursor. Returns `None` when it looks the same as
+/// the capture fingerprinted in `last`, which is updated.
+pub fn capture_tile(
+    source: &mut dyn ScreenSource,
+    settings: &PreviewSettings,
+    last: &mut Option<u64>,
+) -> Result<Option<ScreenTile>, String> {
+    let monitor = *source
+        .monitors()?
+        .get(settings.monitor as usize)
+        .ok_or_else(|| format!("There is no monitor {}", settings.monitor))?;
+    let cursor = source.cursor()?;
+    let area = tile_around(cursor, monitor, settings.width, settings.height);
+    let frame = scale_down(source.capture(area)?, settings.scale);
+
+    let mut hasher = DefaultHasher::new();
+    (area, cursor, settings, &frame.rgb).hash(&mut hasher);
+    let fingerprint = hasher.finish();
+    if last.replace(fingerprint) == Some(fingerprint) {
+        return Ok(None);
+    }
+
+    Ok(Some(ScreenTile {
+        format: settings.format,
+        monitor: settings.monitor,
+        area: Rect {
+            x: area.x - monitor.x,
+            y: area.y - monitor.y,
+            ..area
+        },
+        cursor: (cursor.0 - area.x, cursor.1 - area.y),
+        image: encode(&frame, settings.format)?,
+    }))
+}
+
+/// The area of the monitor centered on the cursor, moved inside the monitor when the
+/// cursor is near its edges
+fn tile_around(cursor: (i32, i32), monitor: Rect, width: u16, height: u16) -> Rect {
+    let width = (width as u32).min(monitor.width);
+    let height = (height as u32).min(monitor.height);
+    let place = |cursor: i32, start: i32, length: u32, size: u32| {
+        (cursor - size as i32 / 2).clamp(start, start + (length - size) as i32)
+    };
+    Rect {
+        x: place(cursor.0, monitor.x, monitor.width, width),
+        y: place(cursor.1, monitor.y, monitor.height, height),
+        width,
+        height,
+    }
+}
+
+/// Shrinks the frame to `scale` percent of its size, averaging the pixels so text
+/// stays readable
+fn scale_down(frame: Frame, scale: u8) -> Frame {
+    if scale >= 100 {
+        return frame;
+    }
+    let width = (frame.width * scale as u32 / 100).max(1);
+    let height = (frame.height * scale as u32 / 100).max(1);
+    // source pixels covered by each scaled pixel, along one axis
+    let span = |i: u32, scaled: u32, original: u32| {
+        let start = i * original / scaled;
+        start..((i + 1) * original / scaled).max(start + 1)
+    };
+
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..height {
+        let rows = span(y, height, frame.height);
+        for x in 0..width {
+            let columns = span(x, width, frame.width);
+            let mut sum = [0u32; 3];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let i = ((row * frame.width + column) * 3) as usize;
+                    for (channel, value) in frame.rgb[i..i + 3].iter().enumerate() {
+                        sum[channel] += *value as u32;
+                    }
+                }
+            }
+            let count = rows.len() as u32 * columns.len() as u32;
+            rgb.extend(sum.map(|channel| (channel / count) as u8));
+        }
+    }
+    Frame { width, height, rgb }
+}
+
+#[cfg(target_os = "linux")]
+fn encode(frame: &Frame, format: PreviewFormat) -> Result<Vec<u8>, String> {
+    let mut image = Vec::new();
+    match format {
+        PreviewFormat::Jpeg => {
+            jpeg_encoder::Encoder::new(&mut image, JPEG_QUALITY)
+                .encode(
+                    &frame.rgb,
+                    frame.width as u16,
+                    frame.height as u16,
+                    jpeg_encoder::ColorType::Rgb,
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        PreviewFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut image, frame.width, frame.height);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_compression(png::Compression::Fast);
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&frame.rgb))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn encode(_frame: &Frame, _format: PreviewFormat) -> Result<Vec<u8>, String> {
+    Err("The screen can only be captured on X11".to_string())
+}
+
+/// Settings of the stream of each client
+pub type PreviewStreams = Arc<Mutex<HashMap<usize, PreviewSettings>>>;
+
+/// Sends the tiles to the clients with a stream. Stopped when dropped.
+pub struct ScreenPreview {
+    shutdown_signal: Arc<AtomicBool>,
+}
+
+impl ScreenPreview {
+    pub fn start(notifier: Notifier, streams: PreviewStreams) -> Self {
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::clone(&shutdown_signal);
+
+        thread::spawn(move || {
+            let mut source = match connect() {
+                Ok(source) => source,
+                Err(e) => {
+                    Self::static_log_warn(&format!("No screen preview: {}", e));
+                    return;
+                }
+            };
+            stream(source.as_mut(), &notifier, &streams, &shutdown);
+            Self::static_log_info("Screen preview has exited.");
+        });
+
+        Self { shutdown_signal }
+    }
+}
+
+impl Drop for ScreenPreview {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+    }
+}
+
+fn stream(
+    source: &mut dyn ScreenSource,
+    notifier: &Notifier,
+    streams: &PreviewStreams,
+    shutdown: &AtomicBool,
+) {
+    // when each client got its last tile, and that tile's fingerprint
+    let mut sent: HashMap<usize, (Instant, Option<u64>)> = HashMap::new();
+    let tick = Duration::from_millis(1000 / MAX_FPS as u64);
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        let due: Vec<(usize, PreviewSettings)> = {
+            let streams = streams.lock().unwrap();
+            sent.retain(|client_id, _| streams.contains_key(client_id));
+            streams
+                .iter()
+                .filter(|(client_id, settings)| {
+                    sent.get(client_id)
+                        .is_none_or(|(at, _)| now - *at >= settings.interval())
+                })
+                .map(|(client_id, settings)| (*client_id, *settings))
+                .collect()
+        };
+
+        for (client_id, settings) in due {
+            let (at, last) = sent.entry(client_id).or_insert((now, None));
+            *at = now;
+            match capture_tile(source, &settings, last) {
+                Ok(Some(tile)) => notifier.send_screen_tile(client_id, &tile),
+                Ok(None) => {}
+                Err(e) => {
+                    ScreenPreview::static_log_warn(&format!(
+                        "Stopping the screen preview of client {}: {}",
+                        client_id, e
+                    ));
+                    streams.lock().unwrap().remove(&client_id);
+                }
+            }
+        }
+
+        thread::sleep(tick.saturating_sub(now.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two 4x2 monitors side by side, the pixels numbered from the left
+    struct FakeScreen {
+        cursor: (i32, i32),
+    }
+
+    impl ScreenSource for FakeScreen {
+        fn monitors(&mut self) -> Result<Vec<Rect>, String> {
+            let monitor = |x| Rect {
+                x,
+                y: 0,
+                width: 4,
+                height: 2,
+            };
+            Ok(vec![monitor(0), monitor(4)])
+        }
+
+        fn cursor(&mut self) -> Result<(i32, i32), String> {
+            Ok(self.cursor)
+        }
+
+        fn capture(&mut self, area: Rect) -> Result<Frame, String> {
+            let mut rgb = Vec::new();
+            for y in area.y..area.y + area.height as i32 {
+                for x in area.x..area.x + area.width as i32 {
+                    rgb.extend([(y * 8 + x) as u8; 3]);
+                }
+            }
+            Ok(Frame {
+                width: area.width,
+                height: area.height,
+                rgb,
+            })
+        }
+    }
+
+    fn settings(monitor: u8, format: PreviewFormat) -> PreviewSettings {
+        PreviewSettings {
+            monitor,
+            format,
+            fps: 5,
+            width: 2,
+            height: 2,
+            scale: 100,
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let settings = PreviewSettings {
+            fps: 60,
+            width: 8000,
+            height: 0,
+            scale: 0,
+            ..settings(0, PreviewFormat::Png)
+        }
+        .clamped();
+        assert_eq!(settings.fps, MAX_FPS);
+        assert_eq!((settings.width, settings.height), (1920, MIN_TILE_SIZE));
+        assert_eq!(settings.scale, MIN_SCALE);
+        assert_eq!(settings.interval(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn tile_stays_on_the_monitor() {
+        let monitor = Rect {
+            x: 1920,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        };
+        let tile = |cursor| tile_around(cursor, monitor, 400, 300);
+        assert_eq!(tile((2500, 500)).x, 2300);
+        assert_eq!(tile((2500, 500)).y, 350);
+        assert_eq!((tile((1930, 10)).x, tile((1930, 10)).y), (1920, 0));
+        assert_eq!((tile((3199, 1023)).x, tile((3199, 1023)).y), (2800, 724));
+        // larger than the monitor
+        assert_eq!(
+            tile_around((0, 0), monitor, 2000, 2000),
+            Rect { x: 1920, ..monitor }
+        );
+    }
+
+    #[test]
+    fn scaling_averages_pixels() {
+        let frame = Frame {
+            width: 4,
+            height: 2,
+            rgb: [0u8, 10, 20, 30, 40, 50, 60, 70]
+                .iter()
+                .flat_map(|value| [*value; 3])
+                .collect(),
+        };
+        let scaled = scale_down(frame, 50);
+        assert_eq!((scaled.width, scaled.height), (2, 1));
+        assert_eq!(scaled.rgb, vec![25, 25, 25, 45, 45, 45]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tile_relative_to_the_monitor() {
+        let mut screen = FakeScreen { cursor: (7, 1) };
+        let mut last = None;
+
+        let tile = capture_tile(&mut screen, &settings(1, PreviewFormat::Png), &mut last)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            tile.area,
+            Rect {
+                x: 2,
+                y: 0,
+                width: 2,
+                height: 2
+            }
+        );
+        assert_eq!(tile.cursor, (1, 1));
+        assert!(tile.image.starts_with(b"\x89PNG"));
+
+        // nothing moved
+        assert_eq!(
+            capture_tile(&mut screen, &settings(1, PreviewFormat::Png), &mut last),
+            Ok(None)
+        );
+        screen.cursor = (5, 1);
+        let tile = capture_tile(&mut screen, &settings(1, PreviewFormat::Jpeg), &mut last)
+            .unwrap()
+            .unwrap();
+        assert!(tile.image.starts_with(&[0xff, 0xd8]));
+
+        assert!(capture_tile(&mut screen, &settings(2, PreviewFormat::Png), &mut last).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn png_tiles_decode() {
+        let frame = FakeScreen { cursor: (0, 0) }
+            .capture(Rect {
+                x: 0,
+                y: 0,
+                width: 3,
+                height: 2,
+            })
+            .unwrap();
+        let image = encode(&frame, PreviewFormat::Png).unwrap();
+
+        let mut reader = png::Decoder::new(image.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, frame.rgb);
+    }
+}
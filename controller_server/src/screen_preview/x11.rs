@@ -0,0 +1,185 @@
+//! Screen capture on X11, reading the root window.
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{ConnectionExt as _, ImageFormat, ImageOrder, Window},
+    rust_connection::RustConnection,
+};
+
+use super::{Frame, Rect, ScreenSource};
+use crate::monitors::x11::list_monitors;
+
+/// How the root window's pixels are laid out in the captured data
+struct PixelFormat {
+    bytes_per_pixel: usize,
+    /// Rows are padded to a multiple of this many bits
+    scanline_pad: usize,
+    /// Most significant byte first
+    big_endian: bool,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+}
+
+pub struct X11Screen {
+    connection: RustConnection,
+    root: Window,
+    format: PixelFormat,
+}
+
+impl X11Screen {
+    pub fn connect() -> Result<Self, String> {
+        let (connection, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let setup = connection.setup();
+        let screen = &setup.roots[screen];
+
+        let pixmap_format = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == screen.root_depth)
+            .ok_or("The root window has no pixmap format")?;
+        if !matches!(pixmap_format.bits_per_pixel, 24 | 32) {
+            return Err(format!(
+                "Can't capture {} bits per pixel",
+                pixmap_format.bits_per_pixel
+            ));
+        }
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.visual_id == screen.root_visual)
+            .ok_or("The root window has no visual")?;
+
+        let format = PixelFormat {
+            bytes_per_pixel: pixmap_format.bits_per_pixel as usize / 8,
+            scanline_pad: pixmap_format.scanline_pad as usize,
+            big_endian: setup.image_byte_order == ImageOrder::MSB_FIRST,
+            red_mask: visual.red_mask,
+            green_mask: visual.green_mask,
+            blue_mask: visual.blue_mask,
+        };
+        let root = screen.root;
+        Ok(Self {
+            connection,
+            root,
+            format,
+        })
+    }
+}
+
+impl ScreenSource for X11Screen {
+    fn monitors(&mut self) -> Result<Vec<Rect>, String> {
+        Ok(list_monitors(&self.connection, self.root)?
+            .iter()
+            .map(|monitor| Rect {
+                x: monitor.x,
+                y: monitor.y,
+                width: monitor.width,
+                height: monitor.height,
+            })
+            .collect())
+    }
+
+    fn cursor(&mut self) -> Result<(i32, i32), String> {
+        let pointer = self
+            .connection
+            .query_pointer(self.root)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        Ok((pointer.root_x as i32, pointer.root_y as i32))
+    }
+
+    fn capture(&mut self, area: Rect) -> Result<Frame, String> {
+        let image = self
+            .connection
+            .get_image(
+                ImageFormat::Z_PIXMAP,
+                self.root,
+                area.x as i16,
+                area.y as i16,
+                area.width as u16,
+                area.height as u16,
+                !0,
+            )
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        Ok(to_rgb(&image.data, area.width, area.height, &self.format))
+    }
+}
+
+fn to_rgb(data: &[u8], width: u32, height: u32, format: &PixelFormat) -> Frame {
+    let pad = format.scanline_pad.max(8);
+    let stride = (width as usize * format.bytes_per_pixel * 8).div_ceil(pad) * pad / 8;
+    let channel = |pixel: u32, mask: u32| ((pixel & mask) >> mask.trailing_zeros()) as u8;
+
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for row in data.chunks(stride).take(height as usize) {
+        for bytes in row
+            .chunks_exact(format.bytes_per_pixel)
+            .take(width as usize)
+        {
+            let pixel = if format.big_endian {
+                bytes
+                    .iter()
+                    .fold(0, |pixel, byte| pixel << 8 | *byte as u32)
+            } else {
+                bytes
+                    .iter()
+                    .rev()
+                    .fold(0, |pixel, byte| pixel << 8 | *byte as u32)
+            };
+            rgb.extend([
+                channel(pixel, format.red_mask),
+                channel(pixel, format.green_mask),
+                channel(pixel, format.blue_mask),
+            ]);
+        }
+    }
+    Frame { width, height, rgb }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen_preview::{capture_tile, PreviewFormat, PreviewSettings};
+
+    #[test]
+    fn bgrx_pixels() {
+        let format = PixelFormat {
+            bytes_per_pixel: 4,
+            scanline_pad: 32,
+            big_endian: false,
+            red_mask: 0xff0000,
+            green_mask: 0xff00,
+            blue_mask: 0xff,
+        };
+        //                 | blue green red x |
+        let data: &[u8] = &[3, 2, 1, 0, 30, 20, 10, 0];
+        let frame = to_rgb(data, 1, 2, &format);
+        assert_eq!(frame.rgb, vec![1, 2, 3, 10, 20, 30]);
+    }
+
+    /// Runs against the session's X server: `xvfb-run -a cargo test -- --ignored`
+    #[test]
+    #[ignore = "needs an X server"]
+    fn capture_around_the_cursor() {
+        let mut screen = X11Screen::connect().unwrap();
+        let settings = PreviewSettings {
+            monitor: 0,
+            format: PreviewFormat::Png,
+            fps: 1,
+            width: 64,
+            height: 48,
+            scale: 50,
+        };
+
+        let tile = capture_tile(&mut screen, &settings, &mut None)
+            .unwrap()
+            .unwrap();
+        assert_eq!((tile.area.width, tile.area.height), (64, 48));
+        assert!(tile.image.starts_with(b"\x89PNG"));
+    }
+}
@@ -1,3 +1,5 @@
+use super::{config::ServerConfig, notifier::Notifier};
+
 /// Represents the response from the application to the server.
 /// If the application parses the input and decides to close the server, then
 /// the server is closed.
@@ -18,6 +20,7 @@ pub trait Application: Sync + Send {
     ///
     /// # Arguments
     ///
+    /// * `client_id` - The id of the client that sent the input
     /// * `input` - The input received from the client encoded into a byte array
     ///
     /// # Returns
@@ -26,5 +29,17 @@ pub trait Application: Sync + Send {
     /// the connection. The return value is used to check for that command, and close the
     /// connection if such command is issued, or keep the connection alive otherwise.
     ///
-    fn dispatch_to_device(&mut self, input: &[u8]) -> ConnectionStatus;
+    fn dispatch_to_device(&mut self, client_id: usize, input: &[u8]) -> ConnectionStatus;
+
+    /// Invoked once a client is gone, whatever the reason.
+    /// Any per-client state kept by the application should be released here.
+    fn client_disconnected(&mut self, _client_id: usize) {}
+
+    /// Invoked once by the server before any client connects.
+    /// The `notifier` can be kept by the application to push data back to the mobile clients.
+    fn set_notifier(&mut self, _notifier: Notifier) {}
+
+    /// Invoked with the server config before any client connects, and again whenever
+    /// it is updated, e.g. to apply the permissions or the key bindings.
+    fn configure(&mut self, _config: &ServerConfig) {}
 }
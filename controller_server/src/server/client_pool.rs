@@ -1,14 +1,14 @@
 use std::{
     collections::HashMap,
     io::{Read, Write},
-    net::{SocketAddr, TcpStream},
+    net::{IpAddr, SocketAddr, TcpStream},
     sync::{
         atomic::AtomicBool,
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{logger::Loggable, server::core::ClientInfo};
@@ -17,15 +17,19 @@ use tokio::sync::broadcast;
 
 use super::{
     application::{Application, ConnectionStatus},
+    config::{Heartbeat, ServerConfig},
     core::ServerEvent,
+    notifier::Notifier,
     utils,
 };
 
 const CLIENT_POOL_RESERVED_ID: usize = 0;
-const DEFAULT_CLIENT_PORT: usize = 7878;
 
 const CLIENT_READ_TIMEOUT: Duration = Duration::from_secs(1);
 
+/// How long a write may wait for a client that stopped reading before it is dropped
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
 const ATOMIC_BOOL_ORDERING: std::sync::atomic::Ordering = std::sync::atomic::Ordering::SeqCst;
 
 /// Event codes sent from server to mobile client
@@ -46,6 +50,15 @@ pub struct ClientPool {
 
     max_concurrent_clients_allowed: usize,
 
+    /// Port the server listens on for new clients. Client `n` gets the port `n` above it.
+    starting_port: usize,
+
+    /// Address the client sockets are bound to, the local IP address when `None`
+    bind_address: Option<IpAddr>,
+
+    /// Keepalive probes of the client connections
+    heartbeat: Heartbeat,
+
     /// Store clients by id
     /// Must be inside a mutex - there could be conflicts between
     /// creating and removing clients since they run in different
@@ -66,12 +79,18 @@ pub struct ClientPool {
 }
 
 impl ClientPool {
-    pub fn new(max_clients: usize, event_publisher: broadcast::Sender<ServerEvent>) -> ClientPool {
+    pub fn new(
+        config: &ServerConfig,
+        event_publisher: broadcast::Sender<ServerEvent>,
+    ) -> ClientPool {
         let (sender, receiver) = channel();
         let pool = ClientPool {
             // IDs must start at 1, to differ from base port used to receive new client requests
             client_id_counter: 1,
-            max_concurrent_clients_allowed: max_clients,
+            max_concurrent_clients_allowed: config.max_clients,
+            starting_port: config.starting_port,
+            bind_address: config.bind_address,
+            heartbeat: config.heartbeat,
             clients: Arc::new(Mutex::new(HashMap::new())),
             client_termination_sender: sender,
             event_publisher,
@@ -82,6 +101,11 @@ impl ClientPool {
         pool
     }
 
+    /// Returns a handle that can push data to every client in this pool.
+    pub fn notifier(&self) -> Notifier {
+        Notifier::new(Arc::clone(&self.clients), self.event_publisher.clone())
+    }
+
     /// Starts a thread that listens for termination requests from clients.
     /// When a termination request is received, it removes the client from the pool.
     /// This is used to release resources from an already shut-down client thread.
@@ -107,11 +131,7 @@ impl ClientPool {
                     ));
 
                     if let Some(client) = clients.remove(&terminate.client_id) {
-                        let client_info = ClientInfo {
-                            id: client.id,
-                            addr: client.address.to_string(),
-                            device_name: client.device_name.lock().ok().and_then(|g| g.clone()),
-                        };
+                        let client_info = client.info();
 
                         // publish event about client removal
                         let _ = event_publisher
@@ -150,7 +170,7 @@ impl ClientPool {
     ) -> Result<usize, String> {
         let mut clients = self.clients.lock().unwrap();
 
-        if clients.len() == self.max_concurrent_clients_allowed {
+        if clients.len() >= self.max_concurrent_clients_allowed {
             return Err("Maximum number of concurrent clients reached!".to_string());
         }
 
@@ -158,6 +178,11 @@ impl ClientPool {
         let new_client = Client::launch_new_client(
             addr,
             self.client_id_counter,
+            (
+                self.bind_address,
+                self.starting_port + self.client_id_counter,
+            ),
+            self.heartbeat,
             app,
             self.client_termination_sender.clone(),
             self.event_publisher.clone(),
@@ -166,11 +191,7 @@ impl ClientPool {
 
         // publish event about new client
         self.event_publisher
-            .send(ServerEvent::ClientAdded(ClientInfo {
-                id: new_client.id,
-                addr: new_client.address.to_string(),
-                device_name: new_client.device_name.lock().ok().and_then(|g| g.clone()),
-            }))
+            .send(ServerEvent::ClientAdded(new_client.info()))
             .map_err(|e| {
                 ClientPool::static_log_warn(&format!(
                     "Failed to send client addition event: {}",
@@ -188,6 +209,37 @@ impl ClientPool {
         Ok(port)
     }
 
+    /// The connected clients, by id
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let clients = self.clients.lock().unwrap();
+        let mut list: Vec<ClientInfo> = clients.values().map(|client| client.info()).collect();
+        list.sort_by_key(|client| client.id);
+        list
+    }
+
+    pub fn get(&self, client_id: usize) -> Option<ClientInfo> {
+        let clients = self.clients.lock().unwrap();
+        clients.get(&client_id).map(|client| client.info())
+    }
+
+    pub fn max_clients(&self) -> usize {
+        self.max_concurrent_clients_allowed
+    }
+
+    /// Applies the config to the clients connecting from now on. The connected ones
+    /// keep their port, even past a lower `max_clients`, but get the new heartbeat.
+    pub fn configure(&mut self, config: &ServerConfig) {
+        self.max_concurrent_clients_allowed = config.max_clients;
+        self.starting_port = config.starting_port;
+        self.bind_address = config.bind_address;
+        if self.heartbeat != config.heartbeat {
+            self.heartbeat = config.heartbeat;
+            for client in self.clients.lock().unwrap().values() {
+                client.set_heartbeat(&self.heartbeat);
+            }
+        }
+    }
+
     /// Schedules client for termination and removes from pool.
     /// Emits ClientRemoved event so UI can update.
     pub fn terminate_client(&self, client_id: usize) -> Result<(), String> {
@@ -197,11 +249,7 @@ impl ClientPool {
             client.exit_requested.store(true, ATOMIC_BOOL_ORDERING);
 
             // Emit event so UI updates
-            let client_info = ClientInfo {
-                id: client.id,
-                addr: client.address.to_string(),
-                device_name: client.device_name.lock().ok().and_then(|g| g.clone()),
-            };
+            let client_info = client.info();
             let _ = self
                 .event_publisher
                 .send(ServerEvent::ClientRemoved(client_info.clone()))
@@ -260,7 +308,7 @@ impl ClientPool {
     }
 }
 
-struct Client {
+pub(super) struct Client {
     address: SocketAddr,
     id: usize,
     port: usize,
@@ -287,12 +335,13 @@ impl Client {
     fn launch_new_client<A: Application + 'static>(
         address: SocketAddr,
         id: usize,
+        (bind_address, port): (Option<IpAddr>, usize),
+        heartbeat: Heartbeat,
         app: Arc<Mutex<A>>,
         termination_sender: Sender<Terminate>,
         event_publisher: broadcast::Sender<ServerEvent>,
     ) -> Result<Arc<Client>, std::io::Error> {
-        let port = DEFAULT_CLIENT_PORT + id;
-        let socket = utils::create_socket(port)?;
+        let socket = utils::create_socket(bind_address, port)?;
 
         let client = Arc::new(Client {
             address,
@@ -311,11 +360,20 @@ impl Client {
             log::info!("Client created {:?} @ {:?}:{:?}", id, address, port);
 
             let exit_reason = match socket.accept() {
-                Ok((stream, _)) => client.handle_requests(stream, app),
+                Ok((stream, _)) => {
+                    if let Err(e) = utils::set_heartbeat(&stream, &heartbeat) {
+                        Self::static_log_warn(&format!(
+                            "Failed to set the heartbeat of client {}: {}",
+                            id, e
+                        ));
+                    }
+                    client.handle_requests(stream, Arc::clone(&app))
+                }
                 Err(e) => {
                     ExitReason::Unexpected(format!("Could not parse stream in Client {id}: {}", e))
                 }
             };
+            app.lock().unwrap().client_disconnected(id);
 
             match exit_reason {
                 ExitReason::RequestedByServer => {
@@ -369,6 +427,12 @@ impl Client {
                 ));
             }
         };
+        if let Err(e) = stream_clone.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)) {
+            Self::static_log_warn(&format!(
+                "Failed to set write timeout for client {}: {}",
+                self.id, e
+            ));
+        }
         *self.stream.lock().unwrap() = Some(stream_clone);
 
         // client timesout if no data available & checks for termination signal
@@ -390,6 +454,13 @@ impl Client {
             let mut bytes = [0; 1024];
 
             match stream.read(&mut bytes) {
+                // The client closed the connection without saying so
+                Ok(0) => {
+                    return ExitReason::Unexpected(format!(
+                        "Client {:?} listening at {:?} closed the connection",
+                        self.id, self.address
+                    ));
+                }
                 // Normal processing
                 Ok(bytes_size) => {
                     if bytes.is_empty() {
@@ -440,15 +511,12 @@ impl Client {
                     }
 
                     if let ConnectionStatus::Disconnected =
-                        app.lock().unwrap().dispatch_to_device(bytes)
+                        app.lock().unwrap().dispatch_to_device(self.id, bytes)
                     {
                         return ExitReason::RequestedByClient;
                     }
                 }
-                Err(ref e)
-                    if e.kind() == std::io::ErrorKind::WouldBlock
-                        || e.kind() == std::io::ErrorKind::TimedOut =>
-                {
+                Err(ref e) if is_read_timeout(e) => {
                     // No data available, timed out
 
                     // client was requested to terminate by the server
@@ -469,27 +537,153 @@ impl Client {
         }
     }
 
+    /// Applies the heartbeat to the connection, if the client has connected yet
+    fn set_heartbeat(&self, heartbeat: &Heartbeat) {
+        if let Some(stream) = self.stream.lock().unwrap().as_ref() {
+            if let Err(e) = utils::set_heartbeat(stream, heartbeat) {
+                Self::static_log_warn(&format!(
+                    "Failed to set the heartbeat of client {}: {}",
+                    self.id, e
+                ));
+            }
+        }
+    }
+
+    fn info(&self) -> ClientInfo {
+        ClientInfo {
+            id: self.id,
+            addr: self.address.to_string(),
+            device_name: self.device_name.lock().ok().and_then(|g| g.clone()),
+        }
+    }
+
     /// Send an event byte to the mobile client
     fn send_event(&self, event_code: u8) {
+        self.send_bytes(&[event_code]);
+    }
+
+    /// Send raw bytes to the mobile client.
+    /// Silently skipped if the client has not connected to its dedicated port yet.
+    pub(super) fn send_bytes(&self, bytes: &[u8]) {
         if let Some(stream) = self.stream.lock().unwrap().as_mut() {
-            if let Err(e) = stream.write_all(&[event_code]) {
+            if let Err(e) = write_within(stream, bytes, CLIENT_WRITE_TIMEOUT) {
                 Self::static_log_warn(&format!(
-                    "Failed to send event {} to client {}: {}",
-                    event_code, self.id, e
+                    "Failed to send event {} to client {}, disconnecting it: {}",
+                    bytes[0], self.id, e
                 ));
+                // part of the frame may have been sent, the stream can't be used anymore.
+                // The client thread sees the connection closed and releases the client.
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                return;
             }
             if let Err(e) = stream.flush() {
                 Self::static_log_warn(&format!(
                     "Failed to flush event {} to client {}: {}",
-                    event_code, self.id, e
+                    bytes[0], self.id, e
                 ));
             }
         }
     }
 }
 
+/// Writes all the bytes, failing if it takes longer than `timeout` in total. The write
+/// timeout of the socket alone would let a client reading a few bytes at a time stall
+/// every write for as long as it wants.
+fn write_within(
+    stream: &mut TcpStream,
+    mut bytes: &[u8],
+    timeout: Duration,
+) -> std::io::Result<()> {
+    let deadline = Instant::now() + timeout;
+    while !bytes.is_empty() {
+        if Instant::now() >= deadline {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        match stream.write(bytes) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(written) => bytes = &bytes[written..],
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Whether the read only ran into `CLIENT_READ_TIMEOUT`. Windows reports it as
+/// `TimedOut`, which elsewhere means the heartbeat got no answer.
+fn is_read_timeout(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::WouldBlock
+        || (cfg!(target_os = "windows") && e.kind() == std::io::ErrorKind::TimedOut)
+}
+
 enum ExitReason {
     RequestedByServer,
     RequestedByClient,
     Unexpected(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ignore;
+
+    impl Application for Ignore {
+        fn dispatch_to_device(&mut self, _: usize, _: &[u8]) -> ConnectionStatus {
+            ConnectionStatus::Connected
+        }
+    }
+
+    /// Polls the condition until it holds, failing after `timeout`
+    fn wait_until(timeout: Duration, condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + timeout;
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn a_client_that_never_reads_is_dropped() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let (event_pub, _events) = broadcast::channel(10);
+        let config = ServerConfig::new(47841, 2).with_bind_address(localhost);
+        let mut pool = ClientPool::new(&config, event_pub);
+        let port = pool
+            .add(
+                SocketAddr::new(localhost, 50312),
+                Arc::new(Mutex::new(Ignore)),
+            )
+            .unwrap();
+
+        // connects, then never reads
+        let _phone = TcpStream::connect((localhost, port as u16)).unwrap();
+        let client = pool
+            .clients
+            .lock()
+            .unwrap()
+            .values()
+            .next()
+            .cloned()
+            .unwrap();
+        wait_until(Duration::from_secs(2), || {
+            client.stream.lock().unwrap().is_some()
+        });
+        drop(client);
+
+        let notifier = pool.notifier();
+        let sender = thread::spawn(move || notifier.broadcast(&"x".repeat(64 << 20)));
+
+        // the pool stays usable while the write is stuck
+        thread::sleep(Duration::from_millis(200));
+        let (listed, list) = channel();
+        let clients = Arc::clone(&pool.clients);
+        thread::spawn(move || listed.send(clients.lock().unwrap().len()).unwrap());
+        assert_eq!(list.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+
+        // and the client is dropped once the write times out
+        wait_until(CLIENT_WRITE_TIMEOUT * 3, || sender.is_finished());
+        wait_until(Duration::from_secs(5), || pool.list().is_empty());
+        pool.shutdown();
+    }
+}
@@ -1,19 +1,17 @@
 use std::sync::mpsc::RecvTimeoutError;
 use std::{
     error::Error,
-    sync::{
-        atomic::AtomicBool,
-        mpsc::{Receiver, Sender},
-        Arc, RwLock,
-    },
+    sync::{atomic::AtomicBool, mpsc::Receiver, Arc, RwLock},
     thread,
     time::Duration,
 };
 
+use super::command_sender::PendingRequest;
 use super::commands::{
-    ClientTerminated, ServerRequest, ServerResponse, ServerStarted, ServerStopped,
-    ServerTerminated, VariantOf,
+    ClientDetails, ClientList, ClientTerminated, ConfigUpdated, ServerRequest, ServerResponse,
+    ServerStarted, ServerStatus, ServerStopped, ServerTerminated, VariantOf,
 };
+use super::config::ServerConfig;
 use crate::logger::Loggable;
 
 #[derive(Debug)]
@@ -35,8 +33,10 @@ impl std::fmt::Display for ProcessError {
 
 impl Error for ProcessError {}
 
+type ProcessorCallback<Req, Resp> = Box<dyn Fn(Req) -> Result<Resp, ProcessError> + Send + Sync>;
+
 struct CommandProcessor<Req, Resp> {
-    callback: RwLock<Option<Box<dyn Fn(Req) -> Result<Resp, ProcessError> + Send + Sync>>>,
+    callback: RwLock<Option<ProcessorCallback<Req, Resp>>>,
 }
 
 /// Holds a processor callback function that can process commands of type `Req` and return a response of type `Resp`.
@@ -99,30 +99,29 @@ impl CommandListenerHandler {
 
 /// A command listener that processes server requests in a separate thread.
 ///
-/// The `CommandListener` receives `ServerRequest` messages through a channel receiver
-/// and sends `ServerResponse` messages back through a channel sender. It uses a
+/// The `CommandListener` receives `PendingRequest`s through a channel receiver and sends
+/// each `ServerResponse` back on the request's own oneshot channel. It uses a
 /// configurable command processor to handle the actual request processing logic.
 ///
 /// # Usage
 ///
-/// 1. Create a new `CommandListener` with sender and receiver channels
+/// 1. Create a new `CommandListener` with the receiver of a `CommandSender`'s channel
 /// 2. Set a command processor using `set_command_processor()`
 /// 3. Start listening by calling `listen()` which spawns a new thread
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// let (tx, rx) = mpsc::channel();
-/// let (response_tx, response_rx) = mpsc::channel();
 ///
-/// let listener = CommandListener::new(response_tx, rx);
+/// let listener = CommandListener::new(rx);
 /// listener.set_command_processor(|req| {
 ///     // Process the request and return a response
 ///     Ok(ServerResponse::ServerStarted(ServerStarted))
 /// });
 ///
 /// let handler = listener.listen(Duration::from_secs(1));
-/// // ... send requests through tx channel
+/// // ... send requests through CommandSender::new(tx)
 /// handler.schedule_shutdown();
 /// handler.wait_for_exit();
 /// ```
@@ -132,6 +131,10 @@ impl CommandListenerHandler {
 /// - `InitServer`: Initializes the server and returns `ServerStarted` response
 /// - `TerminateServer`: Terminates the server and returns `ServerTerminated` response  
 /// - `TerminateClient(client_id)`: Terminates a specific client and returns `ClientTerminated` response
+/// - `ListClients`, `GetStatus`, `GetClient(client_id)`: Return `ClientList`, `ServerStatus`
+///   and `ClientDetails` without changing anything
+/// - `GetConfig`: Returns the `ServerConfig` in use
+/// - `UpdateConfig(config)`: Applies the config and returns `ConfigUpdated`
 ///
 /// # Error Handling
 ///
@@ -144,8 +147,7 @@ impl CommandListenerHandler {
 /// between threads. The listener waits for a processor to be set before beginning
 /// request processing.
 pub struct CommandListener {
-    sender: Sender<ServerResponse>,
-    receiver: Receiver<ServerRequest>,
+    receiver: Receiver<PendingRequest>,
 
     /// This is an Arc to allow some other thread to set the command processor callback.
     /// when calling the `listen` method, the command listener thread will wait for the processor to be set.
@@ -159,9 +161,8 @@ pub struct CommandListener {
 }
 
 impl CommandListener {
-    pub fn new(sender: Sender<ServerResponse>, receiver: Receiver<ServerRequest>) -> Self {
+    pub fn new(receiver: Receiver<PendingRequest>) -> Self {
         CommandListener {
-            sender,
             receiver,
             command_processor: Arc::new(CommandProcessor::new()),
             termination_signal: Arc::new(AtomicBool::new(false)),
@@ -177,7 +178,7 @@ impl CommandListener {
 
     /// Creates a new thread that listens for ServerController commands.
     /// This thread listens for commands received via the channel receiver,
-    /// and sends responses through the channel sender.
+    /// and answers each of them on its own channel.
     /// The server controller (tauri app) should connect to this port to send commands.
     /// After a command is processed, this thread sends a response back to the server controller.
     ///
@@ -197,9 +198,19 @@ impl CommandListener {
 
             loop {
                 match self.receiver.recv_timeout(Duration::from_millis(1000)) {
-                    Ok(message) => {
-                        self.log_debug(&format!("Received message: {:?}", message));
-                        self.parse_message(&message);
+                    Ok(pending) => {
+                        self.log_debug(&format!(
+                            "Received request {}: {:?}",
+                            pending.id, pending.request
+                        ));
+                        let id = pending.id;
+                        let response = self.parse_message(&pending.request);
+                        if !pending.respond(response) {
+                            self.log_warn(&format!(
+                                "Request {} was answered after its sender stopped waiting.",
+                                id
+                            ));
+                        }
                     }
                     Err(RecvTimeoutError::Timeout) => {
                         if self
@@ -221,12 +232,12 @@ impl CommandListener {
         });
 
         CommandListenerHandler {
-            thread_handle: thread_handle,
-            termination_signal: termination_signal,
+            thread_handle,
+            termination_signal,
         }
     }
 
-    fn parse_message(&self, message: &ServerRequest) {
+    fn parse_message(&self, message: &ServerRequest) -> ServerResponse {
         match message {
             ServerRequest::InitServer => {
                 self.log_info("Received InitServer request from ServerController. Processing...");
@@ -236,18 +247,17 @@ impl CommandListener {
                         let started = ServerStarted::assert_variant_of(resp);
                         let response = ServerResponse::ServerStarted(started);
 
-                        self.log_info(&format!("Received confirmation that server started"));
+                        self.log_info("Received confirmation that server started");
                         self.log_info("Sending response back to ServerController");
 
-                        self.sender.send(response).unwrap();
+                        response
                     }
                     Err(e) => {
                         let err_msg = format!("Failed to initialize server: {}", e);
                         self.log_error(&err_msg);
 
-                        // send error response back to the client
-                        let response = ServerResponse::Error(err_msg);
-                        self.sender.send(response).unwrap();
+                        // error response back to the client
+                        ServerResponse::Error(err_msg)
                     }
                 }
             }
@@ -264,15 +274,14 @@ impl CommandListener {
                         let terminated = ServerTerminated::assert_variant_of(resp);
                         let response = ServerResponse::ServerTerminated(terminated);
                         self.log_info("Received confirmation that server terminated.");
-                        self.sender.send(response).unwrap();
+                        response
                     }
                     Err(e) => {
                         let err_msg = format!("Failed to terminate server: {}", e);
                         self.log_error(&err_msg);
 
-                        // send error response back to the client
-                        let response = ServerResponse::Error(err_msg.to_string());
-                        self.sender.send(response).unwrap();
+                        // error response back to the client
+                        ServerResponse::Error(err_msg.to_string())
                     }
                 }
             }
@@ -290,15 +299,14 @@ impl CommandListener {
                             "Received confirmation that client {} terminated.",
                             client_id
                         ));
-                        self.sender.send(response).unwrap();
+                        response
                     }
                     Err(e) => {
                         let err_msg = format!("Failed to terminate client {}: {}", client_id, e);
                         self.log_error(&err_msg);
 
-                        // send error response back to the client
-                        let response = ServerResponse::Error(err_msg.to_string());
-                        self.sender.send(response).unwrap();
+                        // error response back to the client
+                        ServerResponse::Error(err_msg.to_string())
                     }
                 }
             }
@@ -310,14 +318,75 @@ impl CommandListener {
                         let stopped = ServerStopped::assert_variant_of(resp);
                         let response = ServerResponse::ServerStopped(stopped);
                         self.log_info("Received confirmation that server stopped.");
-                        self.sender.send(response).unwrap();
+                        response
                     }
                     Err(e) => {
                         let err_msg = format!("Failed to stop server: {}", e);
                         self.log_error(&err_msg);
 
-                        let response = ServerResponse::Error(err_msg);
-                        self.sender.send(response).unwrap();
+                        ServerResponse::Error(err_msg)
+                    }
+                }
+            }
+            ServerRequest::ListClients => {
+                self.log_debug("Received ListClients request from ServerController.");
+
+                match self.command_processor.process(ServerRequest::ListClients) {
+                    Ok(resp) => ServerResponse::ClientList(ClientList::assert_variant_of(resp)),
+                    Err(e) => ServerResponse::Error(format!("Failed to list clients: {}", e)),
+                }
+            }
+            ServerRequest::GetStatus => {
+                self.log_debug("Received GetStatus request from ServerController.");
+
+                match self.command_processor.process(ServerRequest::GetStatus) {
+                    Ok(resp) => ServerResponse::ServerStatus(ServerStatus::assert_variant_of(resp)),
+                    Err(e) => ServerResponse::Error(format!("Failed to get status: {}", e)),
+                }
+            }
+            ServerRequest::GetClient(client_id) => {
+                self.log_debug(&format!(
+                    "Received GetClient request for client {} from ServerController.",
+                    client_id
+                ));
+
+                match self
+                    .command_processor
+                    .process(ServerRequest::GetClient(*client_id))
+                {
+                    Ok(resp) => {
+                        ServerResponse::ClientDetails(ClientDetails::assert_variant_of(resp))
+                    }
+                    Err(e) => {
+                        ServerResponse::Error(format!("Failed to get client {}: {}", client_id, e))
+                    }
+                }
+            }
+            ServerRequest::GetConfig => {
+                self.log_debug("Received GetConfig request from ServerController.");
+
+                match self.command_processor.process(ServerRequest::GetConfig) {
+                    Ok(resp) => ServerResponse::ServerConfig(ServerConfig::assert_variant_of(resp)),
+                    Err(e) => ServerResponse::Error(format!("Failed to get config: {}", e)),
+                }
+            }
+            ServerRequest::UpdateConfig(config) => {
+                self.log_info("Received UpdateConfig request from ServerController. Processing...");
+
+                match self
+                    .command_processor
+                    .process(ServerRequest::UpdateConfig(config.clone()))
+                {
+                    Ok(resp) => {
+                        let updated = ConfigUpdated::assert_variant_of(resp);
+                        self.log_info(&format!("Applied the new config: {:?}", updated));
+                        ServerResponse::ConfigUpdated(updated)
+                    }
+                    Err(e) => {
+                        let err_msg = format!("Failed to update config: {}", e);
+                        self.log_error(&err_msg);
+
+                        ServerResponse::Error(err_msg)
                     }
                 }
             }
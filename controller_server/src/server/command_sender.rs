@@ -1,64 +1,204 @@
 use super::commands::{ServerRequest, ServerResponse};
-use std::sync::mpsc::{Receiver, Sender};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Sender,
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
 
-/// A communication interface for interacting with a server through message passing channels.
+/// How long a request waits for its response unless set otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Identifies a request in the logs and in the errors about it
+pub type RequestId = u64;
+
+/// A request on its way to the server, with the channel its response goes back on.
+#[derive(Debug)]
+pub struct PendingRequest {
+    pub id: RequestId,
+    pub request: ServerRequest,
+    responder: oneshot::Sender<ServerResponse>,
+}
+
+impl PendingRequest {
+    /// Sends the response back to whoever made the request.
+    /// Returns false if they stopped waiting for it, e.g. after a timeout.
+    pub fn respond(self, response: ServerResponse) -> bool {
+        self.responder.send(response).is_ok()
+    }
+}
+
+/// A communication interface for sending requests to a server through message passing
+/// channels.
 ///
-/// `CommandSender` provides a simple abstraction over message-passing channels to send
-/// requests to a server and receive responses back. It uses Rust's standard library MPSC
-/// (Multi-Producer, Single-Consumer) channels for thread-safe communication.
+/// Each request gets its own id and its own oneshot channel for the response, so
+/// concurrent requests can't receive each other's responses. A request that is not
+/// answered within the timeout, or that the server drops, fails instead of waiting
+/// forever.
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```ignore
 /// use std::sync::mpsc;
 ///
 /// let (req_sender, req_receiver) = mpsc::channel();
-/// let (resp_sender, resp_receiver) = mpsc::channel();
-///
-/// let mut communicator = CommandSender::new(req_sender, resp_receiver);
+/// let communicator = CommandSender::new(req_sender);
 ///
-/// // Send a request to initialize the server
-/// communicator.send_request(ServerRequest::InitServer);
-///
-/// // Receive the response
-/// match communicator.receive_response() {
-///     Ok(ServerResponse::ServerStarted(info)) => {
-///         println!("Server started at: {}", info.addr);
-///     }
+/// // From async code, e.g. a Tauri command
+/// match communicator.request_async(ServerRequest::InitServer).await {
+///     Ok(ServerResponse::ServerStarted(_)) => println!("Server started"),
 ///     Ok(response) => println!("Received: {:?}", response),
-///     Err(e) => eprintln!("Failed to receive response: {}", e),
+///     Err(e) => eprintln!("Request failed: {}", e),
 /// }
+///
+/// // From a plain thread
+/// let response = communicator.request(ServerRequest::GetStatus);
 /// ```
 ///
 /// # Thread Safety
 ///
-/// This struct is designed to be used in multi-threaded environments where one thread
-/// sends requests and waits for responses, while another thread (typically the server)
-/// processes requests and sends back responses through the corresponding channels.
+/// `CommandSender` is `Sync`: any number of threads or tasks can send requests through
+/// it at once, while another thread (typically the server's command listener) answers
+/// them.
 #[derive(Debug)]
 pub struct CommandSender {
-    sender_channel: Sender<super::commands::ServerRequest>,
-    receiver_channel: Receiver<ServerResponse>,
+    sender_channel: Sender<PendingRequest>,
+    next_id: AtomicU64,
+    timeout: Duration,
 }
 
 impl CommandSender {
-    pub fn new(sender: Sender<ServerRequest>, receiver: Receiver<ServerResponse>) -> Self {
+    pub fn new(sender: Sender<PendingRequest>) -> Self {
         CommandSender {
             sender_channel: sender,
-            receiver_channel: receiver,
+            next_id: AtomicU64::new(1),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
         }
     }
 
-    /// Sends a request to the server.
+    /// Fails the requests not answered within `timeout`
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sends a request to the server. Its response arrives on the returned receiver.
     pub fn send_request(
         &self,
         request: ServerRequest,
-    ) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.sender_channel.send(request)
+    ) -> Result<(RequestId, oneshot::Receiver<ServerResponse>), String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (responder, response) = oneshot::channel();
+        self.sender_channel
+            .send(PendingRequest {
+                id,
+                request,
+                responder,
+            })
+            .map_err(|e| {
+                format!(
+                    "Request {} ({:?}) not sent, the server has exited",
+                    id, e.0.request
+                )
+            })?;
+        Ok((id, response))
+    }
+
+    /// Sends a request and waits for its response, at most the timeout.
+    pub async fn request_async(&self, request: ServerRequest) -> Result<ServerResponse, String> {
+        let description = format!("{:?}", request);
+        let (id, response) = self.send_request(request)?;
+        match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(format!(
+                "Request {} ({}) was dropped by the server",
+                id, description
+            )),
+            Err(_) => Err(format!(
+                "Request {} ({}) timed out after {:?}",
+                id, description, self.timeout
+            )),
+        }
+    }
+
+    /// Blocking version of `request_async`, for threads outside of an async runtime.
+    pub fn request(&self, request: ServerRequest) -> Result<ServerResponse, String> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(|e| e.to_string())?
+            .block_on(self.request_async(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::commands::{ServerStarted, ServerStatus};
+    use std::{sync::mpsc, thread};
+
+    #[test]
+    fn responses_go_to_their_request() {
+        let (sender, requests) = mpsc::channel::<PendingRequest>();
+        let communicator = CommandSender::new(sender);
+
+        let (first, first_response) = communicator
+            .send_request(ServerRequest::InitServer)
+            .unwrap();
+        let (second, second_response) =
+            communicator.send_request(ServerRequest::GetStatus).unwrap();
+        assert_ne!(first, second);
+
+        // answered out of order
+        let first_request = requests.recv().unwrap();
+        let second_request = requests.recv().unwrap();
+        assert_eq!((first_request.id, second_request.id), (first, second));
+        assert!(second_request.respond(ServerResponse::Error("busy".to_string())));
+        assert!(first_request.respond(ServerResponse::ServerStarted(ServerStarted {})));
+
+        assert!(matches!(
+            first_response.blocking_recv().unwrap(),
+            ServerResponse::ServerStarted(_)
+        ));
+        assert!(matches!(
+            second_response.blocking_recv().unwrap(),
+            ServerResponse::Error(_)
+        ));
     }
 
-    /// Receives a response from the server.
-    pub fn receive_response(&mut self) -> Result<ServerResponse, std::sync::mpsc::RecvError> {
-        self.receiver_channel.recv()
+    #[test]
+    fn unanswered_requests_fail() {
+        let (sender, requests) = mpsc::channel::<PendingRequest>();
+        let mut communicator = CommandSender::new(sender);
+        communicator.set_timeout(Duration::from_millis(50));
+
+        // never answered
+        let error = communicator.request(ServerRequest::GetStatus).unwrap_err();
+        assert!(error.contains("timed out"), "{}", error);
+        let late = requests.recv().unwrap();
+        assert!(!late.respond(ServerResponse::ServerStatus(ServerStatus {
+            listening: false,
+            address: None,
+            port: 7878,
+            max_clients: 1,
+            clients: 0,
+            uptime_secs: 0,
+            discovery: false,
+        })));
+
+        // dropped, e.g. by a listener that failed
+        let dropper = thread::spawn(move || drop(requests.recv().unwrap()));
+        let error = communicator
+            .request(ServerRequest::ListClients)
+            .unwrap_err();
+        assert!(error.contains("dropped"), "{}", error);
+        dropper.join().unwrap();
+
+        // the listener is gone
+        let error = communicator
+            .request(ServerRequest::ListClients)
+            .unwrap_err();
+        assert!(error.contains("exited"), "{}", error);
     }
 }
@@ -1,5 +1,9 @@
+use std::net::SocketAddr;
+
 use serde::{Deserialize, Serialize};
 
+use super::{config::ServerConfig, core::ClientInfo};
+
 // ------------------ Requests ------------------- //
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -8,6 +12,12 @@ pub enum ServerRequest {
     StopServer,
     TerminateServer,
     TerminateClient(usize),
+    ListClients,
+    GetStatus,
+    GetClient(usize),
+    GetConfig,
+    /// Applies a new config to the running server
+    UpdateConfig(ServerConfig),
 }
 
 // ------------------ Responses ------------------ //
@@ -18,6 +28,11 @@ pub enum ServerResponse {
     ServerStopped(ServerStopped),
     ServerTerminated(ServerTerminated),
     ClientTerminated(ClientTerminated),
+    ClientList(ClientList),
+    ServerStatus(ServerStatus),
+    ClientDetails(ClientDetails),
+    ServerConfig(ServerConfig),
+    ConfigUpdated(ConfigUpdated),
     Error(String),
 }
 
@@ -35,12 +50,51 @@ pub struct ClientTerminated {
     pub client_id: usize,
 }
 
+/// The connected clients, by id
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientList {
+    pub clients: Vec<ClientInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    /// Whether new clients are accepted, false after `StopServer`
+    pub listening: bool,
+    /// Address new clients connect to, none if it could not be listened on
+    pub address: Option<SocketAddr>,
+    pub port: usize,
+    pub max_clients: usize,
+    pub clients: usize,
+    /// Seconds since the server started
+    pub uptime_secs: u64,
+    /// Whether the phones can find the server through UDP discovery
+    pub discovery: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientDetails {
+    pub client: ClientInfo,
+}
+
+/// Which listeners were restarted to apply the new config
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigUpdated {
+    /// The phones now connect to the new port or address
+    pub listener_restarted: bool,
+    pub discovery_restarted: bool,
+}
+
 // ------------------ VariantOf Trait ------------------ //
 
 pub trait VariantOf<T> {
     fn assert_variant_of(other: T) -> Self
     where
         Self: Sized;
+
+    /// The inner value if `other` is this variant, otherwise `other` back
+    fn try_variant_of(other: T) -> Result<Self, T>
+    where
+        Self: Sized;
 }
 
 /// A macro to implement the `VariantOf` trait for each variant of an enum.
@@ -58,6 +112,14 @@ macro_rules! impl_variant_of {
                         panic!("Expected variant {} but found {:?}", stringify!($variant), other);
                     }
                 }
+
+                fn try_variant_of(other: $enum_type) -> Result<Self, $enum_type> {
+                    if let $enum_type::$variant(inner) = other {
+                        Ok(inner)
+                    } else {
+                        Err(other)
+                    }
+                }
             }
         )*
     };
@@ -68,6 +130,11 @@ impl_variant_of!(ServerResponse => {
     ServerStopped,
     ServerTerminated,
     ClientTerminated,
+    ClientList,
+    ServerStatus,
+    ClientDetails,
+    ServerConfig,
+    ConfigUpdated,
 });
 
 #[cfg(test)]
@@ -80,10 +147,10 @@ mod tests {
         let serialized = serde_json::to_string(&request).unwrap();
         let deserialized: ServerRequest = serde_json::from_str(&serialized).unwrap();
 
-        match deserialized {
-            ServerRequest::InitServer => assert!(true),
-            _ => panic!("Expected InitServer"),
-        }
+        assert!(
+            matches!(deserialized, ServerRequest::InitServer),
+            "Expected InitServer"
+        );
     }
 
     #[test]
@@ -104,10 +171,10 @@ mod tests {
         let serialized = serde_json::to_string(&response).unwrap();
         let deserialized: ServerResponse = serde_json::from_str(&serialized).unwrap();
 
-        match deserialized {
-            ServerResponse::ServerStarted(_) => assert!(true),
-            _ => panic!("Expected ServerStarted"),
-        }
+        assert!(
+            matches!(deserialized, ServerResponse::ServerStarted(_)),
+            "Expected ServerStarted"
+        );
     }
 
     #[test]
@@ -122,6 +189,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_query_requests() {
+        let serialized = serde_json::to_string(&ServerRequest::GetClient(3)).unwrap();
+        assert_eq!(serialized, r#"{"GetClient":3}"#);
+        assert!(matches!(
+            serde_json::from_str(r#""ListClients""#).unwrap(),
+            ServerRequest::ListClients
+        ));
+
+        let response = ServerResponse::ServerStatus(ServerStatus {
+            listening: true,
+            address: Some(SocketAddr::from(([192, 168, 1, 20], 7878))),
+            port: 7878,
+            max_clients: 10,
+            clients: 1,
+            uptime_secs: 90,
+            discovery: false,
+        });
+        let serialized = serde_json::to_string(&response).unwrap();
+        let status = ServerStatus::assert_variant_of(serde_json::from_str(&serialized).unwrap());
+        assert_eq!(status.address.unwrap().to_string(), "192.168.1.20:7878");
+        assert_eq!(status.uptime_secs, 90);
+    }
+
     #[test]
     fn test_server_response_error() {
         let error_msg = "Test error message";
@@ -141,6 +232,13 @@ mod tests {
         let _extracted = ServerStarted::assert_variant_of(response);
     }
 
+    #[test]
+    fn test_try_variant_of() {
+        let response = ServerResponse::ServerStopped(ServerStopped {});
+        let response = ServerStarted::try_variant_of(response).unwrap_err();
+        assert!(ServerStopped::try_variant_of(response).is_ok());
+    }
+
     #[test]
     #[should_panic(expected = "Expected variant ServerStarted")]
     fn test_variant_of_wrong_variant() {
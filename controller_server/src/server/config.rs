@@ -0,0 +1,306 @@
+//! Server configuration, kept in `server.json` in the config directory:
+//!
+//! ```json
+//! {
+//!     "starting_port": 7878,
+//!     "bind_address": "192.168.1.20",
+//!     "max_clients": 4,
+//!     "discovery": false,
+//!     "permissions": { "power": false, "commands": false },
+//!     "notifications": { "blocked_apps": ["Spotify"] },
+//!     "heartbeat": { "interval_secs": 10, "missed": 3 },
+//!     "key_bindings": "keys.json"
+//! }
+//! ```
+//!
+//! Missing fields keep their defaults. A running server applies a new config sent
+//! with `ServerRequest::UpdateConfig`, only restarting the listeners whose address
+//! changed.
+
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+pub const SERVER_CONFIG_FILE: &str = "server.json";
+
+pub const DEFAULT_PORT: usize = 7878;
+
+pub const DEFAULT_MAX_CLIENTS: usize = 10;
+
+/// Represents the server configuration.
+///
+/// All new connections will start from the specified port
+/// increasing by 1 for each new connection until reaching
+/// the max_clients.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Port the phones connect to first. Phone `n` then gets the port `n` above it.
+    pub starting_port: usize,
+    /// Address the sockets are bound to. The local network IP address when `None`.
+    pub bind_address: Option<IpAddr>,
+    pub max_clients: usize,
+    /// Whether the phones can find the server through UDP discovery
+    pub discovery: bool,
+    /// What the phones are allowed to do on the desktop
+    pub permissions: Permissions,
+    /// Which desktop notifications are mirrored, when `permissions.notifications` allows it
+    pub notifications: NotificationFilter,
+    pub heartbeat: Heartbeat,
+    /// File replacing the key combos of the phone keys, relative to the config directory.
+    /// See `KeyBindings::override_from`.
+    pub key_bindings: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            starting_port: DEFAULT_PORT,
+            bind_address: None,
+            max_clients: DEFAULT_MAX_CLIENTS,
+            discovery: true,
+            permissions: Permissions::default(),
+            notifications: NotificationFilter::default(),
+            heartbeat: Heartbeat::default(),
+            key_bindings: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn new(starting_port: usize, max_clients: usize) -> Self {
+        Self {
+            starting_port,
+            max_clients,
+            ..Self::default()
+        }
+    }
+
+    /// Listens on the given address instead of the local network IP address
+    pub fn with_bind_address(mut self, address: IpAddr) -> Self {
+        self.bind_address = Some(address);
+        self
+    }
+
+    /// The user's `server.json`, or the defaults if there is none or it is invalid
+    pub fn load() -> Self {
+        paths::load_config(SERVER_CONFIG_FILE, Self::open)
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        Self::open(path)?.ok_or_else(|| "No such file".to_string())
+    }
+
+    /// The valid config in the file, `None` if there is no file
+    fn open(path: &Path) -> Result<Option<Self>, String> {
+        let config: Option<Self> = paths::load_json(path)?;
+        if let Some(config) = &config {
+            config.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// Saves the config as the user's `server.json`
+    pub fn save(&self) -> Result<(), String> {
+        let path = paths::config_file(SERVER_CONFIG_FILE)
+            .ok_or("No config directory to save the server config in")?;
+        self.save_to(&path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        self.validate()?;
+        paths::save_json(path, self)
+    }
+
+    /// Checks the phones can be given a port each, and the heartbeat can be sent
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_clients == 0 {
+            return Err("At least one phone must be allowed to connect".to_string());
+        }
+        let last_port = self.starting_port.checked_add(self.max_clients);
+        if self.starting_port == 0 || last_port.is_none_or(|last| last > u16::MAX as usize) {
+            return Err(format!(
+                "Ports {} to {} are not all valid",
+                self.starting_port,
+                self.starting_port.saturating_add(self.max_clients)
+            ));
+        }
+        if self.heartbeat.enabled
+            && (self.heartbeat.interval_secs == 0 || self.heartbeat.missed == 0)
+        {
+            return Err("The heartbeat needs an interval and a number of missed beats".to_string());
+        }
+        Ok(())
+    }
+
+    /// Where the key bindings file is, if there is one
+    pub fn key_bindings_file(&self) -> Option<PathBuf> {
+        let file = self.key_bindings.as_ref()?;
+        if file.is_absolute() {
+            Some(file.clone())
+        } else {
+            paths::config_dir().map(|dir| dir.join(file))
+        }
+    }
+}
+
+/// Kinds of actions the phones may perform. All are allowed by default.
+///
+/// Disconnecting, and asking for the desktop state, macros, shortcuts or apps, are
+/// always allowed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Permissions {
+    /// Keyboard, mouse, gamepad, pen, gestures, presentations, windows and monitors
+    pub input: bool,
+    /// Volume, brightness and media playback
+    pub media: bool,
+    /// Lock, sleep, restart and shut down the desktop
+    pub power: bool,
+    /// Run shell commands, macros and shortcuts, launch apps and open links
+    pub commands: bool,
+    /// Send and receive files
+    pub files: bool,
+    pub screen_preview: bool,
+    /// Mirror the desktop notifications
+    pub notifications: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            input: true,
+            media: true,
+            power: true,
+            commands: true,
+            files: true,
+            screen_preview: true,
+            notifications: true,
+        }
+    }
+}
+
+/// Apps whose notifications are mirrored to the phones. App names are compared without case.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationFilter {
+    pub blocked_apps: Vec<String>,
+    /// When set, the only apps mirrored
+    pub allowed_apps: Option<Vec<String>>,
+}
+
+impl NotificationFilter {
+    /// Whether notifications of this app are mirrored
+    pub fn allows(&self, app: &str) -> bool {
+        let listed = |apps: &[String]| apps.iter().any(|listed| listed.eq_ignore_ascii_case(app));
+        !listed(&self.blocked_apps) && self.allowed_apps.as_deref().is_none_or(listed)
+    }
+}
+
+/// TCP keepalive probes sent to the phones that stay silent, so the ones gone without
+/// disconnecting (out of Wi-Fi range, battery dead) are dropped. The phones don't have
+/// to do anything, their network stack answers the probes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Heartbeat {
+    pub enabled: bool,
+    /// Seconds of silence before the first probe, and between the probes
+    pub interval_secs: u64,
+    /// Unanswered probes after which the phone is dropped
+    pub missed: u32,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 5,
+            missed: 3,
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let config: ServerConfig = serde_json::from_str(
+            r#"{
+                "max_clients": 2,
+                "permissions": { "power": false },
+                "notifications": { "blocked_apps": ["Slack"] },
+                "key_bindings": "keys.json"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.starting_port, DEFAULT_PORT);
+        assert_eq!(config.max_clients, 2);
+        assert!(config.discovery);
+        assert!(!config.permissions.power && config.permissions.input);
+        assert_eq!(config.notifications.blocked_apps, ["Slack"]);
+        assert_eq!(config.notifications.allowed_apps, None);
+        assert_eq!(config.heartbeat, Heartbeat::default());
+        assert_eq!(config.key_bindings, Some(PathBuf::from("keys.json")));
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join(SERVER_CONFIG_FILE);
+
+        let mut config = ServerConfig::new(9000, 3).with_bind_address([127, 0, 0, 1].into());
+        config.discovery = false;
+        config.heartbeat.interval_secs = 30;
+        config.save_to(&path).unwrap();
+        assert_eq!(ServerConfig::from_file(&path).unwrap(), config);
+
+        config.max_clients = 0;
+        assert!(config.save_to(&path).is_err());
+    }
+
+    #[test]
+    fn notification_filter() {
+        let mut filter = NotificationFilter {
+            blocked_apps: vec!["Spotify".to_string()],
+            ..NotificationFilter::default()
+        };
+        assert!(filter.allows("Firefox"));
+        assert!(!filter.allows("spotify"));
+
+        filter.allowed_apps = Some(vec!["Thunderbird".to_string()]);
+        assert!(filter.allows("thunderbird"));
+        assert!(!filter.allows("Firefox"));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(ServerConfig::default().validate().is_ok());
+        assert!(ServerConfig::new(7878, 0).validate().is_err());
+        assert!(ServerConfig::new(0, 1).validate().is_err());
+        assert!(ServerConfig::new(65530, 10).validate().is_err());
+        assert!(ServerConfig::new(usize::MAX, 4).validate().is_err());
+        assert!(ServerConfig::new(1, usize::MAX).validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.heartbeat.interval_secs = 0;
+        assert!(config.validate().is_err());
+        config.heartbeat.enabled = false;
+        assert!(config.validate().is_ok());
+    }
+}
@@ -0,0 +1,415 @@
+//! Control socket of a running server.
+//!
+//! Scripts and `lazectl` talk to the server over a Unix domain socket, one JSON
+//! value per line. Each line sent is a [`ControlRequest`], answered by one
+//! [`ControlReply`] line:
+//!
+//! ```text
+//! > {"Server":"StopServer"}
+//! < {"Response":{"ServerStopped":{}}}
+//! > {"Server":{"TerminateClient":2}}
+//! < {"Response":{"ClientTerminated":{"client_id":2}}}
+//! > "Subscribe"
+//! < {"Event":{"ClientAdded":{"id":0,"addr":"192.168.1.20:50312","device_name":null}}}
+//! ```
+//!
+//! After `Subscribe`, the connection only streams the server events. The socket is
+//! only accessible by the user running the server, and is only created in a directory
+//! of theirs nobody else can enter.
+
+use std::{
+    fs::{self, DirBuilder, Permissions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::logger::Loggable;
+
+use super::{
+    commands::{ServerRequest, ServerResponse},
+    core::{ServerEvent, ServerHandler},
+};
+
+/// How often the listener checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ControlRequest {
+    /// Checks the server is there, answered with `Pong`
+    Ping,
+    /// Forwarded to the server, answered with its `Response`
+    Server(ServerRequest),
+    /// Turns the connection into a stream of `Event`s
+    Subscribe,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ControlReply {
+    Pong { version: String },
+    Response(ServerResponse),
+    Event(ServerEvent),
+}
+
+/// Listens on the control socket until dropped, then removes it
+pub struct ControlSocket {
+    path: PathBuf,
+    shutdown_signal: Arc<AtomicBool>,
+}
+
+impl ControlSocket {
+    /// Listens at `path`, forwarding the requests to the server behind `handler`.
+    /// `on_terminated` is called once a client has terminated the server.
+    pub fn bind(
+        path: &Path,
+        handler: Arc<ServerHandler>,
+        on_terminated: impl Fn() + Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        let listener = listen(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        thread::spawn({
+            let shutdown_signal = Arc::clone(&shutdown_signal);
+            move || accept(listener, handler, Arc::new(on_terminated), &shutdown_signal)
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            shutdown_signal,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Serves each connection on its own thread until `shutdown_signal` is set
+fn accept(
+    listener: UnixListener,
+    handler: Arc<ServerHandler>,
+    on_terminated: Arc<dyn Fn() + Send + Sync>,
+    shutdown_signal: &AtomicBool,
+) {
+    while !shutdown_signal.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let handler = Arc::clone(&handler);
+                let on_terminated = Arc::clone(&on_terminated);
+                thread::spawn(move || {
+                    if let Err(e) = serve(stream, &handler, &*on_terminated) {
+                        ControlSocket::static_log_debug(&format!("Connection closed: {}", e));
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                ControlSocket::static_log_error(&format!("Error accepting connection: {}", e));
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Binds the socket in a directory only the user can enter. A socket left over by a
+/// server that crashed is replaced, one still answering is not.
+fn listen(path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        check_private(dir)?;
+    }
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                ErrorKind::AddrInUse,
+                "in use, is the server already running?",
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+    // nobody else can reach it before this, the directory is private
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Refuses a directory another user could have created or swapped for a symlink in a
+/// shared temp directory, or that lets them reach the socket
+fn check_private(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    let refuse = |reason: String| {
+        Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} {}", dir.display(), reason),
+        ))
+    };
+    if !metadata.file_type().is_dir() {
+        return refuse("is not a directory".to_string());
+    }
+    // SAFETY: getuid takes no arguments and always succeeds
+    if metadata.uid() != unsafe { libc::getuid() } {
+        return refuse("is owned by another user".to_string());
+    }
+    if metadata.mode() & 0o777 != 0o700 {
+        return refuse(format!(
+            "has mode {:o}, only its owner may access it",
+            metadata.mode() & 0o777
+        ));
+    }
+    Ok(())
+}
+
+fn serve(stream: UnixStream, handler: &ServerHandler, on_terminated: &dyn Fn()) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(ControlRequest::Ping) => ControlReply::Pong {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            Ok(ControlRequest::Server(request)) => {
+                ControlReply::Response(forward(handler, request, on_terminated))
+            }
+            Ok(ControlRequest::Subscribe) => {
+                let events = handler.subscribe_events();
+                return stream_events(events, writer);
+            }
+            Err(e) => ControlReply::Response(ServerResponse::Error(format!("Bad request: {}", e))),
+        };
+        write_line(&mut writer, &reply)?;
+    }
+    Ok(())
+}
+
+fn forward(
+    handler: &ServerHandler,
+    request: ServerRequest,
+    on_terminated: &dyn Fn(),
+) -> ServerResponse {
+    let response = handler
+        .request(request)
+        .unwrap_or_else(ServerResponse::Error);
+    if matches!(response, ServerResponse::ServerTerminated(_)) {
+        on_terminated();
+    }
+    response
+}
+
+/// Writes the events until the client goes away or the server is dropped
+fn stream_events(
+    mut events: broadcast::Receiver<ServerEvent>,
+    mut writer: UnixStream,
+) -> io::Result<()> {
+    loop {
+        match events.blocking_recv() {
+            Ok(event) => write_line(&mut writer, &ControlReply::Event(event))?,
+            Err(RecvError::Lagged(missed)) => {
+                ControlSocket::static_log_warn(&format!("A subscriber missed {} events", missed))
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+fn write_line(writer: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    writer.write_all(&line)
+}
+
+/// Connection to the control socket of a running server
+pub struct ControlClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl ControlClient {
+    pub fn connect(path: &Path) -> Result<Self, String> {
+        let writer = UnixStream::connect(path)
+            .map_err(|e| format!("Can't reach the server at {}: {}", path.display(), e))?;
+        let reader = BufReader::new(writer.try_clone().map_err(|e| e.to_string())?);
+        Ok(Self { reader, writer })
+    }
+
+    pub fn send(&mut self, request: &ControlRequest) -> Result<(), String> {
+        write_line(&mut self.writer, request).map_err(|e| e.to_string())
+    }
+
+    /// The next reply, or event once subscribed
+    pub fn receive(&mut self) -> Result<ControlReply, String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err("The server closed the connection".to_string()),
+            Ok(_) => serde_json::from_str(&line).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn request(&mut self, request: &ControlRequest) -> Result<ControlReply, String> {
+        self.send(request)?;
+        self.receive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        command_sender::{CommandSender, PendingRequest},
+        commands::{ServerStopped, ServerTerminated},
+        core::ClientInfo,
+    };
+    use std::sync::mpsc;
+
+    /// A handler whose server answers every request as the real one would
+    fn fake_server() -> (ServerHandler, broadcast::Sender<ServerEvent>) {
+        let (event_pub, _) = broadcast::channel(10);
+        let (request_sender, requests) = mpsc::channel::<PendingRequest>();
+        thread::spawn(move || {
+            for pending in requests {
+                let response = match pending.request {
+                    ServerRequest::StopServer => ServerResponse::ServerStopped(ServerStopped {}),
+                    ServerRequest::TerminateServer => {
+                        ServerResponse::ServerTerminated(ServerTerminated)
+                    }
+                    ref other => ServerResponse::Error(format!("Unexpected {:?}", other)),
+                };
+                pending.respond(response);
+            }
+        });
+        let handler = ServerHandler::new(event_pub.clone(), CommandSender::new(request_sender));
+        (handler, event_pub)
+    }
+
+    #[test]
+    fn requests_and_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join("control.sock");
+        let (handler, event_pub) = fake_server();
+        let (terminated_sender, terminated) = mpsc::channel();
+        let socket = ControlSocket::bind(&path, Arc::new(handler), move || {
+            terminated_sender.send(()).unwrap()
+        })
+        .unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let mut client = ControlClient::connect(&path).unwrap();
+        assert!(matches!(
+            client.request(&ControlRequest::Ping).unwrap(),
+            ControlReply::Pong { .. }
+        ));
+        assert!(matches!(
+            client
+                .request(&ControlRequest::Server(ServerRequest::StopServer))
+                .unwrap(),
+            ControlReply::Response(ServerResponse::ServerStopped(_))
+        ));
+        client
+            .writer
+            .write_all(b"{\"Server\":\"Restart\"}\n")
+            .unwrap();
+        assert!(matches!(
+            client.receive().unwrap(),
+            ControlReply::Response(ServerResponse::Error(_))
+        ));
+
+        let mut subscriber = ControlClient::connect(&path).unwrap();
+        subscriber.send(&ControlRequest::Subscribe).unwrap();
+        // the subscription is made once the line is read
+        while event_pub.receiver_count() == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        event_pub
+            .send(ServerEvent::ClientAdded(ClientInfo {
+                id: 3,
+                addr: "192.168.1.20:50312".to_string(),
+                device_name: None,
+            }))
+            .unwrap();
+        assert!(matches!(
+            subscriber.receive().unwrap(),
+            ControlReply::Event(ServerEvent::ClientAdded(ClientInfo { id: 3, .. }))
+        ));
+
+        assert!(matches!(
+            client
+                .request(&ControlRequest::Server(ServerRequest::TerminateServer))
+                .unwrap(),
+            ControlReply::Response(ServerResponse::ServerTerminated(_))
+        ));
+        terminated.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn replaces_a_stale_socket_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join("control.sock");
+        DirBuilder::new()
+            .mode(0o700)
+            .create(path.parent().unwrap())
+            .unwrap();
+        // left over from a server that crashed
+        drop(UnixListener::bind(&path).unwrap());
+
+        let (handler, _) = fake_server();
+        let handler = Arc::new(handler);
+        let _socket = ControlSocket::bind(&path, Arc::clone(&handler), || {}).unwrap();
+        assert!(ControlSocket::bind(&path, handler, || {}).is_err());
+        assert!(ControlClient::connect(&path).is_ok());
+    }
+
+    #[test]
+    fn only_binds_in_a_private_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let (handler, _) = fake_server();
+        let handler = Arc::new(handler);
+
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = shared.join("control.sock");
+        assert!(ControlSocket::bind(&path, Arc::clone(&handler), || {}).is_err());
+        assert!(!path.exists());
+
+        let private = dir.path().join("private");
+        fs::create_dir(&private).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o700)).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&private, &link).unwrap();
+        assert!(
+            ControlSocket::bind(&link.join("control.sock"), Arc::clone(&handler), || {}).is_err()
+        );
+
+        let path = private.join("control.sock");
+        let _socket = ControlSocket::bind(&path, handler, || {}).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+    }
+}
@@ -1,90 +1,87 @@
 use std::{
     io::Write,
-    net::{SocketAddr, TcpStream},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
     sync::{mpsc::channel, Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use serde::{Deserialize, Serialize};
 
 use tokio::sync::broadcast;
 
-use crate::logger::Loggable;
+use crate::{
+    logger::Loggable,
+    monitors::{self, MonitorInfo},
+};
 
 use super::{
     application::Application,
     client_pool::ClientPool,
     command_listener::{CommandListener, ProcessError},
-    command_sender::CommandSender,
+    command_sender::{CommandSender, PendingRequest},
     commands::{
-        ClientTerminated, ServerRequest, ServerResponse, ServerStarted, ServerStopped,
-        ServerTerminated,
+        ClientDetails, ClientList, ClientTerminated, ConfigUpdated, ServerRequest, ServerResponse,
+        ServerStarted, ServerStatus, ServerStopped, ServerTerminated, VariantOf,
     },
-    discovery::{start_discovery_listener, DiscoveryHandle},
+    config::ServerConfig,
+    discovery::{start_discovery_listener, DiscoveryHandle, DISCOVERY_PORT},
     utils,
 };
 
 const SERVER_REACHED_MAX_CONCURRENT_CLIENTS: i32 = -1;
 
-/// Represents the server configuration.
-///
-/// All new connections will start from the specified port
-/// increasing by 1 for each new connection until reaching
-/// the max_clients.
-pub struct ServerConfig {
-    starting_port: usize,
-    max_clients: usize,
-}
-
-impl ServerConfig {
-    pub fn new(starting_port: usize, max_clients: usize) -> Self {
-        Self {
-            starting_port,
-            max_clients,
-        }
-    }
-}
-
-/// Data sent to a brand new client specifying both:
+/// Data sent to a brand new client specifying:
 /// 1) The new port to which the client should connect.
 /// 2) The server's OS type
+/// 3) The monitor layout, empty when it is unknown
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewClientResponse {
     port: i32,
     server_os: String,
+    #[serde(default)]
+    monitors: Vec<MonitorInfo>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum ServerEvent {
     ClientAdded(ClientInfo),
     ClientRemoved(ClientInfo),
     ClientUpdated(ClientInfo),
+    /// A phone sent a file, now saved in the download directory
+    FileReceived(TransferredFile),
+    /// A phone has received a desktop file
+    FileSent(TransferredFile),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientInfo {
     pub id: usize,
     pub addr: String,
     pub device_name: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferredFile {
+    pub client_id: usize,
+    pub name: String,
+    /// Where the file is on the desktop
+    pub path: PathBuf,
+    pub size: u64,
+}
+
 pub struct ServerHandler {
     event_pub: broadcast::Sender<ServerEvent>,
     command_sender: CommandSender,
-    discovery_handle: Option<DiscoveryHandle>,
 }
 
 impl ServerHandler {
-    pub fn new(
-        event_pub: broadcast::Sender<ServerEvent>,
-        command_sender: CommandSender,
-        discovery_handle: Option<DiscoveryHandle>,
-    ) -> Self {
+    pub fn new(event_pub: broadcast::Sender<ServerEvent>, command_sender: CommandSender) -> Self {
         Self {
             event_pub,
             command_sender,
-            discovery_handle,
         }
     }
 
@@ -92,43 +89,79 @@ impl ServerHandler {
         self.event_pub.subscribe()
     }
 
-    fn send_request(
-        &self,
-        request: ServerRequest,
-    ) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.command_sender.send_request(request)
+    /// Fails the requests not answered within `timeout`, 5 seconds by default
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.command_sender.set_timeout(timeout);
+    }
+
+    /// Sends a request and waits for the server's response to it.
+    /// Blocks the thread, from async code use `request_async` or the typed requests.
+    pub fn request(&self, request: ServerRequest) -> Result<ServerResponse, String> {
+        self.command_sender.request(request)
+    }
+
+    /// Sends a request and waits for the server's response to it
+    pub async fn request_async(&self, request: ServerRequest) -> Result<ServerResponse, String> {
+        self.command_sender.request_async(request).await
+    }
+
+    pub async fn start_server(&self) -> Result<ServerStarted, String> {
+        expect_response(self.request_async(ServerRequest::InitServer).await)
     }
 
-    pub fn start_server(&self) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.send_request(ServerRequest::InitServer)
+    pub async fn stop_server(&self) -> Result<ServerStopped, String> {
+        expect_response(self.request_async(ServerRequest::StopServer).await)
     }
 
-    pub fn stop_server(&self) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.send_request(ServerRequest::StopServer)
+    pub async fn terminate_server(&self) -> Result<ServerTerminated, String> {
+        expect_response(self.request_async(ServerRequest::TerminateServer).await)
     }
 
-    pub fn terminate_server(&self) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.send_request(ServerRequest::TerminateServer)
+    pub async fn terminate_client(&self, client_id: usize) -> Result<ClientTerminated, String> {
+        expect_response(
+            self.request_async(ServerRequest::TerminateClient(client_id))
+                .await,
+        )
     }
 
-    pub fn terminate_client(
-        &self,
-        client_id: usize,
-    ) -> Result<(), std::sync::mpsc::SendError<ServerRequest>> {
-        self.send_request(ServerRequest::TerminateClient(client_id))
+    pub async fn list_clients(&self) -> Result<Vec<ClientInfo>, String> {
+        expect_response::<ClientList>(self.request_async(ServerRequest::ListClients).await)
+            .map(|list| list.clients)
     }
 
-    pub fn receive_response(&mut self) -> Result<ServerResponse, std::sync::mpsc::RecvError> {
-        self.command_sender.receive_response()
+    pub async fn get_status(&self) -> Result<ServerStatus, String> {
+        expect_response(self.request_async(ServerRequest::GetStatus).await)
+    }
+
+    pub async fn get_client(&self, client_id: usize) -> Result<ClientInfo, String> {
+        expect_response::<ClientDetails>(
+            self.request_async(ServerRequest::GetClient(client_id))
+                .await,
+        )
+        .map(|details| details.client)
+    }
+
+    pub async fn get_config(&self) -> Result<ServerConfig, String> {
+        expect_response(self.request_async(ServerRequest::GetConfig).await)
+    }
+
+    /// Applies the config to the running server. It is not saved.
+    pub async fn update_config(&self, config: ServerConfig) -> Result<ConfigUpdated, String> {
+        expect_response(
+            self.request_async(ServerRequest::UpdateConfig(config))
+                .await,
+        )
     }
 }
 
-/// Automatic cleanup when ServerHandler is dropped
-impl Drop for ServerHandler {
-    fn drop(&mut self) {
-        // Automatically shutdown discovery when the handler is dropped
-        if let Some(handle) = self.discovery_handle.take() {
-            handle.shutdown();
+/// The expected response, `Error` responses and any other response being errors
+fn expect_response<T: VariantOf<ServerResponse>>(
+    response: Result<ServerResponse, String>,
+) -> Result<T, String> {
+    match response? {
+        ServerResponse::Error(e) => Err(e),
+        other => {
+            T::try_variant_of(other).map_err(|other| format!("Unexpected response: {:?}", other))
         }
     }
 }
@@ -148,37 +181,54 @@ pub struct Server<A: Application> {
 
     /// Indicates whether the server is currently terminating.
     terminate_signal: bool,
+
+    started_at: Instant,
+
+    /// Address the client listener is bound to, once it is
+    address: Option<SocketAddr>,
+
+    /// Where new clients connect, polled by the client listener. Kept here so
+    /// `UpdateConfig` can close it before binding another address on the same port.
+    listener: Option<TcpListener>,
+
+    /// The UDP discovery listener, if it is running
+    discovery: Option<DiscoveryHandle>,
 }
 
 impl<A: Application + 'static> Server<A> {
     /// Runs the server.
     ///
     /// This call is non-blocking, and allows the server to wait for
-    /// new client connection requests at port 7878.
+    /// new client connection requests at the configured `starting_port` (7878 by default).
     /// It also creates a thread that listens for server commands through channels.
     ///
     /// Once a new connection to a new client is established, that client is assigned a new
     /// isolated socket with its own port. Each client then connects to the server through its own
     /// assigned socket.
     ///
-    /// The port 7878 is only used as a common ground to establish new connections with new clients.
-    pub fn start(config: ServerConfig, app: A) -> ServerHandler {
+    /// The starting port is only used as a common ground to establish new connections with new clients.
+    pub fn start(config: ServerConfig, mut app: A) -> ServerHandler {
         let (event_pub, _) = broadcast::channel(100);
 
-        // Extract port before config is moved
-        let starting_port = config.starting_port;
-
         // Initialize logger with default settings
         env_logger::init();
-        let clients = ClientPool::new(config.max_clients, event_pub.clone());
+        let clients = ClientPool::new(&config, event_pub.clone());
+        app.set_notifier(clients.notifier());
+        app.configure(&config);
 
-        // Unidirectional channel from ServerController (client) -> Server
-        let (send_to_server, receive_from_client) = channel::<ServerRequest>();
-        // Unidirectional channel from Server -> ServerController (client)
-        let (send_to_client, receive_from_server) = channel::<ServerResponse>();
+        // Channel from ServerController (client) -> Server.
+        // Each request carries its own channel for the response.
+        let (send_to_server, receive_from_client) = channel::<PendingRequest>();
 
         // will listen for commands from server controller and will parse them.
-        let command_listener = CommandListener::new(send_to_client, receive_from_client);
+        let command_listener = CommandListener::new(receive_from_client);
+
+        // Start UDP discovery listener so clients can find the server
+        let discovery = if config.discovery {
+            Self::start_discovery(config.starting_port)
+        } else {
+            None
+        };
 
         thread::spawn(move || {
             let server = Arc::new(Mutex::new(Server {
@@ -187,6 +237,10 @@ impl<A: Application + 'static> Server<A> {
                 app: Arc::new(Mutex::new(app)),
                 listening_to_clients: false,
                 terminate_signal: false,
+                started_at: Instant::now(),
+                address: None,
+                discovery,
+                listener: None,
             }));
 
             // set command listener callback for parsing server commands before starting command listener thread
@@ -210,12 +264,20 @@ impl<A: Application + 'static> Server<A> {
             Self::static_log_info("Command listener thread has exited.");
         });
 
-        // Start UDP discovery listener so clients can find the server
-        let discovery_handle = match start_discovery_listener(starting_port as u16) {
+        // return channel endpoints to send messages and also receive messages to / from the server
+        ServerHandler {
+            event_pub,
+            command_sender: CommandSender::new(send_to_server),
+        }
+    }
+
+    /// Starts the UDP discovery listener advertising the port, if it can be bound
+    fn start_discovery(starting_port: usize) -> Option<DiscoveryHandle> {
+        match start_discovery_listener(starting_port as u16) {
             Ok(handle) => {
                 Self::static_log_info(&format!(
-                    "UDP discovery listener started on port 7877 (advertising TCP port {})",
-                    starting_port
+                    "UDP discovery listener started on port {} (advertising TCP port {})",
+                    DISCOVERY_PORT, starting_port
                 ));
                 Some(handle)
             }
@@ -226,57 +288,30 @@ impl<A: Application + 'static> Server<A> {
                 ));
                 None
             }
-        };
-
-        // return channel endpoints to send messages and also receive messages to / from the server
-        ServerHandler {
-            event_pub,
-            command_sender: CommandSender::new(send_to_server, receive_from_server),
-            discovery_handle,
         }
     }
 
     /// Main loop.
-    /// Waits on a new client connection.
+    /// Waits on a new client connection, once `InitServer` bound the listener.
     fn start_client_listener(server: Arc<Mutex<Self>>) {
-        let mut server_is_scheduled_for_termination;
-
-        let starting_port = {
-            let lock = server.lock().unwrap();
-            server_is_scheduled_for_termination = lock.terminate_signal;
-            lock.config.starting_port
-        };
-
-        let socket = match utils::create_socket(starting_port) {
-            Ok(socket) => socket,
-            Err(e) => {
-                Self::static_log_error(&format!(
-                    "Failed to create socket for client listener: {}. Network may be unavailable.",
-                    e
-                ));
-                return;
-            }
-        };
-
-        // configure it such that .accept() returns immediately
-        // without blocking the thread. Allows to sleep for a while if no
-        // new connections are available.
-        socket.set_nonblocking(true).unwrap();
-
-        Self::static_log_info(&format!(
-            "Starting client listener: {}",
-            socket.local_addr().unwrap()
-        ));
-
         loop {
-            if server_is_scheduled_for_termination {
-                Self::static_log_info(&format!(
-                    "Terminated client listener thread. Server is scheduled for termination."
-                ));
-                break;
-            }
+            // pull the server lock to check if the server is scheduled for termination,
+            // and to accept on the listener it currently has
+            let connection = {
+                let lock = server.lock().unwrap();
+                if lock.terminate_signal {
+                    Self::static_log_info(
+                        "Terminated client listener thread. Server is scheduled for termination.",
+                    );
+                    break;
+                }
+                match &lock.listener {
+                    Some(listener) => listener.accept(),
+                    None => Err(std::io::ErrorKind::WouldBlock.into()),
+                }
+            };
 
-            match socket.accept() {
+            match connection {
                 Ok(connection) => {
                     Self::handle_new_client(&server, connection);
                 }
@@ -288,13 +323,32 @@ impl<A: Application + 'static> Server<A> {
                     Self::static_log_error(&format!("Error accepting connection: {}", e));
                 }
             }
+        }
+    }
 
-            // pull the server lock to check if the server is scheduled for termination
-            server_is_scheduled_for_termination = {
-                let lock = server.lock().unwrap();
-                lock.terminate_signal
-            };
+    /// Binds the client listener on the configured address, unless it already is.
+    /// Binding again after a failure, e.g. once the network is back, is up to the caller.
+    fn ensure_listening(&mut self) -> Result<(), String> {
+        if self.listener.is_some() {
+            return Ok(());
         }
+        let socket = Self::listen(self.config.bind_address, self.config.starting_port)
+            .map_err(|e| format!("Can't listen on {}: {}", listen_address(&self.config), e))?;
+        self.address = socket.local_addr().ok();
+        Self::static_log_info(&format!("Starting client listener: {:?}", self.address));
+        self.listener = Some(socket);
+        Ok(())
+    }
+
+    /// Binds the socket new clients connect to.
+    ///
+    /// It is configured such that .accept() returns immediately
+    /// without blocking the thread. Allows to sleep for a while if no
+    /// new connections are available.
+    fn listen(bind_address: Option<IpAddr>, port: usize) -> std::io::Result<TcpListener> {
+        let socket = utils::create_socket(bind_address, port)?;
+        socket.set_nonblocking(true)?;
+        Ok(socket)
     }
 
     // Upon receiving a new connection request, create a new client
@@ -321,7 +375,7 @@ impl<A: Application + 'static> Server<A> {
                     connection_port as i32
                 }
                 Err(reason) => {
-                    Self::static_log_error(&format!("{reason}"));
+                    Self::static_log_error(&reason.to_string());
                     SERVER_REACHED_MAX_CONCURRENT_CLIENTS
                 }
             };
@@ -329,6 +383,7 @@ impl<A: Application + 'static> Server<A> {
             let data = serde_json::to_vec(&NewClientResponse {
                 port,
                 server_os: std::env::consts::OS.to_owned(), // send the server OS to client
+                monitors: monitors::list(),
             })
             .unwrap();
 
@@ -350,7 +405,8 @@ impl<A: Application + 'static> Server<A> {
 
         match req {
             ServerRequest::InitServer => {
-                // send response to the server
+                lock.ensure_listening()
+                    .map_err(|message| ProcessError { message })?;
                 lock.listening_to_clients = true;
                 Ok(ServerResponse::ServerStarted(ServerStarted {}))
             }
@@ -363,6 +419,9 @@ impl<A: Application + 'static> Server<A> {
             ServerRequest::TerminateServer => {
                 lock.terminate_signal = true;
                 lock.clients.shutdown();
+                if let Some(discovery) = lock.discovery.take() {
+                    discovery.shutdown();
+                }
                 Ok(ServerResponse::ServerTerminated(ServerTerminated {}))
             }
             ServerRequest::TerminateClient(client_id) => {
@@ -376,21 +435,124 @@ impl<A: Application + 'static> Server<A> {
                     }
                 }
             }
+            ServerRequest::ListClients => Ok(ServerResponse::ClientList(ClientList {
+                clients: lock.clients.list(),
+            })),
+            ServerRequest::GetStatus => Ok(ServerResponse::ServerStatus(lock.status())),
+            ServerRequest::GetClient(client_id) => match lock.clients.get(client_id) {
+                Some(client) => Ok(ServerResponse::ClientDetails(ClientDetails { client })),
+                None => Err(ProcessError {
+                    message: format!("Client {} not found in pool.", client_id),
+                }),
+            },
+            ServerRequest::GetConfig => Ok(ServerResponse::ServerConfig(lock.config.clone())),
+            ServerRequest::UpdateConfig(config) => lock
+                .update_config(config)
+                .map(ServerResponse::ConfigUpdated)
+                .map_err(|message| ProcessError { message }),
         }
     }
+
+    /// Applies a new config, only restarting the client listener when its address
+    /// changed and the discovery listener when it was toggled or advertises another
+    /// port. Nothing changes if the new address can't be listened on.
+    fn update_config(&mut self, config: ServerConfig) -> Result<ConfigUpdated, String> {
+        config.validate()?;
+
+        let listener_restarted = config.starting_port != self.config.starting_port
+            || config.bind_address != self.config.bind_address;
+        if listener_restarted {
+            // closed first, the new address may overlap it, e.g. 0.0.0.0 on the same port
+            let previous = self.listener.take();
+            drop(previous);
+            match Self::listen(config.bind_address, config.starting_port) {
+                Ok(socket) => {
+                    self.address = socket.local_addr().ok();
+                    Self::static_log_info(&format!("Moved client listener to {:?}", self.address));
+                    self.listener = Some(socket);
+                }
+                Err(e) => {
+                    self.listener =
+                        Self::listen(self.config.bind_address, self.config.starting_port)
+                            .map_err(|e| {
+                                Self::static_log_error(&format!(
+                                    "Failed to listen on the previous address again: {}",
+                                    e
+                                ))
+                            })
+                            .ok();
+                    self.address = self
+                        .listener
+                        .as_ref()
+                        .and_then(|listener| listener.local_addr().ok());
+                    return Err(format!(
+                        "Can't listen on {}: {}",
+                        listen_address(&config),
+                        e
+                    ));
+                }
+            }
+        }
+
+        let discovery_restarted = config.discovery != self.config.discovery
+            || (config.discovery && config.starting_port != self.config.starting_port);
+        if discovery_restarted {
+            if let Some(discovery) = self.discovery.take() {
+                discovery.shutdown();
+                // frees the UDP discovery port for the new discovery listener
+                discovery.wait_for_exit();
+            }
+            if config.discovery {
+                self.discovery = Self::start_discovery(config.starting_port);
+            }
+        }
+
+        self.clients.configure(&config);
+        self.app.lock().unwrap().configure(&config);
+        self.config = config;
+
+        Ok(ConfigUpdated {
+            listener_restarted,
+            discovery_restarted,
+        })
+    }
+
+    fn status(&self) -> ServerStatus {
+        ServerStatus {
+            listening: self.listening_to_clients,
+            address: self.address,
+            port: self.config.starting_port,
+            max_clients: self.clients.max_clients(),
+            clients: self.clients.list().len(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            discovery: self.discovery.is_some(),
+        }
+    }
+}
+
+/// Where the client listener of the config listens, for the error messages
+fn listen_address(config: &ServerConfig) -> String {
+    match config.bind_address {
+        Some(ip) => SocketAddr::new(ip, config.starting_port as u16).to_string(),
+        None => format!("port {} of the local network address", config.starting_port),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::server::application::ConnectionStatus;
     use std::sync::mpsc::channel;
 
     #[test]
     fn test_server_config_creation() {
         let config = ServerConfig::new(8000, 10);
-        // We can access private fields in the same module
         assert_eq!(config.starting_port, 8000);
         assert_eq!(config.max_clients, 10);
+        assert_eq!(config.bind_address, None);
+
+        let config = config.with_bind_address(IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(config.bind_address, Some(IpAddr::from([127, 0, 0, 1])));
     }
 
     #[test]
@@ -441,16 +603,240 @@ mod tests {
         }
     }
 
+    struct Ignore;
+
+    impl Application for Ignore {
+        fn dispatch_to_device(&mut self, _: usize, _: &[u8]) -> ConnectionStatus {
+            ConnectionStatus::Connected
+        }
+    }
+
+    #[test]
+    fn test_query_requests() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        // the pool fails to add clients when nobody listens to its events
+        let (event_pub, _events) = broadcast::channel(10);
+        let config = ServerConfig::new(47801, 3).with_bind_address(localhost);
+        let server = Arc::new(Mutex::new(Server {
+            clients: ClientPool::new(&config, event_pub),
+            config,
+            app: Arc::new(Mutex::new(Ignore)),
+            listening_to_clients: true,
+            terminate_signal: false,
+            started_at: Instant::now(),
+            address: Some(SocketAddr::new(localhost, 47801)),
+            discovery: None,
+            listener: None,
+        }));
+        {
+            let mut lock = server.lock().unwrap();
+            let app = Arc::clone(&lock.app);
+            lock.clients
+                .add(SocketAddr::new(localhost, 50312), app)
+                .unwrap();
+        }
+
+        let status = ServerStatus::assert_variant_of(
+            Server::command_parser(server.clone(), ServerRequest::GetStatus).unwrap(),
+        );
+        assert!(status.listening && !status.discovery);
+        assert_eq!(status.address, Some(SocketAddr::new(localhost, 47801)));
+        assert_eq!(
+            (status.port, status.max_clients, status.clients),
+            (47801, 3, 1)
+        );
+
+        let list = ClientList::assert_variant_of(
+            Server::command_parser(server.clone(), ServerRequest::ListClients).unwrap(),
+        );
+        assert_eq!(list.clients.len(), 1);
+        assert_eq!(list.clients[0].addr, "127.0.0.1:50312");
+
+        let details = ClientDetails::assert_variant_of(
+            Server::command_parser(server.clone(), ServerRequest::GetClient(list.clients[0].id))
+                .unwrap(),
+        );
+        assert_eq!(details.client.id, list.clients[0].id);
+        assert!(Server::command_parser(server.clone(), ServerRequest::GetClient(99)).is_err());
+
+        server.lock().unwrap().clients.shutdown();
+    }
+
+    #[test]
+    fn test_init_server_listens() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let (event_pub, _events) = broadcast::channel(10);
+        let config = ServerConfig::new(47851, 3).with_bind_address(localhost);
+        let server = Arc::new(Mutex::new(Server {
+            clients: ClientPool::new(&config, event_pub),
+            config,
+            app: Arc::new(Mutex::new(Ignore)),
+            listening_to_clients: false,
+            terminate_signal: false,
+            started_at: Instant::now(),
+            address: None,
+            discovery: None,
+            listener: None,
+        }));
+        let init = || Server::command_parser(server.clone(), ServerRequest::InitServer);
+
+        let taken = TcpListener::bind((localhost, 47851)).unwrap();
+        let error = init().unwrap_err().message;
+        assert!(error.contains("127.0.0.1:47851"), "{}", error);
+        assert!(!server.lock().unwrap().status().listening);
+
+        drop(taken);
+        ServerStarted::assert_variant_of(init().unwrap());
+        let status = server.lock().unwrap().status();
+        assert!(status.listening);
+        assert_eq!(status.address, Some(SocketAddr::new(localhost, 47851)));
+        // already listening
+        ServerStarted::assert_variant_of(init().unwrap());
+    }
+
+    /// Keeps the config it was last given
+    #[derive(Default)]
+    struct Configured(Option<ServerConfig>);
+
+    impl Application for Configured {
+        fn dispatch_to_device(&mut self, _: usize, _: &[u8]) -> ConnectionStatus {
+            ConnectionStatus::Connected
+        }
+
+        fn configure(&mut self, config: &ServerConfig) {
+            self.0 = Some(config.clone());
+        }
+    }
+
+    #[test]
+    fn test_update_config() {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let (event_pub, _events) = broadcast::channel(10);
+        let mut config = ServerConfig::new(47811, 3).with_bind_address(localhost);
+        config.discovery = false;
+        let app = Arc::new(Mutex::new(Configured::default()));
+        let server = Arc::new(Mutex::new(Server {
+            clients: ClientPool::new(&config, event_pub),
+            config: config.clone(),
+            app: Arc::clone(&app),
+            listening_to_clients: true,
+            terminate_signal: false,
+            started_at: Instant::now(),
+            address: Some(SocketAddr::new(localhost, 47811)),
+            discovery: None,
+            listener: Some(Server::<Configured>::listen(Some(localhost), 47811).unwrap()),
+        }));
+        let update = |config: ServerConfig| {
+            Server::command_parser(server.clone(), ServerRequest::UpdateConfig(config))
+        };
+
+        // applied live
+        config.max_clients = 5;
+        config.permissions.power = false;
+        let updated = ConfigUpdated::assert_variant_of(update(config.clone()).unwrap());
+        assert!(!updated.listener_restarted && !updated.discovery_restarted);
+        assert_eq!(server.lock().unwrap().clients.max_clients(), 5);
+        assert_eq!(app.lock().unwrap().0, Some(config.clone()));
+
+        let listening_on = |address: SocketAddr| {
+            let lock = server.lock().unwrap();
+            assert_eq!(lock.status().address, Some(address));
+            let listener = lock.listener.as_ref().unwrap();
+            assert_eq!(listener.local_addr().unwrap(), address);
+        };
+
+        // the listener moves to another address on the same port
+        let any = IpAddr::from([0, 0, 0, 0]);
+        config.bind_address = Some(any);
+        let updated = ConfigUpdated::assert_variant_of(update(config.clone()).unwrap());
+        assert!(updated.listener_restarted);
+        listening_on(SocketAddr::new(any, 47811));
+        assert!(TcpStream::connect((localhost, 47811)).is_ok());
+
+        // and to another port
+        config.starting_port = 47821;
+        let updated = ConfigUpdated::assert_variant_of(update(config.clone()).unwrap());
+        assert!(updated.listener_restarted);
+        listening_on(SocketAddr::new(any, 47821));
+
+        // nothing changes when the config can't be applied
+        let _taken = TcpListener::bind((localhost, 47831)).unwrap();
+        let mut unusable = config.clone();
+        unusable.starting_port = 47831;
+        unusable.max_clients = 1;
+        let error = update(unusable).unwrap_err().message;
+        assert!(error.contains("0.0.0.0:47831"), "{}", error);
+        assert!(update(ServerConfig::new(47811, 0)).is_err());
+
+        let current = ServerConfig::assert_variant_of(
+            Server::command_parser(server.clone(), ServerRequest::GetConfig).unwrap(),
+        );
+        assert_eq!(current, config);
+        listening_on(SocketAddr::new(any, 47821));
+
+        server.lock().unwrap().clients.shutdown();
+    }
+
     #[test]
     fn test_server_handler_new_and_subscribe() {
         let (event_pub, _) = broadcast::channel(10);
         let (req_sender, _req_receiver) = channel();
-        let (_resp_sender, resp_receiver) = channel();
 
-        let command_sender = CommandSender::new(req_sender, resp_receiver);
-        let handler = ServerHandler::new(event_pub, command_sender, None);
+        let command_sender = CommandSender::new(req_sender);
+        let handler = ServerHandler::new(event_pub, command_sender);
 
         // Test subscribe_events returns a valid receiver
         let _receiver = handler.subscribe_events();
     }
+
+    #[test]
+    fn test_concurrent_requests() {
+        let (event_pub, _) = broadcast::channel(10);
+        let (req_sender, req_receiver) = channel();
+        let listener = CommandListener::new(req_receiver);
+        listener.set_command_processor(|req| match req {
+            ServerRequest::GetClient(client_id) => {
+                Ok(ServerResponse::ClientDetails(ClientDetails {
+                    client: ClientInfo {
+                        id: client_id,
+                        addr: format!("10.0.0.{}:50000", client_id),
+                        device_name: None,
+                    },
+                }))
+            }
+            ServerRequest::InitServer => Ok(ServerResponse::ServerStarted(ServerStarted {})),
+            _ => Err(ProcessError::new("Not supported".to_string())),
+        });
+        let listener = listener.listen(Duration::ZERO);
+        let handler = Arc::new(ServerHandler::new(
+            event_pub,
+            CommandSender::new(req_sender),
+        ));
+
+        let requests: Vec<_> = (1..=4)
+            .map(|client_id| {
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    let response = handler.request(ServerRequest::GetClient(client_id));
+                    ClientDetails::assert_variant_of(response.unwrap())
+                        .client
+                        .id
+                        == client_id
+                })
+            })
+            .collect();
+        assert!(requests.into_iter().all(|request| request.join().unwrap()));
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            assert!(handler.start_server().await.is_ok());
+            let error = handler.stop_server().await.unwrap_err();
+            assert!(error.contains("Not supported"), "{}", error);
+            let client = handler.get_client(2).await.unwrap();
+            assert_eq!(client.addr, "10.0.0.2:50000");
+        });
+
+        listener.schedule_shutdown();
+        listener.wait_for_exit();
+    }
 }
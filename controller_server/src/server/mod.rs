@@ -1,9 +1,13 @@
 mod client_pool;
 mod command_listener;
 pub mod discovery;
-mod utils;
+pub(crate) mod utils;
 
 pub mod application;
 pub mod command_sender;
 pub mod commands;
+pub mod config;
+#[cfg(unix)]
+pub mod control;
 pub mod core;
+pub mod notifier;
@@ -0,0 +1,170 @@
+//! Server -> mobile client push channel.
+//!
+//! Besides the single-byte lifecycle events (client terminated, server shutdown),
+//! the server may push structured data to the mobile clients. Those messages are
+//! framed as `[EVENT_PAYLOAD][u32 big-endian length][JSON payload]`. File chunks
+//! sent to a client skip the JSON and are framed as
+//! `[EVENT_FILE_CHUNK][u32 transfer id][u64 offset][u32 length][bytes]`, and screen
+//! preview tiles as `[EVENT_SCREEN_TILE][u8 format][u8 monitor][i32 x][i32 y]
+//! [u16 width][u16 height][i32 cursor x][i32 cursor y][u32 length][image]`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{
+    logger::Loggable,
+    screen_preview::{PreviewFormat, ScreenTile},
+};
+
+use super::{client_pool::Client, core::ServerEvent};
+
+/// Event code that precedes a length-prefixed JSON payload
+pub const EVENT_PAYLOAD: u8 = 253;
+
+/// Event code that precedes a chunk of a file sent to the client
+pub const EVENT_FILE_CHUNK: u8 = 252;
+
+/// Event code that precedes a tile of the screen preview
+pub const EVENT_SCREEN_TILE: u8 = 251;
+
+/// Handle given to the application so it can push data to connected clients.
+///
+/// Cloning is cheap - all clones refer to the same pool of clients.
+/// It also lets the application publish `ServerEvent`s to the server's controller.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    clients: Arc<Mutex<HashMap<usize, Arc<Client>>>>,
+    events: Option<broadcast::Sender<ServerEvent>>,
+}
+
+impl Notifier {
+    pub(super) fn new(
+        clients: Arc<Mutex<HashMap<usize, Arc<Client>>>>,
+        events: broadcast::Sender<ServerEvent>,
+    ) -> Self {
+        Self {
+            clients,
+            events: Some(events),
+        }
+    }
+
+    /// Number of clients currently in the pool.
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Sends a payload to every connected client.
+    pub fn broadcast<T: Serialize>(&self, payload: &T) {
+        let Some(frame) = Self::encode(payload) else {
+            return;
+        };
+        // written without the pool locked, a slow client must not hold up the others
+        let clients: Vec<Arc<Client>> = self.clients.lock().unwrap().values().cloned().collect();
+        for client in clients {
+            client.send_bytes(&frame);
+        }
+    }
+
+    /// Sends a payload to a single client. Does nothing if it is gone.
+    pub fn send_to<T: Serialize>(&self, client_id: usize, payload: &T) {
+        if let Some(frame) = Self::encode(payload) {
+            self.send_frame(client_id, &frame);
+        }
+    }
+
+    /// Sends a chunk of a file to a client, starting at `offset` in the file
+    pub fn send_file_chunk(&self, client_id: usize, transfer_id: u32, offset: u64, data: &[u8]) {
+        let mut frame = Vec::with_capacity(data.len() + 17);
+        frame.push(EVENT_FILE_CHUNK);
+        frame.write_u32::<BigEndian>(transfer_id).unwrap();
+        frame.write_u64::<BigEndian>(offset).unwrap();
+        frame.write_u32::<BigEndian>(data.len() as u32).unwrap();
+        frame.extend_from_slice(data);
+        self.send_frame(client_id, &frame);
+    }
+
+    /// Sends a screen preview tile to a client. The format is 0 for JPEG and 1 for PNG.
+    pub fn send_screen_tile(&self, client_id: usize, tile: &ScreenTile) {
+        let mut frame = Vec::with_capacity(tile.image.len() + 27);
+        frame.push(EVENT_SCREEN_TILE);
+        frame.push(match tile.format {
+            PreviewFormat::Jpeg => 0,
+            PreviewFormat::Png => 1,
+        });
+        frame.push(tile.monitor);
+        frame.write_i32::<BigEndian>(tile.area.x).unwrap();
+        frame.write_i32::<BigEndian>(tile.area.y).unwrap();
+        frame
+            .write_u16::<BigEndian>(tile.area.width as u16)
+            .unwrap();
+        frame
+            .write_u16::<BigEndian>(tile.area.height as u16)
+            .unwrap();
+        frame.write_i32::<BigEndian>(tile.cursor.0).unwrap();
+        frame.write_i32::<BigEndian>(tile.cursor.1).unwrap();
+        frame
+            .write_u32::<BigEndian>(tile.image.len() as u32)
+            .unwrap();
+        frame.extend_from_slice(&tile.image);
+        self.send_frame(client_id, &frame);
+    }
+
+    fn send_frame(&self, client_id: usize, frame: &[u8]) {
+        let client = self.clients.lock().unwrap().get(&client_id).cloned();
+        if let Some(client) = client {
+            client.send_bytes(frame);
+        }
+    }
+
+    /// Publishes an event to whoever controls the server, e.g. the desktop app
+    pub fn publish(&self, event: ServerEvent) {
+        if let Some(events) = &self.events {
+            // no subscriber is not an error, the event is just not needed
+            let _ = events.send(event);
+        }
+    }
+
+    /// Frames a payload as `[EVENT_PAYLOAD][len][json]`.
+    pub fn encode<T: Serialize>(payload: &T) -> Option<Vec<u8>> {
+        let json = match serde_json::to_vec(payload) {
+            Ok(json) => json,
+            Err(e) => {
+                Self::static_log_error(&format!("Failed to serialize client payload: {}", e));
+                return None;
+            }
+        };
+
+        let mut frame = Vec::with_capacity(json.len() + 5);
+        frame.push(EVENT_PAYLOAD);
+        frame.write_u32::<BigEndian>(json.len() as u32).unwrap();
+        frame.extend_from_slice(&json);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_prefixes_event_code_and_length() {
+        let frame = Notifier::encode(&"hi").unwrap();
+        assert_eq!(frame[0], EVENT_PAYLOAD);
+        assert_eq!(&frame[1..5], &[0, 0, 0, 4]);
+        assert_eq!(&frame[5..], b"\"hi\"");
+    }
+
+    #[test]
+    fn default_notifier_has_no_clients() {
+        let notifier = Notifier::default();
+        assert_eq!(notifier.client_count(), 0);
+        // broadcasting without clients is a no-op
+        notifier.broadcast(&42);
+    }
+}
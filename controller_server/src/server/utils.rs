@@ -1,13 +1,33 @@
-use std::net::TcpListener;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
 
 use local_ip_address::local_ip;
+use socket2::{SockRef, TcpKeepalive};
 
-/// Creates a TcpListener using the local machine's IP address
+use super::config::Heartbeat;
+
+/// Creates a TcpListener on the given address, or the local machine's IP address
 ///
 /// Returns an error if no network is available or if binding fails.
-pub fn create_socket(port: usize) -> std::io::Result<TcpListener> {
-    let local_ip = local_ip()
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::NetworkUnreachable, e.to_string()))?;
-    log::debug!("Created new Sync socket: {local_ip}:{port}");
-    TcpListener::bind(format!("{local_ip}:{port}"))
+pub fn create_socket(address: Option<IpAddr>, port: usize) -> std::io::Result<TcpListener> {
+    let ip = match address {
+        Some(ip) => ip,
+        None => local_ip().map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::NetworkUnreachable, e.to_string())
+        })?,
+    };
+    log::debug!("Created new Sync socket: {ip}:{port}");
+    TcpListener::bind(SocketAddr::new(ip, port as u16))
+}
+
+/// Probes the connection with TCP keepalives as the heartbeat says, or stops probing it
+pub fn set_heartbeat(stream: &TcpStream, heartbeat: &Heartbeat) -> std::io::Result<()> {
+    let socket = SockRef::from(stream);
+    if !heartbeat.enabled {
+        return socket.set_keepalive(false);
+    }
+    let keepalive = TcpKeepalive::new()
+        .with_time(heartbeat.interval())
+        .with_interval(heartbeat.interval())
+        .with_retries(heartbeat.missed);
+    socket.set_tcp_keepalive(&keepalive)
 }
@@ -0,0 +1,291 @@
+//! Shortcut buttons shared by every paired phone.
+//!
+//! The catalog lives in `shortcuts.json` in the config directory and is edited from
+//! the desktop app. Phones fetch it with `ListShortcuts`, run an entry by id, and are
+//! sent the new catalog whenever it changes.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{input::InputKey, paths};
+
+pub const SHORTCUTS_FILE: &str = "shortcuts.json";
+
+/// Flutter icon, stored the way the phone builds an `IconData`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShortcutIcon {
+    pub code_point: u32,
+    /// `None` for the Material icons font
+    #[serde(default)]
+    pub font_family: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShortcutAction {
+    /// Press the keys together, e.g. Ctrl+Shift+T
+    Keys {
+        keys: Vec<InputKey>,
+    },
+    Text {
+        text: String,
+    },
+    /// Shell command, run in the background
+    Command {
+        command: String,
+    },
+    /// Run the macro with this id
+    Macro {
+        id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    pub id: String,
+    pub label: String,
+    pub icon: ShortcutIcon,
+    pub action: ShortcutAction,
+}
+
+type ChangeListener = Arc<dyn Fn(&[Shortcut]) + Send + Sync>;
+
+#[derive(Default)]
+struct Catalog {
+    shortcuts: Vec<Shortcut>,
+    /// Where the catalog is saved, `None` to keep it in memory only
+    path: Option<PathBuf>,
+    listeners: Vec<(usize, ChangeListener)>,
+    next_listener_id: usize,
+}
+
+/// Keeps a change listener registered. Dropping it removes the listener.
+pub struct ShortcutSubscription {
+    catalog: ShortcutCatalog,
+    id: usize,
+}
+
+impl Drop for ShortcutSubscription {
+    fn drop(&mut self) {
+        let mut catalog = self.catalog.inner.lock().unwrap();
+        catalog.listeners.retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// The shortcuts shown on every phone. Cloning it doesn't copy them, so the edits of the
+/// desktop app reach the controller.
+#[derive(Clone, Default)]
+pub struct ShortcutCatalog {
+    inner: Arc<Mutex<Catalog>>,
+}
+
+impl ShortcutCatalog {
+    /// Catalog backed by `shortcuts.json` in the config directory
+    pub fn load() -> Self {
+        paths::load_config(SHORTCUTS_FILE, Self::open).unwrap_or_else(Self::in_memory)
+    }
+
+    /// Catalog backed by the given file, which doesn't need to exist yet
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let shortcuts = paths::load_json(path)?.unwrap_or_default();
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Catalog {
+                shortcuts,
+                path: Some(path.to_path_buf()),
+                ..Catalog::default()
+            })),
+        })
+    }
+
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    pub fn shortcuts(&self) -> Vec<Shortcut> {
+        self.inner.lock().unwrap().shortcuts.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Shortcut> {
+        self.inner
+            .lock()
+            .unwrap()
+            .shortcuts
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+    }
+
+    /// Calls `listener` with the whole catalog every time it changes, until the
+    /// returned subscription is dropped.
+    pub fn on_change(
+        &self,
+        listener: impl Fn(&[Shortcut]) + Send + Sync + 'static,
+    ) -> ShortcutSubscription {
+        let mut catalog = self.inner.lock().unwrap();
+        let id = catalog.next_listener_id;
+        catalog.next_listener_id += 1;
+        catalog.listeners.push((id, Arc::new(listener)));
+        ShortcutSubscription {
+            catalog: self.clone(),
+            id,
+        }
+    }
+
+    /// Adds the shortcut, or replaces the one with the same id, and saves the catalog
+    pub fn save_shortcut(&self, shortcut: Shortcut) -> Result<(), String> {
+        if shortcut.id.trim().is_empty() {
+            return Err("Shortcut id can't be empty".to_string());
+        }
+        let mut catalog = self.inner.lock().unwrap();
+        match catalog.shortcuts.iter_mut().find(|s| s.id == shortcut.id) {
+            Some(existing) => *existing = shortcut,
+            None => catalog.shortcuts.push(shortcut),
+        }
+        Self::changed(catalog)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut catalog = self.inner.lock().unwrap();
+        let before = catalog.shortcuts.len();
+        catalog.shortcuts.retain(|s| s.id != id);
+        if catalog.shortcuts.len() == before {
+            return Err(format!("No shortcut with id '{}'", id));
+        }
+        Self::changed(catalog)
+    }
+
+    /// Moves the shortcuts into the given order. Ids that are left out keep
+    /// their relative order after the listed ones.
+    pub fn reorder(&self, ids: &[String]) -> Result<(), String> {
+        let mut catalog = self.inner.lock().unwrap();
+        catalog
+            .shortcuts
+            .sort_by_key(|s| ids.iter().position(|id| *id == s.id).unwrap_or(ids.len()));
+        Self::changed(catalog)
+    }
+}
+
+impl ShortcutCatalog {
+    /// Saves the edited catalog, then tells the listeners about it once it is unlocked:
+    /// they may be slow, e.g. writing to every phone
+    fn changed(catalog: MutexGuard<Catalog>) -> Result<(), String> {
+        let saved = catalog.save();
+        let shortcuts = catalog.shortcuts.clone();
+        let listeners: Vec<ChangeListener> = catalog
+            .listeners
+            .iter()
+            .map(|(_, listener)| Arc::clone(listener))
+            .collect();
+        drop(catalog);
+
+        for listener in listeners {
+            listener(&shortcuts);
+        }
+        saved
+    }
+}
+
+impl Catalog {
+    fn save(&self) -> Result<(), String> {
+        match &self.path {
+            Some(path) => paths::save_json(path, &self.shortcuts),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcut(id: &str) -> Shortcut {
+        Shortcut {
+            id: id.to_string(),
+            label: id.to_uppercase(),
+            icon: ShortcutIcon {
+                code_point: 0xe14d,
+                font_family: None,
+            },
+            action: ShortcutAction::Keys {
+                keys: vec![InputKey::Control, InputKey::Unicode('c')],
+            },
+        }
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("laze").join(SHORTCUTS_FILE);
+
+        let catalog = ShortcutCatalog::open(&path).unwrap();
+        catalog.save_shortcut(shortcut("copy")).unwrap();
+
+        let reloaded = ShortcutCatalog::open(&path).unwrap();
+        assert_eq!(reloaded.get("copy"), Some(shortcut("copy")));
+
+        reloaded.remove("copy").unwrap();
+        assert!(ShortcutCatalog::open(&path).unwrap().shortcuts().is_empty());
+        assert!(reloaded.remove("copy").is_err());
+        assert!(reloaded.save_shortcut(shortcut(" ")).is_err());
+    }
+
+    #[test]
+    fn file_format() {
+        let json = r#"[{
+            "id": "browser",
+            "label": "Browser",
+            "icon": { "code_point": 59530 },
+            "action": { "type": "command", "command": "firefox" }
+        }]"#;
+        let shortcuts: Vec<Shortcut> = serde_json::from_str(json).unwrap();
+        assert_eq!(shortcuts[0].icon.font_family, None);
+        assert_eq!(
+            shortcuts[0].action,
+            ShortcutAction::Command {
+                command: "firefox".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn listeners_see_every_change() {
+        let catalog = ShortcutCatalog::in_memory();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let subscription = catalog.on_change(move |shortcuts| {
+            sink.lock()
+                .unwrap()
+                .push(shortcuts.iter().map(|s| s.id.clone()).collect::<Vec<_>>())
+        });
+
+        catalog.save_shortcut(shortcut("a")).unwrap();
+        catalog.save_shortcut(shortcut("b")).unwrap();
+        catalog.reorder(&["b".to_string()]).unwrap();
+        catalog.remove("a").unwrap();
+        drop(subscription);
+        catalog.remove("b").unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![vec!["a"], vec!["a", "b"], vec!["b", "a"], vec!["b"],]
+        );
+    }
+
+    #[test]
+    fn listeners_can_read_the_catalog() {
+        let catalog = ShortcutCatalog::in_memory();
+        let seen = Arc::new(Mutex::new(0));
+        let (reader, sink) = (catalog.clone(), Arc::clone(&seen));
+        let _subscription = catalog.on_change(move |_| {
+            *sink.lock().unwrap() = reader.shortcuts().len();
+        });
+
+        catalog.save_shortcut(shortcut("a")).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), 1);
+    }
+}
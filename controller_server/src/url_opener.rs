@@ -0,0 +1,179 @@
+//! Opens links shared from the phone with the desktop's default handler.
+//!
+//! Only `http`, `https` and `mailto` links are opened by default. Other schemes can
+//! be allowed in `open_url.json` in the config directory:
+//!
+//! ```json
+//! { "schemes": ["http", "https", "mailto", "spotify"] }
+//! ```
+//!
+//! The link is handed to the opener (`xdg-open`, `open`, or the Windows URL handler)
+//! as a single argument, never through a shell.
+
+use std::{path::Path, process::Command};
+
+use serde::Deserialize;
+
+use crate::paths;
+
+pub const OPEN_URL_FILE: &str = "open_url.json";
+
+const DEFAULT_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Longest link accepted, in bytes
+const MAX_URL_LEN: usize = 8 * 1024;
+
+#[derive(Deserialize)]
+struct Settings {
+    schemes: Vec<String>,
+}
+
+pub struct UrlOpener {
+    /// Allowed schemes, lowercase
+    schemes: Vec<String>,
+}
+
+impl UrlOpener {
+    /// The default schemes, replaced by the ones in `open_url.json` if there is one
+    pub fn load() -> Self {
+        paths::load_config(OPEN_URL_FILE, |path| {
+            let mut opener = Self::defaults();
+            opener.override_from(path).map(|_| opener)
+        })
+        .unwrap_or_else(Self::defaults)
+    }
+
+    pub fn defaults() -> Self {
+        Self {
+            schemes: DEFAULT_SCHEMES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Replaces the allowed schemes with the ones listed in the file, if there is one
+    pub fn override_from(&mut self, path: &Path) -> Result<(), String> {
+        let Some(settings) = paths::load_json::<Settings>(path)? else {
+            return Ok(());
+        };
+        self.schemes = settings
+            .schemes
+            .iter()
+            .map(|scheme| scheme.to_lowercase())
+            .collect();
+        Ok(())
+    }
+
+    /// Checks the link, returning it without surrounding whitespace
+    pub fn validate<'a>(&self, url: &'a str) -> Result<&'a str, String> {
+        let url = url.trim();
+        if url.len() > MAX_URL_LEN {
+            return Err(format!("Links are limited to {} bytes", MAX_URL_LEN));
+        }
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err("Links can't contain spaces or control characters".to_string());
+        }
+
+        let (scheme, rest) = url
+            .split_once(':')
+            .filter(|(scheme, _)| is_scheme(scheme))
+            .ok_or_else(|| format!("'{}' is not a link", url))?;
+        let scheme = scheme.to_lowercase();
+        if !self.schemes.contains(&scheme) {
+            return Err(format!("Opening {} links is not allowed", scheme));
+        }
+        if (scheme == "http" || scheme == "https")
+            && rest.strip_prefix("//").is_none_or(|authority| {
+                authority.starts_with(['/', '?', '#']) || authority.is_empty()
+            })
+        {
+            return Err(format!("'{}' has no host", url));
+        }
+        if rest.is_empty() {
+            return Err(format!("'{}' is empty", url));
+        }
+        Ok(url)
+    }
+
+    /// Opens the link with the desktop's default handler
+    pub fn open(&self, url: &str) -> Result<(), String> {
+        let url = self.validate(url)?;
+        let mut child = opener_command(url)
+            .spawn()
+            .map_err(|e| format!("Failed to start the opener: {}", e))?;
+        // reap it once it exits
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// `ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )`, as in RFC 3986
+fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+#[cfg(target_os = "windows")]
+fn opener_command(url: &str) -> Command {
+    let mut command = Command::new("rundll32");
+    command.arg("url.dll,FileProtocolHandler").arg(url);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn opener_command(url: &str) -> Command {
+    let mut command = Command::new("open");
+    command.arg(url);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn opener_command(url: &str) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(url);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn default_schemes() {
+        let opener = UrlOpener::defaults();
+        assert_eq!(
+            opener.validate(" https://example.com/a?b=c#d\n"),
+            Ok("https://example.com/a?b=c#d")
+        );
+        assert!(opener.validate("HTTP://example.com").is_ok());
+        assert!(opener.validate("mailto:me@example.com").is_ok());
+
+        assert!(opener.validate("file:///etc/passwd").is_err());
+        assert!(opener.validate("javascript:alert(1)").is_err());
+        assert!(opener.validate("example.com").is_err());
+        assert!(opener.validate("-https://example.com").is_err());
+        assert!(opener.validate("https:///path").is_err());
+        assert!(opener.validate("https:example.com").is_err());
+        assert!(opener.validate("mailto:").is_err());
+    }
+
+    #[test]
+    fn no_spaces_or_control_characters() {
+        let opener = UrlOpener::defaults();
+        assert!(opener.validate("https://example.com/$(rm -rf ~)").is_err());
+        assert!(opener.validate("https://example.com/\u{0}").is_err());
+    }
+
+    #[test]
+    fn override_schemes() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, r#"{{ "schemes": ["https", "Spotify"] }}"#).unwrap();
+
+        let mut opener = UrlOpener::defaults();
+        opener.override_from(file.path()).unwrap();
+        assert!(opener
+            .validate("spotify:track:4uLU6hMCjMI75M1A2tKUQC")
+            .is_ok());
+        assert!(opener.validate("http://example.com").is_err());
+    }
+}
@@ -0,0 +1,275 @@
+//! Lists and manages the desktop windows for the phone.
+//!
+//! On X11 the windows are driven through EWMH, which every mainstream window
+//! manager supports. Elsewhere there is no portable way to reach other apps'
+//! windows, so the commands fall back to the desktop's key bindings, acting on
+//! the focused window.
+
+#[cfg(target_os = "linux")]
+mod x11;
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    actions::WindowCommand, client_messages::ClientMessage, input, input::InputKey,
+    logger::Loggable, server::notifier::Notifier,
+};
+
+/// How often the window list is checked for changes while clients are connected
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A top-level window, as shown in the phone's window switcher
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    /// Application class, e.g. `firefox`
+    pub app: String,
+    /// Base64 encoded PNG of the window icon
+    pub icon: Option<String>,
+    /// `None` when the window is shown on every workspace
+    pub workspace: Option<u32>,
+    pub focused: bool,
+    pub minimized: bool,
+}
+
+/// Something able to list and control the desktop windows
+pub trait WindowManager: Send + Sync {
+    /// Short name used in logs, e.g. `ewmh`
+    fn name(&self) -> &'static str;
+
+    /// Windows in stacking order, bottom first
+    fn windows(&mut self) -> Result<Vec<WindowInfo>, String>;
+
+    fn active_window(&mut self) -> Result<Option<u32>, String>;
+
+    /// Raises and focuses the window, restoring it if it is minimized
+    fn focus(&mut self, window: u32) -> Result<(), String>;
+
+    fn minimize(&mut self, window: u32) -> Result<(), String>;
+
+    fn toggle_maximize(&mut self, window: u32) -> Result<(), String>;
+
+    /// Asks the window to close, as if its close button was clicked
+    fn close(&mut self, window: u32) -> Result<(), String>;
+
+    fn move_to_workspace(&mut self, window: u32, workspace: u32) -> Result<(), String>;
+
+    /// Moves the window to another monitor, keeping its relative position
+    fn move_to_monitor(&mut self, window: u32, monitor: u32) -> Result<(), String>;
+}
+
+/// Connects to the window manager of the current session, if it can be driven directly
+pub fn connect() -> Result<Box<dyn WindowManager>, String> {
+    if input::needs_uinput(
+        env::var("DISPLAY").ok().as_deref(),
+        env::var("XDG_SESSION_TYPE").ok().as_deref(),
+    ) {
+        return Err("Windows can only be listed on X11".to_string());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(x11::EwmhWindowManager::connect()?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Windows can only be listed on X11".to_string())
+    }
+}
+
+/// Runs a command through the window manager. Window `0` is the focused window.
+pub fn apply(manager: &mut dyn WindowManager, command: &WindowCommand) -> Result<(), String> {
+    let window = match command.window() {
+        Some(0) => manager
+            .active_window()?
+            .ok_or_else(|| "No window is focused".to_string())?,
+        Some(window) => window,
+        None => return Ok(()),
+    };
+    match command {
+        WindowCommand::List => Ok(()),
+        WindowCommand::Focus(_) => manager.focus(window),
+        WindowCommand::Minimize(_) => manager.minimize(window),
+        WindowCommand::ToggleMaximize(_) => manager.toggle_maximize(window),
+        WindowCommand::Close(_) => manager.close(window),
+        WindowCommand::MoveToWorkspace { workspace, .. } => {
+            manager.move_to_workspace(window, *workspace as u32)
+        }
+        WindowCommand::MoveToMonitor { monitor, .. } => {
+            manager.move_to_monitor(window, *monitor as u32)
+        }
+    }
+}
+
+/// Key combo doing the command on the focused window, when there is no window manager
+/// to talk to. Focusing a given window and picking a workspace have no key binding.
+/// Moving to a monitor goes to the next one.
+pub fn fallback_keys(command: &WindowCommand) -> Option<Vec<InputKey>> {
+    use InputKey::*;
+
+    let keys = match command {
+        WindowCommand::List | WindowCommand::Focus(_) | WindowCommand::MoveToWorkspace { .. } => {
+            return None
+        }
+        WindowCommand::Close(_) if cfg!(target_os = "macos") => vec![Meta, Unicode('w')],
+        WindowCommand::Close(_) => vec![Alt, F4],
+        WindowCommand::Minimize(_) if cfg!(target_os = "macos") => vec![Meta, Unicode('m')],
+        WindowCommand::Minimize(_) if cfg!(target_os = "windows") => vec![Meta, DownArrow],
+        WindowCommand::Minimize(_) => vec![Meta, Unicode('h')],
+        WindowCommand::ToggleMaximize(_) if cfg!(target_os = "macos") => {
+            vec![Control, Meta, Unicode('f')]
+        }
+        WindowCommand::ToggleMaximize(_) => vec![Meta, UpArrow],
+        WindowCommand::MoveToMonitor { .. } if cfg!(target_os = "macos") => return None,
+        WindowCommand::MoveToMonitor { .. } => vec![Shift, Meta, RightArrow],
+    };
+    Some(keys)
+}
+
+/// Pushes the window list to the clients whenever it changes.
+/// Does nothing when the window manager can't be reached.
+pub struct WindowListMonitor {
+    shutdown_signal: Arc<AtomicBool>,
+}
+
+impl WindowListMonitor {
+    pub fn start(notifier: Notifier) -> Self {
+        let shutdown_signal = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::clone(&shutdown_signal);
+
+        thread::spawn(move || {
+            // a connection of its own, so polling never waits on the controller
+            let mut manager = match connect() {
+                Ok(manager) => manager,
+                Err(e) => {
+                    Self::static_log_info(&format!("Not watching the window list: {}", e));
+                    return;
+                }
+            };
+            let mut last_windows: Option<Vec<WindowInfo>> = None;
+            let mut last_client_count = 0;
+
+            while !shutdown.load(Ordering::SeqCst) {
+                let client_count = notifier.client_count();
+                if client_count > 0 {
+                    match manager.windows() {
+                        Ok(windows) => {
+                            if client_count > last_client_count
+                                || last_windows.as_ref() != Some(&windows)
+                            {
+                                notifier.broadcast(&ClientMessage::Windows {
+                                    windows: windows.clone(),
+                                });
+                                last_windows = Some(windows);
+                            }
+                        }
+                        Err(e) => Self::static_log_warn(&format!("Failed to list windows: {}", e)),
+                    }
+                }
+                last_client_count = client_count;
+
+                thread::sleep(POLL_INTERVAL);
+            }
+            Self::static_log_info("Window list monitor has exited.");
+        });
+
+        Self { shutdown_signal }
+    }
+}
+
+impl Drop for WindowListMonitor {
+    fn drop(&mut self) {
+        self.shutdown_signal.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the calls, with window 7 focused
+    #[derive(Default)]
+    struct FakeManager {
+        calls: Vec<String>,
+    }
+
+    impl WindowManager for FakeManager {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+        fn windows(&mut self) -> Result<Vec<WindowInfo>, String> {
+            Ok(Vec::new())
+        }
+        fn active_window(&mut self) -> Result<Option<u32>, String> {
+            Ok(Some(7))
+        }
+        fn focus(&mut self, window: u32) -> Result<(), String> {
+            self.calls.push(format!("focus {}", window));
+            Ok(())
+        }
+        fn minimize(&mut self, window: u32) -> Result<(), String> {
+            self.calls.push(format!("minimize {}", window));
+            Ok(())
+        }
+        fn toggle_maximize(&mut self, window: u32) -> Result<(), String> {
+            self.calls.push(format!("maximize {}", window));
+            Ok(())
+        }
+        fn close(&mut self, window: u32) -> Result<(), String> {
+            self.calls.push(format!("close {}", window));
+            Ok(())
+        }
+        fn move_to_workspace(&mut self, window: u32, workspace: u32) -> Result<(), String> {
+            self.calls
+                .push(format!("window {} to workspace {}", window, workspace));
+            Ok(())
+        }
+        fn move_to_monitor(&mut self, window: u32, monitor: u32) -> Result<(), String> {
+            self.calls
+                .push(format!("window {} to monitor {}", window, monitor));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn window_zero_is_the_focused_one() {
+        let mut manager = FakeManager::default();
+        apply(&mut manager, &WindowCommand::Close(0)).unwrap();
+        apply(&mut manager, &WindowCommand::Focus(3)).unwrap();
+        apply(
+            &mut manager,
+            &WindowCommand::MoveToWorkspace {
+                window: 0,
+                workspace: 2,
+            },
+        )
+        .unwrap();
+        apply(&mut manager, &WindowCommand::List).unwrap();
+        assert_eq!(
+            manager.calls,
+            vec!["close 7", "focus 3", "window 7 to workspace 2"]
+        );
+    }
+
+    #[test]
+    fn key_fallbacks() {
+        assert_eq!(fallback_keys(&WindowCommand::Focus(3)), None);
+        assert_eq!(fallback_keys(&WindowCommand::List), None);
+        let close = fallback_keys(&WindowCommand::Close(0)).unwrap();
+        if cfg!(target_os = "macos") {
+            assert_eq!(close, vec![InputKey::Meta, InputKey::Unicode('w')]);
+        } else {
+            assert_eq!(close, vec![InputKey::Alt, InputKey::F4]);
+        }
+    }
+}
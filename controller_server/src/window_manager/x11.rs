@@ -0,0 +1,436 @@
+//! Window management on X11 through the EWMH hints.
+//!
+//! Requests are sent to the root window as a pager would, so the window manager
+//! applies them without its focus stealing prevention getting in the way.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, Window},
+    rust_connection::RustConnection,
+    CURRENT_TIME,
+};
+
+use super::{WindowInfo, WindowManager};
+use crate::monitors::x11::list_monitors;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        UTF8_STRING,
+        WM_CHANGE_STATE,
+        _NET_CLIENT_LIST,
+        _NET_ACTIVE_WINDOW,
+        _NET_CLOSE_WINDOW,
+        _NET_MOVERESIZE_WINDOW,
+        _NET_WM_NAME,
+        _NET_WM_ICON,
+        _NET_WM_DESKTOP,
+        _NET_WM_STATE,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+    }
+}
+
+/// Source indication of a pager, whose requests the window manager always honors
+const SOURCE_PAGER: u32 = 2;
+
+/// ICCCM state requested to minimize a window
+const ICONIC_STATE: u32 = 3;
+
+// _NET_WM_STATE actions
+const STATE_REMOVE: u32 = 0;
+const STATE_ADD: u32 = 1;
+const STATE_TOGGLE: u32 = 2;
+
+// _NET_MOVERESIZE_WINDOW flags setting x and y
+const MOVE_X: u32 = 1 << 8;
+const MOVE_Y: u32 = 1 << 9;
+
+/// `_NET_WM_DESKTOP` of windows shown on every workspace
+const ALL_WORKSPACES: u32 = 0xFFFF_FFFF;
+
+/// Width of the icons sent to the phone, in pixels
+const ICON_SIZE: u32 = 48;
+
+/// Position and size of a window or monitor
+type Rect = (i32, i32, i32, i32);
+
+pub struct EwmhWindowManager {
+    connection: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    /// Encoded icon of each window, read once since icons rarely change
+    icons: HashMap<Window, Option<String>>,
+}
+
+impl EwmhWindowManager {
+    pub fn connect() -> Result<Self, String> {
+        let (connection, screen) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let root = connection.setup().roots[screen].root;
+        let atoms = Atoms::new(&connection)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            connection,
+            root,
+            atoms,
+            icons: HashMap::new(),
+        })
+    }
+
+    fn property(
+        &self,
+        window: Window,
+        property: u32,
+        type_: impl Into<u32>,
+    ) -> Result<x11rb::protocol::xproto::GetPropertyReply, String> {
+        self.connection
+            .get_property(false, window, property, type_, 0, u32::MAX)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())
+    }
+
+    fn property32(
+        &self,
+        window: Window,
+        property: u32,
+        type_: impl Into<u32>,
+    ) -> Result<Vec<u32>, String> {
+        let reply = self.property(window, property, type_)?;
+        Ok(reply
+            .value32()
+            .map(|values| values.collect())
+            .unwrap_or_default())
+    }
+
+    fn title(&self, window: Window) -> Result<String, String> {
+        let mut title = self
+            .property(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)?
+            .value;
+        if title.is_empty() {
+            title = self
+                .property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING)?
+                .value;
+        }
+        Ok(String::from_utf8_lossy(&title).into_owned())
+    }
+
+    /// Class part of `WM_CLASS`, which holds "instance\0class\0"
+    fn app(&self, window: Window) -> Result<String, String> {
+        let class = self
+            .property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING)?
+            .value;
+        Ok(class
+            .split(|byte| *byte == 0)
+            .rfind(|part| !part.is_empty())
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .unwrap_or_default())
+    }
+
+    fn icon(&mut self, window: Window) -> Option<String> {
+        if let Some(icon) = self.icons.get(&window) {
+            return icon.clone();
+        }
+        let icon = self
+            .property32(window, self.atoms._NET_WM_ICON, AtomEnum::CARDINAL)
+            .ok()
+            .and_then(|data| icon_png(&data));
+        self.icons.insert(window, icon.clone());
+        icon
+    }
+
+    fn window_info(
+        &mut self,
+        window: Window,
+        active: Option<Window>,
+    ) -> Result<Option<WindowInfo>, String> {
+        let state = self.property32(window, self.atoms._NET_WM_STATE, AtomEnum::ATOM)?;
+        if state.contains(&self.atoms._NET_WM_STATE_SKIP_TASKBAR) {
+            return Ok(None);
+        }
+        let workspace = self
+            .property32(window, self.atoms._NET_WM_DESKTOP, AtomEnum::CARDINAL)?
+            .first()
+            .copied()
+            .filter(|workspace| *workspace != ALL_WORKSPACES);
+
+        Ok(Some(WindowInfo {
+            id: window,
+            title: self.title(window)?,
+            app: self.app(window)?,
+            icon: self.icon(window),
+            workspace,
+            focused: active == Some(window),
+            minimized: state.contains(&self.atoms._NET_WM_STATE_HIDDEN),
+        }))
+    }
+
+    /// Sends an EWMH client message about a window to the window manager
+    fn send_message(&self, window: Window, message: u32, data: [u32; 5]) -> Result<(), String> {
+        let event = ClientMessageEvent::new(32, window, message, data);
+        self.connection
+            .send_event(
+                false,
+                self.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )
+            .map_err(|e| e.to_string())?;
+        self.connection.flush().map_err(|e| e.to_string())
+    }
+
+    fn set_maximized(&self, window: Window, action: u32) -> Result<(), String> {
+        self.send_message(
+            window,
+            self.atoms._NET_WM_STATE,
+            [
+                action,
+                self.atoms._NET_WM_STATE_MAXIMIZED_VERT,
+                self.atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                SOURCE_PAGER,
+                0,
+            ],
+        )
+    }
+
+    fn monitors(&self) -> Result<Vec<Rect>, String> {
+        Ok(list_monitors(&self.connection, self.root)?
+            .iter()
+            .map(|m| (m.x, m.y, m.width as i32, m.height as i32))
+            .collect())
+    }
+
+    fn geometry(&self, window: Window) -> Result<Rect, String> {
+        let size = self
+            .connection
+            .get_geometry(window)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        let origin = self
+            .connection
+            .translate_coordinates(window, self.root, 0, 0)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?;
+        Ok((
+            origin.dst_x as i32,
+            origin.dst_y as i32,
+            size.width as i32,
+            size.height as i32,
+        ))
+    }
+}
+
+impl WindowManager for EwmhWindowManager {
+    fn name(&self) -> &'static str {
+        "ewmh"
+    }
+
+    fn windows(&mut self) -> Result<Vec<WindowInfo>, String> {
+        let active = self.active_window()?;
+        let ids = self.property32(self.root, self.atoms._NET_CLIENT_LIST, AtomEnum::WINDOW)?;
+        self.icons.retain(|window, _| ids.contains(window));
+
+        let mut windows = Vec::new();
+        for window in ids {
+            // the window may close while it is being read
+            if let Ok(Some(info)) = self.window_info(window, active) {
+                windows.push(info);
+            }
+        }
+        Ok(windows)
+    }
+
+    fn active_window(&mut self) -> Result<Option<u32>, String> {
+        let active = self.property32(self.root, self.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW)?;
+        Ok(active.first().copied().filter(|window| *window != 0))
+    }
+
+    fn focus(&mut self, window: u32) -> Result<(), String> {
+        self.send_message(
+            window,
+            self.atoms._NET_ACTIVE_WINDOW,
+            [SOURCE_PAGER, CURRENT_TIME, 0, 0, 0],
+        )
+    }
+
+    fn minimize(&mut self, window: u32) -> Result<(), String> {
+        self.send_message(
+            window,
+            self.atoms.WM_CHANGE_STATE,
+            [ICONIC_STATE, 0, 0, 0, 0],
+        )
+    }
+
+    fn toggle_maximize(&mut self, window: u32) -> Result<(), String> {
+        self.set_maximized(window, STATE_TOGGLE)
+    }
+
+    fn close(&mut self, window: u32) -> Result<(), String> {
+        self.send_message(
+            window,
+            self.atoms._NET_CLOSE_WINDOW,
+            [CURRENT_TIME, SOURCE_PAGER, 0, 0, 0],
+        )
+    }
+
+    fn move_to_workspace(&mut self, window: u32, workspace: u32) -> Result<(), String> {
+        self.send_message(
+            window,
+            self.atoms._NET_WM_DESKTOP,
+            [workspace, SOURCE_PAGER, 0, 0, 0],
+        )
+    }
+
+    fn move_to_monitor(&mut self, window: u32, monitor: u32) -> Result<(), String> {
+        let monitors = self.monitors()?;
+        let target = *monitors
+            .get(monitor as usize)
+            .ok_or_else(|| format!("There is no monitor {}", monitor))?;
+        let geometry = self.geometry(window)?;
+        let (x, y) = position_on_monitor(geometry, &monitors, target);
+
+        // maximized windows ignore moves, maximize again once moved
+        let state = self.property32(window, self.atoms._NET_WM_STATE, AtomEnum::ATOM)?;
+        let maximized = state.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_VERT)
+            && state.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_HORZ);
+        if maximized {
+            self.set_maximized(window, STATE_REMOVE)?;
+        }
+        self.send_message(
+            window,
+            self.atoms._NET_MOVERESIZE_WINDOW,
+            [
+                MOVE_X | MOVE_Y | SOURCE_PAGER << 12,
+                x as u32,
+                y as u32,
+                0,
+                0,
+            ],
+        )?;
+        if maximized {
+            self.set_maximized(window, STATE_ADD)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a window goes on the target monitor: at the same offset it has on its
+/// current monitor, kept inside the target
+fn position_on_monitor(window: Rect, monitors: &[Rect], target: Rect) -> (i32, i32) {
+    let (x, y, width, height) = window;
+    let (center_x, center_y) = (x + width / 2, y + height / 2);
+    let current = monitors
+        .iter()
+        .find(|(mx, my, mw, mh)| {
+            (*mx..mx + mw).contains(&center_x) && (*my..my + mh).contains(&center_y)
+        })
+        .copied()
+        .unwrap_or(target);
+
+    let (tx, ty, tw, th) = target;
+    (
+        (tx + x - current.0).min(tx + tw - width).max(tx),
+        (ty + y - current.1).min(ty + th - height).max(ty),
+    )
+}
+
+/// Picks the `_NET_WM_ICON` entry closest to the phone's icon size and encodes it
+/// as a base64 PNG. The property holds width, height and ARGB pixels for each size.
+fn icon_png(data: &[u32]) -> Option<String> {
+    let mut icons = Vec::new();
+    let mut rest = data;
+    while rest.len() >= 2 {
+        let (width, height) = (rest[0], rest[1]);
+        let len = (width as usize).checked_mul(height as usize)?;
+        if width == 0 || height == 0 || rest.len() < 2 + len {
+            break;
+        }
+        icons.push((width, height, &rest[2..2 + len]));
+        rest = &rest[2 + len..];
+    }
+
+    // the smallest icon at least as big as needed, or else the biggest one
+    let (width, height, pixels) = icons
+        .iter()
+        .filter(|(width, _, _)| *width >= ICON_SIZE)
+        .min_by_key(|(width, _, _)| *width)
+        .or_else(|| icons.iter().max_by_key(|(width, _, _)| *width))
+        .copied()?;
+
+    let (scaled_width, scaled_height) = if width > ICON_SIZE {
+        (ICON_SIZE, (height * ICON_SIZE / width).max(1))
+    } else {
+        (width, height)
+    };
+    let mut rgba = Vec::with_capacity((scaled_width * scaled_height * 4) as usize);
+    for row in 0..scaled_height {
+        for column in 0..scaled_width {
+            // nearest neighbour is plenty for an icon in a list
+            let source = (row * height / scaled_height) * width + column * width / scaled_width;
+            let [a, r, g, b] = pixels[source as usize].to_be_bytes();
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, scaled_width, scaled_height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().ok()?;
+    writer.write_image_data(&rgba).ok()?;
+    writer.finish().ok()?;
+    Some(STANDARD.encode(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_offset_on_the_new_monitor() {
+        let monitors = [(0, 0, 1920, 1080), (1920, 0, 1280, 1024)];
+        assert_eq!(
+            position_on_monitor((100, 50, 800, 600), &monitors, monitors[1]),
+            (2020, 50)
+        );
+        // too far right for the smaller monitor
+        assert_eq!(
+            position_on_monitor((1000, 500, 800, 600), &monitors, monitors[1]),
+            (2400, 424)
+        );
+        assert_eq!(
+            position_on_monitor((2020, 50, 800, 600), &monitors, monitors[0]),
+            (100, 50)
+        );
+    }
+
+    #[test]
+    fn icon_is_picked_and_scaled() {
+        // a 2x2 and a 96x96 opaque red icon
+        let mut data = vec![2, 2, 0, 0, 0, 0, 96, 96];
+        data.extend(std::iter::repeat_n(0xffff0000, 96 * 96));
+
+        let png = STANDARD.decode(icon_png(&data).unwrap()).unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (ICON_SIZE, ICON_SIZE));
+        assert_eq!(&pixels[..4], &[0xff, 0, 0, 0xff]);
+    }
+
+    #[test]
+    fn malformed_icons_are_ignored() {
+        assert_eq!(icon_png(&[]), None);
+        assert_eq!(icon_png(&[64, 0]), None);
+        assert_eq!(icon_png(&[64, 64, 1, 2, 3]), None);
+    }
+}
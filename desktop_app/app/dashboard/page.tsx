@@ -3,6 +3,7 @@
 import { invoke } from "@tauri-apps/api/core";
 import { useEffect, useState } from "react";
 import { listen } from "@tauri-apps/api/event";
+import Link from "next/link";
 import { enable, disable, isEnabled } from "@tauri-apps/plugin-autostart";
 import styles from "./dashboard.module.css";
 
@@ -18,6 +19,13 @@ interface ClientInfo {
     device_name?: string;
 }
 
+interface TransferredFile {
+    client_id: number;
+    name: string;
+    path: string;
+    size: number;
+}
+
 interface Client extends ClientInfo {
     name: string;
     timeConnected: Timer;
@@ -40,6 +48,7 @@ export default function Dashboard() {
     const [clients, setClients] = useState<Client[]>([]);
     const [selectedClient, setSelectedClient] = useState<number | null>(null);
     const [autostart, setAutostart] = useState(false);
+    const [notice, setNotice] = useState<string | null>(null);
 
     useEffect(() => {
         isEnabled().then(setAutostart).catch(console.error);
@@ -71,6 +80,29 @@ export default function Dashboard() {
         }
     }
 
+    async function sendFile() {
+        if (selectedClient === null) return;
+        const path = window.prompt("Path of the file to send");
+        if (!path) return;
+        try {
+            await invoke("send_file", { clientId: selectedClient, path });
+        } catch (error) {
+            setNotice(`Failed to send file: ${error}`);
+        }
+    }
+
+    async function changeDownloadDir() {
+        const current = await invoke<string>("get_download_dir").catch(() => "");
+        const path = window.prompt("Save files from the phones in", current);
+        if (path === null) return;
+        try {
+            // an empty path goes back to the default downloads folder
+            await invoke("set_download_dir", { path: path.trim() || null });
+        } catch (error) {
+            setNotice(`Failed to change the download folder: ${error}`);
+        }
+    }
+
     useEffect(() => {
         // Clear navigation flag when dashboard loads (Fast Refresh recovery)
         sessionStorage.removeItem("navigating_to_dashboard");
@@ -88,6 +120,24 @@ export default function Dashboard() {
             );
         }, 1000);
 
+        // Replaces the list with the server's, e.g. after missed events
+        const resyncClients = async () => {
+            try {
+                const current = await invoke<ClientInfo[]>("list_clients");
+                if (!isSubscribed) return;
+                setClients((prev) => current.map(info => {
+                    const known = prev.find(c => c.id === info.id);
+                    return {
+                        ...info,
+                        name: info.device_name || known?.name || "Unknown Device",
+                        timeConnected: known?.timeConnected ?? { hours: 0, minutes: 0, seconds: 0 }
+                    };
+                }));
+            } catch (error) {
+                console.error("Failed to list clients:", error);
+            }
+        };
+
         // Subscribe to client events
         const setupListeners = async () => {
             if (!isSubscribed) return;
@@ -121,9 +171,22 @@ export default function Dashboard() {
                 ));
             });
             if (isSubscribed) unsubscribers.push(clientUpdatedUnsub);
+
+            const resyncUnsub = await listen("clients-resync", resyncClients);
+            if (isSubscribed) unsubscribers.push(resyncUnsub);
+
+            const fileReceivedUnsub = await listen<TransferredFile>("file-received", (event) => {
+                setNotice(`Received ${event.payload.name}, saved to ${event.payload.path}`);
+            });
+            if (isSubscribed) unsubscribers.push(fileReceivedUnsub);
+
+            const fileSentUnsub = await listen<TransferredFile>("file-sent", (event) => {
+                setNotice(`Sent ${event.payload.name}`);
+            });
+            if (isSubscribed) unsubscribers.push(fileSentUnsub);
         };
 
-        setupListeners();
+        setupListeners().then(resyncClients);
 
         return () => {
             isSubscribed = false;
@@ -150,12 +213,27 @@ export default function Dashboard() {
                         />
                         Run on Startup
                     </label>
+                    <button onClick={changeDownloadDir} className={styles.stopButton}>
+                        Download Folder
+                    </button>
+                    <Link href="/shortcuts" className={styles.stopButton}>
+                        Shortcuts
+                    </Link>
+                    <Link href="/macros" className={styles.stopButton}>
+                        Macros
+                    </Link>
                     <button onClick={stopServer} className={styles.stopButton}>
                         Stop Server
                     </button>
                 </div>
             </header>
 
+            {notice && (
+                <div className={styles.notice} onClick={() => setNotice(null)}>
+                    {notice}
+                </div>
+            )}
+
             {/* Client Table */}
             <div className={styles.tableContainer}>
                 <table className={styles.table}>
@@ -184,6 +262,14 @@ export default function Dashboard() {
                 </table>
             </div>
 
+            <button
+                onClick={sendFile}
+                className={`${styles.removeButton} ${styles.sendButton}`}
+                disabled={selectedClient === null}
+            >
+                Send File
+            </button>
+
             {/* Remove Button */}
             <button
                 onClick={removeClient}
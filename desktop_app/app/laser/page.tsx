@@ -0,0 +1,33 @@
+"use client";
+
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+import styles from "./laser.module.css";
+
+interface LaserPosition {
+    /** Fractions of the screen, from the top left */
+    x: number;
+    y: number;
+}
+
+/** Full screen, click-through overlay drawing the presentation laser pointer */
+export default function Laser() {
+    const [position, setPosition] = useState<LaserPosition>({ x: 0.5, y: 0.5 });
+
+    useEffect(() => {
+        document.documentElement.style.background = "transparent";
+        document.body.style.background = "transparent";
+
+        const unlisten = listen<LaserPosition>("laser-moved", (event) => setPosition(event.payload));
+        return () => {
+            unlisten.then(stop => stop());
+        };
+    }, []);
+
+    return (
+        <div
+            className={styles.dot}
+            style={{ left: `${position.x * 100}vw`, top: `${position.y * 100}vh` }}
+        />
+    );
+}
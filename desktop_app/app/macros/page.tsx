@@ -0,0 +1,161 @@
+"use client";
+
+import { invoke } from "@tauri-apps/api/core";
+import { useEffect, useState } from "react";
+import Link from "next/link";
+import dashboard from "../dashboard/dashboard.module.css";
+import styles from "./macros.module.css";
+
+interface MacroStep {
+    type: "keys" | "text" | "delay" | "mouse_move" | "click" | "scroll" | "command";
+    [field: string]: unknown;
+}
+
+interface Macro {
+    id: string;
+    name: string;
+    steps: MacroStep[];
+}
+
+function describeStep(step: MacroStep): string {
+    switch (step.type) {
+        case "keys": return (step.keys as unknown[]).map(k => typeof k === "string" ? k : JSON.stringify(k)).join("+");
+        case "text": return `"${step.text}"`;
+        case "delay": return `wait ${step.ms}ms`;
+        case "mouse_move": return `move ${step.dx},${step.dy}`;
+        case "click": return `${step.button} click`;
+        case "scroll": return `scroll ${step.amount}`;
+        case "command": return `$ ${step.command}`;
+    }
+}
+
+/** Turns a display name into a macro id, e.g. "Open Mail" -> "open-mail" */
+function toId(name: string): string {
+    return name.trim().toLowerCase().replace(/[^a-z0-9]+/g, "-").replace(/^-|-$/g, "");
+}
+
+export default function Macros() {
+    const [macros, setMacros] = useState<Macro[]>([]);
+    const [selected, setSelected] = useState<string | null>(null);
+    const [recording, setRecording] = useState(false);
+    const [name, setName] = useState("");
+    const [error, setError] = useState<string | null>(null);
+
+    async function refresh() {
+        setMacros(await invoke<Macro[]>("list_macros"));
+    }
+
+    useEffect(() => {
+        refresh().catch(console.error);
+    }, []);
+
+    async function startRecording() {
+        setError(null);
+        await invoke("start_macro_recording");
+        setRecording(true);
+    }
+
+    async function stopRecording() {
+        try {
+            await invoke<Macro>("stop_macro_recording", { id: toId(name), name: name.trim() });
+            setName("");
+            await refresh();
+        } catch (e) {
+            setError(String(e));
+        } finally {
+            setRecording(false);
+        }
+    }
+
+    async function cancelRecording() {
+        await invoke("cancel_macro_recording");
+        setRecording(false);
+    }
+
+    async function deleteMacro() {
+        if (selected === null) return;
+        try {
+            await invoke("delete_macro", { id: selected });
+            setSelected(null);
+            await refresh();
+        } catch (e) {
+            setError(String(e));
+        }
+    }
+
+    return (
+        <main className={dashboard.dashboard}>
+            <header className={dashboard.header}>
+                <div className={dashboard.status}>
+                    {recording
+                        ? <span className={styles.recording}>Recording - use your phone to perform the macro</span>
+                        : <span>Macros</span>}
+                </div>
+                <div className={dashboard.headerActions}>
+                    <Link href="/dashboard" className={dashboard.stopButton}>Back</Link>
+                </div>
+            </header>
+
+            <div className={styles.recorder}>
+                <input
+                    placeholder="Macro name"
+                    value={name}
+                    onChange={(e) => setName(e.target.value)}
+                />
+                {recording ? (
+                    <>
+                        <button
+                            className={dashboard.stopButton}
+                            onClick={stopRecording}
+                            disabled={toId(name) === ""}
+                        >
+                            Stop & Save
+                        </button>
+                        <button className={dashboard.stopButton} onClick={cancelRecording}>
+                            Cancel
+                        </button>
+                    </>
+                ) : (
+                    <button className={dashboard.stopButton} onClick={startRecording}>
+                        Record
+                    </button>
+                )}
+            </div>
+
+            {error && <div className={styles.error}>{error}</div>}
+
+            <div className={dashboard.tableContainer}>
+                <table className={dashboard.table}>
+                    <thead>
+                        <tr>
+                            <th>Id</th>
+                            <th>Name</th>
+                            <th>Steps</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {macros.map(m => (
+                            <tr
+                                key={m.id}
+                                className={selected === m.id ? dashboard.selected : ''}
+                                onClick={() => setSelected(m.id)}
+                            >
+                                <td>{m.id}</td>
+                                <td>{m.name}</td>
+                                <td className={styles.steps}>{m.steps.map(describeStep).join(", ")}</td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+            </div>
+
+            <button
+                onClick={deleteMacro}
+                className={dashboard.removeButton}
+                disabled={selected === null}
+            >
+                Delete Macro
+            </button>
+        </main>
+    );
+}
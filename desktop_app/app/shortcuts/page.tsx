@@ -0,0 +1,188 @@
+"use client";
+
+import { invoke } from "@tauri-apps/api/core";
+import { useEffect, useState } from "react";
+import Link from "next/link";
+import dashboard from "../dashboard/dashboard.module.css";
+import styles from "./shortcuts.module.css";
+
+/** Named key such as "control", or a character key */
+type Key = string | { unicode: string };
+
+type ShortcutAction =
+    | { type: "keys"; keys: Key[] }
+    | { type: "text"; text: string }
+    | { type: "command"; command: string }
+    | { type: "macro"; id: string };
+
+interface Shortcut {
+    id: string;
+    label: string;
+    /** Flutter icon shown on the phone */
+    icon: { code_point: number; font_family: string | null };
+    action: ShortcutAction;
+}
+
+function describeAction(action: ShortcutAction): string {
+    switch (action.type) {
+        case "keys": return action.keys.map(k => typeof k === "string" ? k : k.unicode).join("+");
+        case "text": return `"${action.text}"`;
+        case "command": return `$ ${action.command}`;
+        case "macro": return `macro ${action.id}`;
+    }
+}
+
+/** Builds the action from the form, keys being written as e.g. "control+t" */
+function toAction(type: ShortcutAction["type"], value: string): ShortcutAction {
+    switch (type) {
+        case "keys": return {
+            type,
+            keys: value.split("+").map(k => k.trim()).map(k => k.length === 1 ? { unicode: k } : k.toLowerCase()),
+        };
+        case "text": return { type, text: value };
+        case "command": return { type, command: value };
+        case "macro": return { type, id: value.trim() };
+    }
+}
+
+/** Turns a label into a shortcut id, e.g. "Open Mail" -> "open-mail" */
+function toId(label: string): string {
+    return label.trim().toLowerCase().replace(/[^a-z0-9]+/g, "-").replace(/^-|-$/g, "");
+}
+
+export default function Shortcuts() {
+    const [shortcuts, setShortcuts] = useState<Shortcut[]>([]);
+    const [selected, setSelected] = useState<string | null>(null);
+    const [label, setLabel] = useState("");
+    const [icon, setIcon] = useState("e5d2");
+    const [actionType, setActionType] = useState<ShortcutAction["type"]>("keys");
+    const [value, setValue] = useState("");
+    const [error, setError] = useState<string | null>(null);
+
+    async function refresh() {
+        setShortcuts(await invoke<Shortcut[]>("list_shortcuts"));
+    }
+
+    useEffect(() => {
+        refresh().catch(console.error);
+    }, []);
+
+    async function run(command: string, args: Record<string, unknown>) {
+        setError(null);
+        try {
+            await invoke(command, args);
+            await refresh();
+        } catch (e) {
+            setError(String(e));
+        }
+    }
+
+    async function saveShortcut() {
+        const shortcut: Shortcut = {
+            id: toId(label),
+            label: label.trim(),
+            icon: { code_point: parseInt(icon, 16) || 0, font_family: null },
+            action: toAction(actionType, value),
+        };
+        await run("save_shortcut", { shortcut });
+        setLabel("");
+        setValue("");
+    }
+
+    async function move(id: string, offset: number) {
+        const ids = shortcuts.map(s => s.id);
+        const from = ids.indexOf(id);
+        const to = from + offset;
+        if (to < 0 || to >= ids.length) return;
+        [ids[from], ids[to]] = [ids[to], ids[from]];
+        await run("reorder_shortcuts", { ids });
+    }
+
+    async function deleteShortcut() {
+        if (selected === null) return;
+        await run("delete_shortcut", { id: selected });
+        setSelected(null);
+    }
+
+    return (
+        <main className={dashboard.dashboard}>
+            <header className={dashboard.header}>
+                <div className={dashboard.status}>
+                    <span>Shortcuts - shown on every paired phone</span>
+                </div>
+                <div className={dashboard.headerActions}>
+                    <Link href="/dashboard" className={dashboard.stopButton}>Back</Link>
+                </div>
+            </header>
+
+            <div className={styles.editor}>
+                <input placeholder="Label" value={label} onChange={(e) => setLabel(e.target.value)} />
+                <input
+                    className={styles.icon}
+                    placeholder="Icon (hex)"
+                    title="Material icon code point, e.g. e5d2"
+                    value={icon}
+                    onChange={(e) => setIcon(e.target.value)}
+                />
+                <select value={actionType} onChange={(e) => setActionType(e.target.value as ShortcutAction["type"])}>
+                    <option value="keys">Keys</option>
+                    <option value="text">Text</option>
+                    <option value="command">Command</option>
+                    <option value="macro">Macro</option>
+                </select>
+                <input
+                    placeholder={actionType === "keys" ? "control+shift+t" : actionType === "macro" ? "Macro id" : ""}
+                    value={value}
+                    onChange={(e) => setValue(e.target.value)}
+                />
+                <button
+                    className={dashboard.stopButton}
+                    onClick={saveShortcut}
+                    disabled={toId(label) === "" || value.trim() === ""}
+                >
+                    Save
+                </button>
+            </div>
+
+            {error && <div className={styles.error}>{error}</div>}
+
+            <div className={dashboard.tableContainer}>
+                <table className={dashboard.table}>
+                    <thead>
+                        <tr>
+                            <th>Id</th>
+                            <th>Label</th>
+                            <th>Action</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {shortcuts.map(s => (
+                            <tr
+                                key={s.id}
+                                className={selected === s.id ? dashboard.selected : ''}
+                                onClick={() => setSelected(s.id)}
+                            >
+                                <td>{s.id}</td>
+                                <td>{s.label}</td>
+                                <td className={styles.action}>{describeAction(s.action)}</td>
+                                <td>
+                                    <button className={styles.move} onClick={() => move(s.id, -1)}>▲</button>
+                                    <button className={styles.move} onClick={() => move(s.id, 1)}>▼</button>
+                                </td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+            </div>
+
+            <button
+                onClick={deleteShortcut}
+                className={dashboard.removeButton}
+                disabled={selected === null}
+            >
+                Delete Shortcut
+            </button>
+        </main>
+    );
+}
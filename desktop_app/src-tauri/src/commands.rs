@@ -1,12 +1,16 @@
 use std::sync::{Arc, Mutex};
 
-use server::{MobileController, Server, ServerConfig, ServerEvent, ServerHandler};
-use tauri::Emitter;
+use server::{
+    ClientInfo, ConfigUpdated, FileTransfers, MacroLibrary, MobileController, Server, ServerConfig,
+    ServerEvent, ServerHandler, ServerStatus, ShortcutCatalog,
+};
+use tauri::{Emitter, Manager};
 use tokio::sync::broadcast;
 
-use crate::TCP_PORT;
+use crate::laser::WindowLaserOverlay;
 
-pub type SharedCommunicator = Arc<Mutex<Option<ServerHandler>>>;
+/// The handler is shared so async commands can await it without holding the lock
+pub type SharedCommunicator = Arc<Mutex<Option<Arc<ServerHandler>>>>;
 
 /// Result type for server initialization
 #[derive(Debug, Clone, serde::Serialize)]
@@ -25,31 +29,27 @@ pub fn is_server_initialized(state: tauri::State<'_, SharedCommunicator>) -> boo
 /// Initialize the server - can be called multiple times for retry
 #[tauri::command]
 pub fn init_server(
-    _app_handle: tauri::AppHandle,
+    app_handle: tauri::AppHandle,
     state: tauri::State<'_, SharedCommunicator>,
+    macros: tauri::State<'_, MacroLibrary>,
+    shortcuts: tauri::State<'_, ShortcutCatalog>,
+    files: tauri::State<'_, FileTransfers>,
 ) -> InitResult {
     let mut guard = state.lock().unwrap();
 
     // Already initialized - but verify network is still available
-    if let Some(handler) = guard.as_mut() {
+    if let Some(handler) = guard.as_ref() {
         // Check if network is available
         if let Err(e) = local_ip_address::local_ip() {
             // Network went away after initialization. Terminate the old server instance.
-            if let Err(term_err) = handler.terminate_server() {
-                eprintln!(
-                    "Failed to terminate server after network loss: {:?}",
-                    term_err
-                );
-            }
-            // Wait for termination to complete
-            match handler.receive_response() {
-                Ok(server::ServerResponse::ServerTerminated(_)) => {
-                    println!("Server terminated due to network loss.");
+            match tauri::async_runtime::block_on(handler.terminate_server()) {
+                Ok(_) => println!("Server terminated due to network loss."),
+                Err(term_err) => {
+                    eprintln!(
+                        "Failed to terminate server after network loss: {}",
+                        term_err
+                    )
                 }
-                Err(recv_err) => {
-                    eprintln!("Failed to receive termination confirmation: {:?}", recv_err);
-                }
-                _ => {}
             }
 
             *guard = None; // Clear the old handler
@@ -77,7 +77,11 @@ pub fn init_server(
 
     // Try to create the controller (requires display access on Linux)
     let controller = match MobileController::new() {
-        Ok(c) => c,
+        Ok(c) => c
+            .with_macro_library(macros.inner().clone())
+            .with_shortcut_catalog(shortcuts.inner().clone())
+            .with_file_transfers(files.inner().clone())
+            .with_laser_overlay(Box::new(WindowLaserOverlay::new(app_handle))),
         Err(e) => {
             return InitResult {
                 success: false,
@@ -86,11 +90,11 @@ pub fn init_server(
         }
     };
 
-    let config = ServerConfig::new(TCP_PORT as usize, 10);
+    let config = ServerConfig::load();
 
     // Now safe to start since we verified network is available
     let handler = Server::start(config, controller);
-    *guard = Some(handler);
+    *guard = Some(Arc::new(handler));
 
     InitResult {
         success: true,
@@ -98,111 +102,103 @@ pub fn init_server(
     }
 }
 
-#[tauri::command]
-pub fn start_server(
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, SharedCommunicator>,
-) -> String {
-    let mut guard = state.lock().unwrap();
+/// The handler of the initialized server
+fn current_handler(state: &SharedCommunicator) -> Option<Arc<ServerHandler>> {
+    state.lock().unwrap().clone()
+}
 
-    let handler = match guard.as_mut() {
-        Some(h) => h,
-        None => return "Server not initialized. Call init_server first.".to_string(),
+#[tauri::command]
+pub async fn start_server(app_handle: tauri::AppHandle) -> String {
+    let Some(handler) = current_handler(&app_handle.state::<SharedCommunicator>()) else {
+        return "Server not initialized. Call init_server first.".to_string();
     };
 
-    if let Err(e) = handler.start_server() {
-        return format!("Failed to start server: {:?}", e);
-    }
-
-    match handler.receive_response() {
-        Ok(server::ServerResponse::ServerStarted(_)) => {
+    match handler.start_server().await {
+        Ok(_) => {
             spawn_event_listener(handler.subscribe_events(), app_handle);
             "Server initialized successfully.".to_string()
         }
-        Ok(resp) => {
-            format!("Unexpected response: {:?}", resp)
-        }
-        Err(e) => {
-            format!("Failed to receive response: {:?}", e)
-        }
+        Err(e) => format!("Failed to start server: {}", e),
     }
 }
 
 #[tauri::command]
-pub fn stop_server(state: tauri::State<'_, SharedCommunicator>) -> String {
-    let mut guard = state.lock().unwrap();
-
-    let handler = match guard.as_mut() {
-        Some(h) => h,
-        None => return "Server not initialized.".to_string(),
+pub async fn stop_server(app_handle: tauri::AppHandle) -> String {
+    let Some(handler) = current_handler(&app_handle.state::<SharedCommunicator>()) else {
+        return "Server not initialized.".to_string();
     };
 
-    if let Err(e) = handler.stop_server() {
-        return format!("Failed to stop server: {:?}", e);
-    }
-
-    match handler.receive_response() {
-        Ok(server::ServerResponse::ServerStopped(_)) => "Server stopped successfully.".to_string(),
-        Ok(resp) => {
-            format!("Unexpected response: {:?}", resp)
-        }
-        Err(e) => {
-            format!("Failed to receive response: {:?}", e)
-        }
+    match handler.stop_server().await {
+        Ok(_) => "Server stopped successfully.".to_string(),
+        Err(e) => format!("Failed to stop server: {}", e),
     }
 }
 
 /// Called on app exit to fully terminate the server
 pub fn terminate_server(state: &SharedCommunicator) {
-    let mut guard = state.lock().unwrap();
-
-    let handler = match guard.as_mut() {
-        Some(h) => h,
-        None => return, // Server was never initialized, nothing to terminate
+    let Some(handler) = current_handler(state) else {
+        return; // Server was never initialized, nothing to terminate
     };
 
-    if let Err(e) = handler.terminate_server() {
-        eprintln!("Failed to terminate server: {:?}", e);
-        return;
+    match tauri::async_runtime::block_on(handler.terminate_server()) {
+        Ok(_) => println!("Server terminated successfully."),
+        Err(e) => eprintln!("Failed to terminate server: {}", e),
     }
+}
 
-    match handler.receive_response() {
-        Ok(server::ServerResponse::ServerTerminated(_)) => {
-            println!("Server terminated successfully.");
-        }
-        Ok(resp) => {
-            eprintln!("Unexpected response during termination: {:?}", resp);
-        }
-        Err(e) => {
-            eprintln!("Failed to receive termination response: {:?}", e);
-        }
+#[tauri::command]
+pub async fn remove_client(app_handle: tauri::AppHandle, client_id: usize) -> String {
+    let Some(handler) = current_handler(&app_handle.state::<SharedCommunicator>()) else {
+        return "Server not initialized.".to_string();
+    };
+
+    match handler.terminate_client(client_id).await {
+        Ok(_) => format!("Client {} removed successfully.", client_id),
+        Err(e) => format!("Failed to remove client: {}", e),
     }
 }
 
+/// The connected phones, for the UI to resync its list
 #[tauri::command]
-pub fn remove_client(state: tauri::State<'_, SharedCommunicator>, client_id: usize) -> String {
-    let mut guard = state.lock().unwrap();
+pub async fn list_clients(
+    state: tauri::State<'_, SharedCommunicator>,
+) -> Result<Vec<ClientInfo>, String> {
+    let handler = current_handler(&state).ok_or("Server not initialized.")?;
+    handler.list_clients().await
+}
 
-    let handler = match guard.as_mut() {
-        Some(h) => h,
-        None => return "Server not initialized.".to_string(),
-    };
+#[tauri::command]
+pub async fn get_server_status(
+    state: tauri::State<'_, SharedCommunicator>,
+) -> Result<ServerStatus, String> {
+    let handler = current_handler(&state).ok_or("Server not initialized.")?;
+    handler.get_status().await
+}
 
-    if let Err(e) = handler.terminate_client(client_id) {
-        return format!("Failed to remove client: {:?}", e);
+/// The config of the running server, or the saved one before it starts
+#[tauri::command]
+pub async fn get_server_config(
+    state: tauri::State<'_, SharedCommunicator>,
+) -> Result<ServerConfig, String> {
+    match current_handler(&state) {
+        Some(handler) => handler.get_config().await,
+        None => Ok(ServerConfig::load()),
     }
+}
 
-    match handler.receive_response() {
-        Ok(server::ServerResponse::ClientTerminated(_)) => {
-            format!("Client {} removed successfully.", client_id)
-        }
-        Ok(resp) => {
-            format!("Unexpected response: {:?}", resp)
-        }
-        Err(e) => {
-            format!("Failed to receive response: {:?}", e)
-        }
-    }
+/// Applies the config to the running server, then saves it for the next start.
+/// Nothing is saved if the server can't apply it, e.g. when the port is taken.
+#[tauri::command]
+pub async fn update_server_config(
+    state: tauri::State<'_, SharedCommunicator>,
+    config: ServerConfig,
+) -> Result<Option<ConfigUpdated>, String> {
+    let updated = match current_handler(&state) {
+        Some(handler) => Some(handler.update_config(config.clone()).await?),
+        None => None,
+    };
+    config.save()?;
+    Ok(updated)
 }
 
 fn spawn_event_listener(
@@ -225,6 +221,8 @@ fn spawn_event_listener(
                         ServerEvent::ClientUpdated(client_info) => {
                             app_handle.emit("client-updated", client_info)
                         }
+                        ServerEvent::FileReceived(file) => app_handle.emit("file-received", file),
+                        ServerEvent::FileSent(file) => app_handle.emit("file-sent", file),
                     };
 
                     if let Err(e) = result {
@@ -238,6 +236,10 @@ fn spawn_event_listener(
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
                     eprintln!("Event listener lagged, missed {} events", n);
+                    // the client list may be stale, the UI asks for it again
+                    if let Err(e) = app_handle.emit("clients-resync", ()) {
+                        eprintln!("Failed to emit event: {:?}", e);
+                    }
                 }
             }
         }
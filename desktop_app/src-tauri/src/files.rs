@@ -0,0 +1,33 @@
+//! Tauri commands to send files to a phone and pick where received files go.
+//!
+//! The `FileTransfers` are created once at startup and shared with the controller,
+//! which runs the transfers. Received and sent files are reported through the
+//! `file-received` and `file-sent` events.
+
+use std::path::PathBuf;
+
+use server::FileTransfers;
+
+/// Offers the file to the phone, returning the transfer id
+#[tauri::command]
+pub fn send_file(
+    files: tauri::State<'_, FileTransfers>,
+    client_id: usize,
+    path: String,
+) -> Result<u32, String> {
+    files.send_file(client_id, &PathBuf::from(path))
+}
+
+#[tauri::command]
+pub fn get_download_dir(files: tauri::State<'_, FileTransfers>) -> Result<PathBuf, String> {
+    files.download_dir()
+}
+
+/// `None` goes back to the user's downloads directory
+#[tauri::command]
+pub fn set_download_dir(
+    files: tauri::State<'_, FileTransfers>,
+    path: Option<String>,
+) -> Result<(), String> {
+    files.set_download_dir(path.map(PathBuf::from))
+}
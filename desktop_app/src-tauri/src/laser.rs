@@ -0,0 +1,68 @@
+//! Draws the presentation laser pointer in a transparent, click-through window
+//! covering the screen. The window is created the first time the laser is shown.
+
+use server::LaserOverlay;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+const LASER_WINDOW: &str = "laser";
+
+#[derive(Clone, serde::Serialize)]
+struct LaserPosition {
+    x: f32,
+    y: f32,
+}
+
+pub struct WindowLaserOverlay {
+    app: AppHandle,
+}
+
+impl WindowLaserOverlay {
+    pub fn new(app: AppHandle) -> Self {
+        Self { app }
+    }
+
+    fn window(&self) -> Result<WebviewWindow, String> {
+        if let Some(window) = self.app.get_webview_window(LASER_WINDOW) {
+            return Ok(window);
+        }
+
+        let builder =
+            WebviewWindowBuilder::new(&self.app, LASER_WINDOW, WebviewUrl::App("laser".into()))
+                .title("Laser pointer")
+                .decorations(false)
+                .always_on_top(true)
+                .skip_taskbar(true)
+                .fullscreen(true)
+                .focused(false)
+                .shadow(false)
+                .visible(false);
+        // transparent windows need the private API on macOS
+        #[cfg(not(target_os = "macos"))]
+        let builder = builder.transparent(true);
+
+        let window = builder.build().map_err(|e| e.to_string())?;
+        window
+            .set_ignore_cursor_events(true)
+            .map_err(|e| e.to_string())?;
+        Ok(window)
+    }
+}
+
+impl LaserOverlay for WindowLaserOverlay {
+    fn show(&mut self, x: f32, y: f32) -> Result<(), String> {
+        let window = self.window()?;
+        if !window.is_visible().unwrap_or(false) {
+            window.show().map_err(|e| e.to_string())?;
+        }
+        self.app
+            .emit_to(LASER_WINDOW, "laser-moved", LaserPosition { x, y })
+            .map_err(|e| e.to_string())
+    }
+
+    fn hide(&mut self) -> Result<(), String> {
+        match self.app.get_webview_window(LASER_WINDOW) {
+            Some(window) => window.hide().map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+}
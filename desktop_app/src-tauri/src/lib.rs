@@ -1,7 +1,12 @@
 pub mod commands;
+pub mod files;
+pub mod laser;
+pub mod macros;
+pub mod shortcuts;
 
 use std::sync::{Arc, Mutex};
 
+use server::{FileTransfers, MacroLibrary, ShortcutCatalog};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{TrayIconBuilder, TrayIconEvent},
@@ -11,8 +16,6 @@ use tauri_plugin_autostart::MacosLauncher;
 
 use commands::SharedCommunicator;
 
-pub const TCP_PORT: u16 = 7878;
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let app = tauri::Builder::default()
@@ -25,6 +28,11 @@ pub fn run() {
             let shared_comm: SharedCommunicator = Arc::new(Mutex::new(None));
             app.manage(shared_comm);
 
+            // Macros, shortcuts and file transfers are shared by every controller the app creates
+            app.manage(MacroLibrary::load());
+            app.manage(ShortcutCatalog::load());
+            app.manage(FileTransfers::load());
+
             // System Tray Setup
             let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let show_i = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
@@ -73,6 +81,23 @@ pub fn run() {
             commands::start_server,
             commands::stop_server,
             commands::remove_client,
+            commands::list_clients,
+            commands::get_server_status,
+            commands::get_server_config,
+            commands::update_server_config,
+            macros::list_macros,
+            macros::save_macro,
+            macros::delete_macro,
+            macros::start_macro_recording,
+            macros::stop_macro_recording,
+            macros::cancel_macro_recording,
+            shortcuts::list_shortcuts,
+            shortcuts::save_shortcut,
+            shortcuts::delete_shortcut,
+            shortcuts::reorder_shortcuts,
+            files::send_file,
+            files::get_download_dir,
+            files::set_download_dir,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
@@ -0,0 +1,41 @@
+//! Tauri commands to manage the server macros and record new ones.
+//!
+//! The `MacroLibrary` is created once at startup and handed to every controller the
+//! app creates, so edits here are immediately visible to the phones.
+
+use server::{Macro, MacroLibrary};
+
+#[tauri::command]
+pub fn list_macros(library: tauri::State<'_, MacroLibrary>) -> Vec<Macro> {
+    library.macros()
+}
+
+#[tauri::command]
+pub fn save_macro(library: tauri::State<'_, MacroLibrary>, macro_def: Macro) -> Result<(), String> {
+    library.save_macro(macro_def)
+}
+
+#[tauri::command]
+pub fn delete_macro(library: tauri::State<'_, MacroLibrary>, id: String) -> Result<(), String> {
+    library.remove(&id)
+}
+
+/// Starts capturing what the phones do until `stop_macro_recording`
+#[tauri::command]
+pub fn start_macro_recording(library: tauri::State<'_, MacroLibrary>) {
+    library.start_recording();
+}
+
+#[tauri::command]
+pub fn stop_macro_recording(
+    library: tauri::State<'_, MacroLibrary>,
+    id: String,
+    name: String,
+) -> Result<Macro, String> {
+    library.stop_recording(&id, &name)
+}
+
+#[tauri::command]
+pub fn cancel_macro_recording(library: tauri::State<'_, MacroLibrary>) {
+    library.cancel_recording();
+}
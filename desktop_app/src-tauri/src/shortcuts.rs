@@ -0,0 +1,35 @@
+//! Tauri commands to edit the shortcut catalog shown on every phone.
+//!
+//! Like the macros, the `ShortcutCatalog` is created once at startup and shared with
+//! the controller, which pushes each edit to the connected phones.
+
+use server::{Shortcut, ShortcutCatalog};
+
+#[tauri::command]
+pub fn list_shortcuts(catalog: tauri::State<'_, ShortcutCatalog>) -> Vec<Shortcut> {
+    catalog.shortcuts()
+}
+
+#[tauri::command]
+pub fn save_shortcut(
+    catalog: tauri::State<'_, ShortcutCatalog>,
+    shortcut: Shortcut,
+) -> Result<(), String> {
+    catalog.save_shortcut(shortcut)
+}
+
+#[tauri::command]
+pub fn delete_shortcut(
+    catalog: tauri::State<'_, ShortcutCatalog>,
+    id: String,
+) -> Result<(), String> {
+    catalog.remove(&id)
+}
+
+#[tauri::command]
+pub fn reorder_shortcuts(
+    catalog: tauri::State<'_, ShortcutCatalog>,
+    ids: Vec<String>,
+) -> Result<(), String> {
+    catalog.reorder(&ids)
+}